@@ -602,7 +602,7 @@ function populateRunHeader(data) {
     document.querySelector("#run-name").innerText = name;
     document.querySelector("#run-description").innerText = run.description;
 
-    const total_num = run.num_optimal + run.num_suboptimal + run.num_infeasible + run.num_error + run.num_timeout + run.num_incomplete;
+    const total_num = run.num_optimal + run.num_best + run.num_suboptimal + run.num_infeasible + run.num_error + run.num_timeout + run.num_incomplete;
     const total_width = (run.num_scheduled ? run.num_scheduled : total_num);
 
     let stats_tbody = document.querySelector("#run-stats tbody");
@@ -639,6 +639,7 @@ function populateRunHeader(data) {
     }
 
     add_row("Optimal instances", "optimal", "optimal");
+    add_row("Best known instances", "best", "optimal");
     add_row("Suboptimal instances", "suboptimal", "");
     add_row("No/incomplete sol.", "incomplete", "warning");
     add_row("Timeout instances", "timeout", "warning");
@@ -14,9 +14,60 @@ document.querySelector("h1").innerText = `Solver ${SOLVER}`;
 
 const apiBase = '/api/';
 const apiSolverRunList = apiBase + `solver_run/list?solver=${SOLVER}`;
-const apiSolverRunAnnotate = apiBase + `solver_run/annotate?solver=${SOLVER}`;
+const apiSolverRunAnnotate = apiBase + `solver_run/annotate`;
 const apiSolverRunPerformance = apiBase + `solver_run/performance`;
 
+// registered solvers require their key; it is asked for once and kept for the session
+const solverKeyItem = `solver-key-${SOLVER}`;
+
+function sendAnnotation(run, fields) {
+    return fetch(apiSolverRunAnnotate, {
+        method: 'POST',
+        headers: {
+            'Accept': 'application/json',
+            'Content-Type': 'application/json'
+        },
+        body: JSON.stringify({ solver: SOLVER, run: run, key: sessionStorage.getItem(solverKeyItem), ...fields })
+    });
+}
+
+// resolves to whether the annotation was stored; failures are reported to the user
+function annotateRun(run, fields) {
+    return sendAnnotation(run, fields)
+        .then(response => {
+            if (response.status != 403) {
+                return response;
+            }
+
+            const key = prompt("Enter the key of this solver");
+            if (!key) {
+                return response;
+            }
+
+            sessionStorage.setItem(solverKeyItem, key);
+            return sendAnnotation(run, fields);
+        })
+        .then(response => {
+            if (response.ok) {
+                return true;
+            }
+
+            return response.json()
+                .catch(() => ({ message: response.statusText }))
+                .then(error => {
+                    if (response.status == 403) {
+                        sessionStorage.removeItem(solverKeyItem);
+                    }
+                    alert(`Cannot annotate run: ${error.message}`);
+                    return false;
+                });
+        })
+        .catch(error => {
+            alert(`Cannot annotate run: ${error}`);
+            return false;
+        });
+}
+
 function getRunUuid(elem) {
     while (elem != document) {
         if (elem.run_uuid) {
@@ -271,15 +322,19 @@ function populateRuns(data) {
                 const is_hidden = run_elem.classList.contains("hidden");
                 const new_value = !is_hidden;
 
-                if (new_value) {
-                    run_elem.classList.add("hidden");
-                    e.target.innerText = "[unhide]";
-                } else {
-                    run_elem.classList.remove("hidden");
-                    e.target.innerText = "[hide]";
-                }
+                annotateRun(run, { hide: new_value }).then(ok => {
+                    if (!ok) {
+                        return;
+                    }
 
-                fetch(`${apiSolverRunAnnotate}&run=${run}&hide=${new_value}`);
+                    if (new_value) {
+                        run_elem.classList.add("hidden");
+                        e.target.innerText = "[unhide]";
+                    } else {
+                        run_elem.classList.remove("hidden");
+                        e.target.innerText = "[hide]";
+                    }
+                });
             });
 
             add_tool("edit_name", " [change name]", (e) => {
@@ -289,8 +344,11 @@ function populateRuns(data) {
                 const new_value = prompt("Enter new name", old_value);
 
                 if (new_value && new_value != old_value) {
-                    fetch(`${apiSolverRunAnnotate}&run=${run}&name=${encodeURIComponent(new_value)}`);
-                    h4.innerText = new_value;
+                    annotateRun(run, { name: new_value }).then(ok => {
+                        if (ok) {
+                            h4.innerText = new_value;
+                        }
+                    });
                 }
             });
 
@@ -301,8 +359,11 @@ function populateRuns(data) {
                 const new_value = prompt("Enter new description", old_value);
 
                 if (new_value && new_value != old_value) {
-                    fetch(`${apiSolverRunAnnotate}&run=${run}&description=${encodeURIComponent(new_value)}`);
-                    p.innerText = new_value;
+                    annotateRun(run, { description: new_value }).then(ok => {
+                        if (ok) {
+                            p.innerText = new_value;
+                        }
+                    });
                 }
             });
 
@@ -330,7 +391,7 @@ function populateRuns(data) {
         ////
 
         const total_num =
-            run.num_optimal + run.num_suboptimal + run.num_infeasible + run.num_error + run.num_timeout + run.num_incomplete;
+            run.num_optimal + run.num_best + run.num_suboptimal + run.num_infeasible + run.num_error + run.num_timeout + run.num_incomplete;
 
         run.num_error + run.num_infeasible + run.num_valid + run.num_optimal;
         const total_width = (run.num_scheduled ? run.num_scheduled : total_num);
@@ -366,6 +427,7 @@ function populateRuns(data) {
         }
 
         add_block(run.num_optimal, ["bg-success"], "Opt:<br/>$");
+        add_block(run.num_best, ["bg-success", "opacity-75"], "Best:<br/>$");
         add_block(run.num_suboptimal, [], "Subopt:<br/> $");
         add_block(run.num_infeasible, ["bg-danger"], "Infeasible:<br/> $");
         add_block(run.num_timeout, ["bg-warning"], "Timeout:<br/> $");
@@ -399,6 +461,7 @@ function populateRuns(data) {
             right_info.appendChild(elem);
 
             add_field("Optimal", "optimal", "text-optimal");
+            add_field("Best", "best", "text-optimal");
             add_field("Suboptimal", "suboptimal", "text-suboptimal");
             add_field("Infeasible", "infeasible", "text-infeasible");
             add_field("Incomplete", "incomplete", "text-warning");
@@ -14,7 +14,7 @@ cd /srv
 mkdir -p /active
 
 # build and execute the server
-cargo build --bin server --release $STRIDE_BUILD
+cargo build --bin server --release
 mv /target/release/server /active/server
 rm -rf /target
 
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE Instance ADD COLUMN lower_bound INT UNSIGNED;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE Instance ADD COLUMN difficulty DOUBLE;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE
+    IF NOT EXISTS ApiToken (
+        tid INT AUTO_INCREMENT PRIMARY KEY,
+        token_hash BINARY(20) NOT NULL,
+        name VARCHAR(255) NOT NULL,
+        scopes VARCHAR(255) NOT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+        revoked_at TIMESTAMP NULL,
+
+        UNIQUE INDEX `idx_token_hash` (`token_hash`)
+    );
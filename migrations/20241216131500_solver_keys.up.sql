@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE
+    IF NOT EXISTS Solver (
+        solver_uuid BINARY(16) PRIMARY KEY,
+        key_hash BINARY(20) NOT NULL,
+        name VARCHAR(255),
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+    );
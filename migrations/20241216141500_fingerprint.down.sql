@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE Instance
+    DROP INDEX `idx_fingerprint`,
+    DROP COLUMN fingerprint;
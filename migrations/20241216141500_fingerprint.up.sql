@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE Instance
+    ADD COLUMN fingerprint BINARY(20),
+    ADD INDEX `idx_fingerprint` (`fingerprint`);
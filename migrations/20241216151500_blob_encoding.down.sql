@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE InstanceData
+    DROP COLUMN encoding;
+
+ALTER TABLE SolutionData
+    DROP COLUMN encoding;
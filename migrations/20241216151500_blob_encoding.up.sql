@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE InstanceData
+    ADD COLUMN encoding TINYINT UNSIGNED NOT NULL DEFAULT 0;
+
+ALTER TABLE SolutionData
+    ADD COLUMN encoding TINYINT UNSIGNED NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE Instance DROP COLUMN publish_best_solution;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS ScheduledInstance;
+ALTER TABLE SolverRun DROP COLUMN lease_seconds;
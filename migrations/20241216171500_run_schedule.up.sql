@@ -0,0 +1,18 @@
+-- Add up migration script here
+ALTER TABLE SolverRun ADD COLUMN lease_seconds INT UNSIGNED;
+
+CREATE TABLE
+    IF NOT EXISTS ScheduledInstance (
+        sr_uuid BINARY(16) NOT NULL,
+        instance_iid INT NOT NULL,
+        position INT UNSIGNED NOT NULL,
+
+        leased_until TIMESTAMP NULL,
+        num_leases INT UNSIGNED NOT NULL DEFAULT 0,
+        completed_at TIMESTAMP NULL,
+
+        PRIMARY KEY (sr_uuid, instance_iid),
+        INDEX `idx_sr_uuid_position` (`sr_uuid`, `position`),
+        FOREIGN KEY (sr_uuid) REFERENCES SolverRun(run_uuid),
+        FOREIGN KEY (instance_iid) REFERENCES Instance(iid)
+    );
@@ -0,0 +1,9 @@
+-- Add down migration script here
+ALTER TABLE SolverRun
+    DROP COLUMN state,
+    DROP COLUMN solver_version,
+    DROP COLUMN cpu_model,
+    DROP COLUMN timeout_seconds,
+    DROP COLUMN started_at,
+    DROP COLUMN last_heartbeat,
+    DROP COLUMN finished_at;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+ALTER TABLE SolverRun
+    ADD COLUMN state INT UNSIGNED,
+    ADD COLUMN solver_version VARCHAR(255),
+    ADD COLUMN cpu_model VARCHAR(255),
+    ADD COLUMN timeout_seconds DOUBLE,
+    ADD COLUMN started_at TIMESTAMP NULL,
+    ADD COLUMN last_heartbeat TIMESTAMP NULL,
+    ADD COLUMN finished_at TIMESTAMP NULL;
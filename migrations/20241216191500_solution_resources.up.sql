@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TABLE
+    IF NOT EXISTS SolutionResources (
+        solution_sid INT PRIMARY KEY,
+
+        peak_memory_bytes BIGINT UNSIGNED,
+        cpu_seconds DOUBLE,
+        wall_seconds DOUBLE,
+        exit_code INT,
+        term_signal INT,
+        stderr_tail TEXT,
+
+        FOREIGN KEY (solution_sid) REFERENCES Solution(sid) ON DELETE CASCADE
+    );
@@ -0,0 +1,9 @@
+-- Add down migration script here
+ALTER TABLE SolverRun
+    DROP INDEX `idx_git_commit`,
+    DROP COLUMN git_commit,
+    DROP COLUMN build_flags,
+    DROP COLUMN command_line,
+    DROP COLUMN parameters,
+    DROP COLUMN cpu_cores,
+    DROP COLUMN memory_bytes;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+ALTER TABLE SolverRun
+    ADD COLUMN git_commit VARCHAR(64),
+    ADD COLUMN build_flags TEXT,
+    ADD COLUMN command_line TEXT,
+    ADD COLUMN parameters JSON,
+    ADD COLUMN cpu_cores INT UNSIGNED,
+    ADD COLUMN memory_bytes BIGINT UNSIGNED,
+    ADD INDEX `idx_git_commit` (`git_commit`);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE Instance
+    DROP INDEX `idx_difficulty_outdated`,
+    DROP COLUMN difficulty_outdated;
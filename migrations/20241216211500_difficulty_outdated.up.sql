@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE Instance
+    ADD COLUMN difficulty_outdated BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD INDEX `idx_difficulty_outdated` (`difficulty_outdated`);
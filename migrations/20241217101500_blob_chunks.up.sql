@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE
+    IF NOT EXISTS BlobChunk (
+        kind VARCHAR(16) NOT NULL,
+        hash BINARY(20) NOT NULL,
+        seq INT UNSIGNED NOT NULL,
+        data MEDIUMBLOB NOT NULL,
+
+        PRIMARY KEY (kind, hash, seq)
+    );
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE
+    IF NOT EXISTS BlobPending (
+        kind VARCHAR(16) NOT NULL,
+        hash BINARY(20) NOT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+        PRIMARY KEY (kind, hash),
+        INDEX `idx_created_at` (`created_at`)
+    );
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE Instance
+    DROP COLUMN diameter_lower,
+    DROP COLUMN diameter_upper;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE Instance
+    ADD COLUMN diameter_lower INT UNSIGNED,
+    ADD COLUMN diameter_upper INT UNSIGNED;
@@ -1,4 +1,4 @@
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
 use axum::extract::Host;
 use axum::handler::HandlerWithoutStateExt;
@@ -10,7 +10,15 @@ use axum::{
 use dotenv::dotenv;
 use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
 
-use stride_server::server::{app_state::AppState, router::create_router};
+use stride_server::server::{
+    app_state::AppState,
+    auth::{self, Scope},
+    blob_gc::collect_orphaned_blobs_periodically,
+    blob_reencode::reencode_blobs,
+    blob_store::{BlobStore, FilesystemBlobStore, MySqlBlobStore, S3BlobStore, S3Config},
+    handlers::instance_difficulty::refresh_outdated_difficulties,
+    router::create_router,
+};
 
 use structopt::StructOpt;
 use tracing::{error, info};
@@ -118,14 +126,133 @@ struct Opts {
 
     #[structopt(long, default_value = "50")]
     mysql_max_connections: u32,
+
+    /// Only accept solution uploads carrying a token with the solution-upload scope
+    #[structopt(long)]
+    require_solution_upload_token: bool,
+
+    /// Reject uploads and annotations for solvers that were not registered with a key
+    #[structopt(long)]
+    reject_unregistered_solvers: bool,
+
+    /// Allow downloading the best-known solution of instances that do not override it
+    #[structopt(long)]
+    publish_best_solutions: bool,
+
+    /// Only publish best-known solutions that were uploaded at least this many hours ago
+    #[structopt(long, default_value = "0")]
+    best_solution_embargo_hours: u64,
+
+    /// Compress instance and solution blobs stored in the legacy plain format in the background
+    #[structopt(long)]
+    reencode_blobs: bool,
+
+    /// Keep instance and solution payloads as files below this directory instead of MySQL
+    #[structopt(long, parse(from_os_str), conflicts_with = "s3-bucket")]
+    blob_dir: Option<PathBuf>,
+
+    /// Keep payloads in this bucket of an S3-compatible service; credentials are read from
+    /// AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY
+    #[structopt(long, requires = "s3-endpoint")]
+    s3_bucket: Option<String>,
+
+    #[structopt(long)]
+    s3_endpoint: Option<String>,
+
+    #[structopt(long, default_value = "us-east-1")]
+    s3_region: String,
+
+    /// Directory for uploads that exceed the memory bound; defaults to the system's
+    #[structopt(long, parse(from_os_str))]
+    temp_dir: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+fn create_blob_store(opts: &Opts, db: &MySqlPool) -> anyhow::Result<Arc<dyn BlobStore>> {
+    if let Some(dir) = &opts.blob_dir {
+        info!("Store blobs in {dir:?}");
+        return Ok(Arc::new(FilesystemBlobStore::new(dir)));
+    }
+
+    if let (Some(bucket), Some(endpoint)) = (&opts.s3_bucket, &opts.s3_endpoint) {
+        info!("Store blobs in bucket {bucket} of {endpoint}");
+        return Ok(Arc::new(S3BlobStore::new(S3Config::from_env(
+            endpoint.clone(),
+            bucket.clone(),
+            opts.s3_region.clone(),
+        )?)?));
+    }
+
+    Ok(Arc::new(MySqlBlobStore::new(db.clone())))
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Manage the bearer tokens used to access protected endpoints
+    Token(TokenCommand),
+}
+
+#[derive(StructOpt)]
+enum TokenCommand {
+    /// Create a new token and print it; it cannot be retrieved later
+    Mint {
+        #[structopt(short, long)]
+        name: String,
+
+        /// Any of admin, instance-upload, solution-upload
+        #[structopt(short, long, required = true)]
+        scopes: Vec<Scope>,
+    },
+
+    /// Revoke the token with the given id
+    Revoke { tid: i32 },
+
+    /// List all tokens (without their secrets)
+    List,
+}
+
+async fn run_token_command(db: &MySqlPool, command: TokenCommand) -> anyhow::Result<()> {
+    match command {
+        TokenCommand::Mint { name, scopes } => {
+            let (tid, token) = auth::mint_token(db, &name, &scopes).await?;
+            println!("Minted token {tid}: {token}");
+        }
+        TokenCommand::Revoke { tid } => {
+            if !auth::revoke_token(db, tid).await? {
+                anyhow::bail!("There is no active token with id {tid}");
+            }
+            println!("Revoked token {tid}");
+        }
+        TokenCommand::List => {
+            for token in auth::list_tokens(db).await? {
+                let scopes = token.scopes.iter().map(Scope::as_str).collect::<Vec<_>>();
+                println!(
+                    "{:>5} {:<30} {:<40} created {}{}",
+                    token.tid,
+                    token.name,
+                    scopes.join(","),
+                    token.created_at.to_rfc3339(),
+                    token
+                        .revoked_at
+                        .map_or(String::new(), |t| format!(", revoked {}", t.to_rfc3339()))
+                );
+            }
+        }
+    }
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv().ok();
 
+    let mut opts = Opts::from_args();
+    let command = opts.command.take();
+
     let opts = Arc::new({
-        let mut opts = Opts::from_args();
         if opts.mysql_url.is_none() {
             opts.mysql_url = Some(
                 std::env::var("DATABASE_URL")
@@ -148,7 +275,36 @@ async fn main() -> anyhow::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let app_state = Arc::new(AppState::new(connect_to_database(&opts).await));
+    let db = connect_to_database(&opts).await;
+
+    if let Some(Command::Token(command)) = command {
+        return run_token_command(&db, command).await;
+    }
+
+    let blob_store = create_blob_store(&opts, &db)?;
+    let app_state = Arc::new(
+        AppState::new(db)
+            .with_blob_store(blob_store)
+            .with_solution_upload_token_required(opts.require_solution_upload_token)
+            .with_unregistered_solvers_accepted(!opts.reject_unregistered_solvers)
+            .with_best_solutions_published(opts.publish_best_solutions)
+            .with_best_solution_embargo(Duration::from_secs(
+                opts.best_solution_embargo_hours * 3600,
+            ))
+            .with_temp_dir(opts.temp_dir.clone().unwrap_or_else(std::env::temp_dir)),
+    );
+
+    if opts.reencode_blobs {
+        let db = app_state.db().clone();
+        tokio::spawn(async move {
+            if let Err(e) = reencode_blobs(&db).await {
+                error!("Re-encoding blobs failed: {e:?}");
+            }
+        });
+    }
+
+    tokio::spawn(refresh_outdated_difficulties(app_state.db().clone()));
+    tokio::spawn(collect_orphaned_blobs_periodically(app_state.clone()));
 
     let https_handle = tokio::spawn(https_server(app_state.clone(), opts.clone()));
     if opts.no_redirect_to_https {
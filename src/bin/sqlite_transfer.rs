@@ -7,6 +7,8 @@ use sqlx::{
     Sqlite, SqlitePool,
 };
 
+use stride_server::pace::codec::{decode_instance, BlobEncoding};
+
 async fn connect_to_database() -> MySqlPool {
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
 
@@ -50,6 +52,7 @@ async fn connect_to_sqlite(path: &str) -> SqlitePool {
 struct InputRow {
     did: i32,
     data: Option<Vec<u8>>,
+    encoding: u8,
 }
 
 async fn query_existing_rows(pool: &SqlitePool) -> HashSet<i32> {
@@ -121,16 +124,23 @@ async fn main() {
     println!("Total instances: {}", total_instances);
 
     let mut stream =
-        sqlx::query_as::<_, InputRow>("SELECT `did`, `data` FROM InstanceData").fetch(&mysql);
+        sqlx::query_as::<_, InputRow>("SELECT `did`, `data`, `encoding` FROM InstanceData")
+            .fetch(&mysql);
 
     let existing_small = query_existing_rows(&sqllite_small).await;
     let existing_all = query_existing_rows(&sqllite_all).await;
 
     let mut progress_report = ProgressReport::new(total_instances as u64);
     while let Some(row) = stream.try_next().await.expect("To fetch row") {
+        // payloads kept outside of MySQL (see `--blob-dir`/`--s3-bucket`) are not transferred
         if let Some(data) = &row.data {
             progress_report.update();
 
+            // the SQLite copies keep plain DIMACS text
+            let encoding = BlobEncoding::try_from(row.encoding).expect("Known blob encoding");
+            let data = decode_instance(encoding, data).expect("To decode instance");
+            let data = data.as_ref();
+
             if !existing_all.contains(&row.did) {
                 insert_row(&sqllite_all, row.did, data).await;
             }
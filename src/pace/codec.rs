@@ -0,0 +1,323 @@
+use std::{
+    borrow::Cow,
+    io::{BufRead, ErrorKind, Write},
+};
+
+use super::{graph::*, instance_reader::PaceReader, PROBLEM_ID};
+
+pub type Result<T> = std::io::Result<T>;
+
+/// Encoding of a blob in `InstanceData` or `SolutionData`. The discriminant is stored next
+/// to the blob, so existing values must never be reassigned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BlobEncoding {
+    /// DIMACS text for instances and a JSON array of 0-indexed nodes for solutions
+    Plain = 0,
+    /// zstd-compressed `Plain` data
+    Zstd = 1,
+    /// Normalised instance as list of delta-encoded varint edges
+    EdgeList = 2,
+    /// Solution with strictly increasing nodes as bitmap
+    Bitmap = 3,
+    /// Solution as list of zigzag delta-encoded varint nodes
+    NodeList = 4,
+}
+
+impl BlobEncoding {
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+}
+
+impl TryFrom<u8> for BlobEncoding {
+    type Error = std::io::Error;
+
+    fn try_from(id: u8) -> Result<Self> {
+        Ok(match id {
+            0 => BlobEncoding::Plain,
+            1 => BlobEncoding::Zstd,
+            2 => BlobEncoding::EdgeList,
+            3 => BlobEncoding::Bitmap,
+            4 => BlobEncoding::NodeList,
+            _ => return Err(invalid_data("Unknown blob encoding")),
+        })
+    }
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> Result<()> {
+    while value >= 0x80 {
+        writer.write_all(&[value as u8 | 0x80])?;
+        value >>= 7;
+    }
+    writer.write_all(&[value as u8])
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let Some((&byte, rest)) = data.split_first() else {
+            return Err(invalid_data("Truncated varint"));
+        };
+        *data = rest;
+
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(invalid_data("Varint exceeds 64 bits"))
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL)
+}
+
+fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    zstd::decode_all(data)
+}
+
+/// Writes a normalised instance as [`BlobEncoding::EdgeList`]. Each edge `(u, v)` is
+/// stored as the difference to the previous source and, if the source did not change, the
+/// difference to the previous target or `v - u` otherwise.
+///
+/// Fails for instances that are not normalised; these cannot be reproduced exactly.
+pub fn encode_edge_list<R: BufRead, W: Write>(reader: R, mut writer: W) -> Result<()> {
+    let pace_reader = PaceReader::try_new(reader)?;
+    if pace_reader.problem_id() != PROBLEM_ID {
+        return Err(invalid_data("Unexpected problem id"));
+    }
+
+    let number_of_edges = pace_reader.number_of_edges();
+    write_varint(&mut writer, pace_reader.number_of_nodes() as u64)?;
+    write_varint(&mut writer, number_of_edges)?;
+
+    // 1-indexed, so the first edge always has a source different from `prev_u`
+    let (mut prev_u, mut prev_v) = (0, 0);
+    let mut count: NumEdges = 0;
+    for edge in pace_reader {
+        let Edge(u, v) = edge?;
+        let (u, v) = (u as u64 + 1, v as u64 + 1);
+
+        if v < u || (u, v) <= (prev_u, prev_v) {
+            return Err(invalid_data("Edges are not normalised"));
+        }
+
+        if u == prev_u {
+            write_varint(&mut writer, 0)?;
+            write_varint(&mut writer, v - prev_v)?;
+        } else {
+            write_varint(&mut writer, u - prev_u)?;
+            write_varint(&mut writer, v - u)?;
+        }
+
+        (prev_u, prev_v) = (u, v);
+        count += 1;
+    }
+
+    if count != number_of_edges {
+        return Err(invalid_data("Number of edges does not match the header"));
+    }
+
+    writer.flush()
+}
+
+fn decode_edge_list<W: Write>(mut data: &[u8], mut writer: W) -> Result<()> {
+    let number_of_nodes = read_varint(&mut data)?;
+    let number_of_edges = read_varint(&mut data)?;
+    writeln!(writer, "p {PROBLEM_ID} {number_of_nodes} {number_of_edges}")?;
+
+    let (mut u, mut v) = (0, 0);
+    for _ in 0..number_of_edges {
+        let du = read_varint(&mut data)?;
+        let dv = read_varint(&mut data)?;
+
+        if du == 0 {
+            v += dv;
+        } else {
+            u += du;
+            v = u + dv;
+        }
+
+        writeln!(writer, "{u} {v}")?;
+    }
+
+    if !data.is_empty() {
+        return Err(invalid_data("Trailing data after edge list"));
+    }
+
+    writer.flush()
+}
+
+/// Encodes DIMACS text with the most compact encoding that reproduces it byte by byte
+pub fn encode_instance(data: &[u8]) -> Result<(BlobEncoding, Vec<u8>)> {
+    let mut edge_list = Vec::new();
+    if encode_edge_list(data, &mut edge_list).is_ok()
+        && decode_instance(BlobEncoding::EdgeList, &edge_list)?.as_ref() == data
+    {
+        return Ok((BlobEncoding::EdgeList, edge_list));
+    }
+
+    Ok((BlobEncoding::Zstd, compress(data)?))
+}
+
+/// Returns the DIMACS text of an instance blob
+pub fn decode_instance(encoding: BlobEncoding, data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    match encoding {
+        BlobEncoding::Plain => Ok(Cow::Borrowed(data)),
+        BlobEncoding::Zstd => Ok(Cow::Owned(decompress(data)?)),
+        BlobEncoding::EdgeList => {
+            let mut decoded = Vec::with_capacity(4 * data.len());
+            decode_edge_list(data, &mut decoded)?;
+            Ok(Cow::Owned(decoded))
+        }
+        BlobEncoding::Bitmap | BlobEncoding::NodeList => {
+            Err(invalid_data("Not an instance encoding"))
+        }
+    }
+}
+
+fn encode_bitmap(nodes: &[Node]) -> Vec<u8> {
+    let len = nodes.last().map_or(0, |&u| u as usize / 8 + 1);
+    let mut bitmap = vec![0u8; len];
+    for &u in nodes {
+        bitmap[u as usize / 8] |= 1 << (u % 8);
+    }
+    bitmap
+}
+
+fn decode_bitmap(data: &[u8]) -> Vec<Node> {
+    data.iter()
+        .enumerate()
+        .flat_map(|(i, &byte)| {
+            (0..8)
+                .filter(move |bit| byte & (1 << bit) != 0)
+                .map(move |bit| (8 * i + bit) as Node)
+        })
+        .collect()
+}
+
+fn encode_node_list(nodes: &[Node]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(2 * nodes.len());
+    let mut prev = 0i64;
+    for &u in nodes {
+        let delta = u as i64 - prev;
+        // zigzag encoding keeps small negative deltas short
+        write_varint(&mut encoded, ((delta << 1) ^ (delta >> 63)) as u64).unwrap();
+        prev = u as i64;
+    }
+    encoded
+}
+
+fn decode_node_list(mut data: &[u8]) -> Result<Vec<Node>> {
+    let mut nodes = Vec::with_capacity(data.len());
+    let mut prev = 0i64;
+    while !data.is_empty() {
+        let zigzag = read_varint(&mut data)?;
+        prev += (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+        nodes.push(Node::try_from(prev).map_err(|_| invalid_data("Node id out of range"))?);
+    }
+    Ok(nodes)
+}
+
+/// Encodes 0-indexed solution nodes; the order of the nodes is preserved
+pub fn encode_solution(nodes: &[Node]) -> (BlobEncoding, Vec<u8>) {
+    let node_list = encode_node_list(nodes);
+
+    if nodes.windows(2).all(|w| w[0] < w[1]) {
+        let bitmap = encode_bitmap(nodes);
+        if bitmap.len() < node_list.len() {
+            return (BlobEncoding::Bitmap, bitmap);
+        }
+    }
+
+    (BlobEncoding::NodeList, node_list)
+}
+
+/// Returns the 0-indexed nodes of a solution blob
+pub fn decode_solution(encoding: BlobEncoding, data: &[u8]) -> Result<Vec<Node>> {
+    match encoding {
+        BlobEncoding::Plain => Ok(serde_json::from_slice(data)?),
+        BlobEncoding::Zstd => Ok(serde_json::from_slice(&decompress(data)?)?),
+        BlobEncoding::Bitmap => Ok(decode_bitmap(data)),
+        BlobEncoding::NodeList => decode_node_list(data),
+        BlobEncoding::EdgeList => Err(invalid_data("Not a solution encoding")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn varint() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut encoded = Vec::new();
+            write_varint(&mut encoded, value).unwrap();
+
+            let mut data = &encoded[..];
+            assert_eq!(read_varint(&mut data).unwrap(), value);
+            assert!(data.is_empty());
+        }
+
+        assert!(read_varint(&mut &[0x80u8][..]).is_err());
+    }
+
+    #[test]
+    fn instance_roundtrip() {
+        let normalized = b"p ds 5 6\n1 1\n1 2\n1 5\n2 3\n3 4\n4 5\n";
+        let (encoding, encoded) = encode_instance(normalized).unwrap();
+        assert_eq!(encoding, BlobEncoding::EdgeList);
+        assert!(encoded.len() < normalized.len() / 2);
+        assert_eq!(
+            decode_instance(encoding, &encoded).unwrap().as_ref(),
+            normalized
+        );
+
+        // comments, unsorted edges, or odd spacing cannot be reproduced from an edge list
+        for data in [
+            &b"c comment\np ds 2 1\n1 2\n"[..],
+            b"p ds 3 2\n2 3\n1 2\n",
+            b"p ds 2 1\n1  2\n",
+        ] {
+            let (encoding, encoded) = encode_instance(data).unwrap();
+            assert_eq!(encoding, BlobEncoding::Zstd);
+            assert_eq!(decode_instance(encoding, &encoded).unwrap().as_ref(), data);
+        }
+
+        assert_eq!(
+            decode_instance(BlobEncoding::Plain, normalized)
+                .unwrap()
+                .as_ref(),
+            normalized
+        );
+    }
+
+    #[test]
+    fn solution_roundtrip() {
+        let dense: Vec<Node> = (0..100).filter(|u| u % 3 != 0).collect();
+        let sparse = vec![3, 100_000, 2_000_000];
+        let unordered = vec![7, 2, 2, 9];
+
+        for (nodes, expected) in [
+            (dense, BlobEncoding::Bitmap),
+            (sparse, BlobEncoding::NodeList),
+            (unordered, BlobEncoding::NodeList),
+        ] {
+            let (encoding, encoded) = encode_solution(&nodes);
+            assert_eq!(encoding, expected);
+            assert_eq!(decode_solution(encoding, &encoded).unwrap(), nodes);
+        }
+
+        assert_eq!(
+            decode_solution(BlobEncoding::Plain, b"[4,1,2]").unwrap(),
+            vec![4, 1, 2]
+        );
+        assert!(decode_solution(BlobEncoding::EdgeList, b"").is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use sha1::{Digest, Sha1};
+
+use super::graph::*;
+
+/// Number of Weisfeiler-Lehman refinement rounds of [`fingerprint`]
+pub const DEFAULT_WL_ROUNDS: usize = 3;
+
+pub type Fingerprint = [u8; 20];
+
+/// The splitmix64 finalizer; unlike `std::hash` it is stable across releases, which
+/// matters since fingerprints are stored in the database.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Computes an isomorphism-invariant fingerprint of `graph` from its degree sequence and
+/// the label histogram after `rounds` rounds of Weisfeiler-Lehman colour refinement.
+///
+/// Isomorphic graphs always share a fingerprint; the converse need not hold, so equal
+/// fingerprints only indicate a likely duplicate.
+pub fn fingerprint_with_rounds(graph: &Graph, rounds: usize) -> Fingerprint {
+    let mut hasher = Sha1::new();
+    hasher.update(graph.number_of_nodes().to_le_bytes());
+    hasher.update(graph.number_of_edges().to_le_bytes());
+
+    let mut degrees: Vec<NumNodes> = graph.degrees().collect();
+    degrees.sort_unstable();
+    for d in degrees {
+        hasher.update(d.to_le_bytes());
+    }
+
+    let mut labels: Vec<u64> = graph.degrees().map(|d| mix(d as u64)).collect();
+    let mut next = vec![0u64; labels.len()];
+    for round in 0..rounds {
+        for u in graph.vertices() {
+            // the sum of mixed labels is a hash of the neighbourhood's multiset of labels
+            let neighborhood = graph
+                .neighbors_of(u)
+                .fold(0u64, |acc, v| acc.wrapping_add(mix(labels[v as usize])));
+
+            next[u as usize] = mix(labels[u as usize] ^ mix(neighborhood ^ round as u64));
+        }
+        std::mem::swap(&mut labels, &mut next);
+    }
+
+    labels.sort_unstable();
+    for label in labels {
+        hasher.update(label.to_le_bytes());
+    }
+
+    hasher.finalize().into()
+}
+
+pub fn fingerprint(graph: &Graph) -> Fingerprint {
+    fingerprint_with_rounds(graph, DEFAULT_WL_ROUNDS)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn relabel(graph: &Graph, perm: &[Node]) -> Graph {
+        Graph::try_from_edges(
+            graph.number_of_nodes(),
+            graph
+                .edges()
+                .map(|Edge(u, v)| Edge(perm[u as usize], perm[v as usize])),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn invariant_under_relabeling() {
+        let graph = Graph::try_from_edges(
+            6,
+            [
+                Edge(0, 1),
+                Edge(1, 2),
+                Edge(2, 0),
+                Edge(2, 3),
+                Edge(3, 4),
+                Edge(4, 5),
+            ],
+        )
+        .unwrap();
+
+        let permuted = relabel(&graph, &[3, 5, 0, 1, 4, 2]);
+        assert_ne!(graph, permuted);
+        assert_eq!(fingerprint(&graph), fingerprint(&permuted));
+    }
+
+    #[test]
+    fn distinguishes_same_degree_sequence() {
+        // a 6-cycle and two triangles are both 2-regular on six nodes
+        let cycle = Graph::try_from_edges(6, (0..6).map(|u| Edge(u, (u + 1) % 6))).unwrap();
+        let triangles = Graph::try_from_edges(
+            6,
+            [
+                Edge(0, 1),
+                Edge(1, 2),
+                Edge(2, 0),
+                Edge(3, 4),
+                Edge(4, 5),
+                Edge(5, 3),
+            ],
+        )
+        .unwrap();
+
+        // colour refinement cannot tell regular graphs apart ...
+        assert_eq!(fingerprint(&cycle), fingerprint(&triangles));
+
+        // ... but it separates graphs with equal degree sequences otherwise
+        let path = Graph::try_from_edges(5, (1..5).map(|u| Edge(u - 1, u))).unwrap();
+        let other =
+            Graph::try_from_edges(5, [Edge(0, 1), Edge(1, 2), Edge(2, 0), Edge(3, 4)]).unwrap();
+
+        assert_ne!(fingerprint(&path), fingerprint(&other));
+    }
+}
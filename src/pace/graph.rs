@@ -1,3 +1,7 @@
+use std::io::BufRead;
+
+use super::instance_reader::PaceReader;
+
 pub type Node = u32;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -27,3 +31,222 @@ impl Edge {
 
 pub type NumNodes = Node;
 pub type NumEdges = u64;
+
+type Result<T> = std::io::Result<T>;
+
+/// Undirected graph stored as compressed sparse rows.
+///
+/// Each neighbourhood is sorted and free of duplicates. Self-loops are kept
+/// (once, in the neighbourhood of their node) so that a graph can be written
+/// back without losing edges of the original instance.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    offsets: Vec<usize>,
+    neighbors: Vec<Node>,
+    num_edges: NumEdges,
+}
+
+impl Graph {
+    /// Builds a graph with `n` nodes; fails if an edge references a node `>= n`.
+    pub fn try_from_edges(n: NumNodes, edges: impl IntoIterator<Item = Edge>) -> Result<Self> {
+        let edges: Vec<Edge> = edges.into_iter().collect();
+
+        // count degrees (with multiplicities) to place each row
+        let mut offsets = vec![0usize; n as usize + 1];
+        for &Edge(u, v) in &edges {
+            if u.max(v) >= n {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Edge contains node id larger than the number of nodes",
+                ));
+            }
+
+            offsets[u as usize + 1] += 1;
+            if u != v {
+                offsets[v as usize + 1] += 1;
+            }
+        }
+
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+
+        let mut neighbors = vec![0 as Node; offsets[n as usize]];
+        {
+            let mut write_pos = offsets.clone();
+            for Edge(u, v) in edges {
+                neighbors[write_pos[u as usize]] = v;
+                write_pos[u as usize] += 1;
+
+                if u != v {
+                    neighbors[write_pos[v as usize]] = u;
+                    write_pos[v as usize] += 1;
+                }
+            }
+        }
+
+        // sort and deduplicate rows, compacting them towards the front
+        let mut num_edges: NumEdges = 0;
+        let mut write = 0;
+        for u in 0..n as usize {
+            let (begin, end) = (offsets[u], offsets[u + 1]);
+            neighbors[begin..end].sort_unstable();
+
+            offsets[u] = write;
+            let mut prev = None;
+            for i in begin..end {
+                let v = neighbors[i];
+                if prev == Some(v) {
+                    continue;
+                }
+                prev = Some(v);
+
+                neighbors[write] = v;
+                write += 1;
+
+                if u as Node <= v {
+                    num_edges += 1;
+                }
+            }
+        }
+        offsets[n as usize] = write;
+
+        neighbors.truncate(write);
+        neighbors.shrink_to_fit();
+
+        Ok(Self {
+            offsets,
+            neighbors,
+            num_edges,
+        })
+    }
+
+    /// Reads all edges from `reader`; the number of nodes is taken from its header.
+    pub fn try_from_pace_reader<R: BufRead>(reader: PaceReader<R>) -> Result<Self> {
+        let n = reader.number_of_nodes();
+        let mut edges = Vec::with_capacity(reader.number_of_edges() as usize);
+        for edge in reader {
+            edges.push(edge?);
+        }
+
+        Self::try_from_edges(n, edges)
+    }
+
+    pub fn number_of_nodes(&self) -> NumNodes {
+        (self.offsets.len() - 1) as NumNodes
+    }
+
+    /// Number of distinct edges, including self-loops.
+    pub fn number_of_edges(&self) -> NumEdges {
+        self.num_edges
+    }
+
+    pub fn vertices(&self) -> impl Iterator<Item = Node> {
+        0..self.number_of_nodes()
+    }
+
+    pub fn degree_of(&self, u: Node) -> NumNodes {
+        (self.offsets[u as usize + 1] - self.offsets[u as usize]) as NumNodes
+    }
+
+    pub fn degrees(&self) -> impl Iterator<Item = NumNodes> + '_ {
+        self.offsets.windows(2).map(|w| (w[1] - w[0]) as NumNodes)
+    }
+
+    pub fn neighbors_of(&self, u: Node) -> impl Iterator<Item = Node> + '_ {
+        self.neighbors_slice(u).iter().copied()
+    }
+
+    /// Yields `u` followed by all of its neighbours other than `u` itself.
+    pub fn closed_neighbors_of(&self, u: Node) -> impl Iterator<Item = Node> + '_ {
+        std::iter::once(u).chain(self.neighbors_of(u).filter(move |&v| v != u))
+    }
+
+    pub fn neighbors_slice(&self, u: Node) -> &[Node] {
+        &self.neighbors[self.offsets[u as usize]..self.offsets[u as usize + 1]]
+    }
+
+    /// Yields every edge once as `Edge(u, v)` with `u <= v` in lexicographic order.
+    pub fn edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.vertices().flat_map(move |u| {
+            self.neighbors_of(u)
+                .filter(move |&v| u <= v)
+                .map(move |v| Edge(u, v))
+        })
+    }
+}
+
+/// Deterministic source of small pseudo-random graphs for tests
+#[cfg(test)]
+pub struct RandomGraphs {
+    state: u64,
+}
+
+#[cfg(test)]
+impl RandomGraphs {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns a number in `0..m`
+    pub fn below(&mut self, m: u32) -> u32 {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.state >> 33) % m as u64) as u32
+    }
+
+    /// Returns a graph on `n` nodes with `m` random edges (before removing duplicates)
+    pub fn graph(&mut self, n: NumNodes, m: NumEdges) -> Graph {
+        let edges: Vec<_> = (0..m).map(|_| Edge(self.below(n), self.below(n))).collect();
+        Graph::try_from_edges(n, edges).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_edges() {
+        let graph = Graph::try_from_edges(
+            5,
+            [Edge(0, 1), Edge(2, 1), Edge(1, 0), Edge(3, 3), Edge(1, 2)],
+        )
+        .unwrap();
+
+        assert_eq!(graph.number_of_nodes(), 5);
+        assert_eq!(graph.number_of_edges(), 3);
+
+        assert_eq!(graph.neighbors_of(1).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(
+            graph.closed_neighbors_of(1).collect::<Vec<_>>(),
+            vec![1, 0, 2]
+        );
+        assert_eq!(graph.closed_neighbors_of(3).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(graph.degrees().collect::<Vec<_>>(), vec![1, 2, 1, 1, 0]);
+        assert_eq!(graph.degree_of(4), 0);
+
+        assert_eq!(
+            graph.edges().collect::<Vec<_>>(),
+            vec![Edge(0, 1), Edge(1, 2), Edge(3, 3)]
+        );
+    }
+
+    #[test]
+    fn from_edges_out_of_range() {
+        assert!(Graph::try_from_edges(2, [Edge(0, 2)]).is_err());
+    }
+
+    #[test]
+    fn from_pace_reader() {
+        let data = "p ds 4 3\n1 2\n2 3\n3 4\n";
+        let reader = PaceReader::try_new(data.as_bytes()).unwrap();
+        let graph = Graph::try_from_pace_reader(reader).unwrap();
+
+        assert_eq!(graph.number_of_nodes(), 4);
+        assert_eq!(graph.number_of_edges(), 3);
+        assert_eq!(graph.neighbors_of(2).collect::<Vec<_>>(), vec![1, 3]);
+    }
+}
@@ -0,0 +1,523 @@
+use std::{
+    io::{BufRead, BufReader, Cursor, ErrorKind, Read},
+    str::FromStr,
+};
+
+use flate2::bufread::MultiGzDecoder;
+use serde::{Deserialize, Serialize};
+
+use super::{graph::*, instance_reader::PaceReader};
+
+pub type Result<T> = std::io::Result<T>;
+
+pub type EdgeIter<'a> = Box<dyn Iterator<Item = Result<Edge>> + 'a>;
+
+/// Gzip and format detection look at (at least) this many bytes of the input
+const DETECTION_BYTES: usize = 64;
+
+/// Format detection looks at no more than this many bytes, which covers small inputs
+/// completely
+const MAX_DETECTION_BYTES: usize = 1 << 16;
+
+/// Input formats an instance can be read from. Nodes are 1-indexed unless stated otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    /// PACE DIMACS text (`p ds n m` followed by one edge per line)
+    Dimacs,
+    /// SNAP edge list: one edge `u v` per line with 0-indexed nodes; lines starting with
+    /// `#` or `%` are comments and further columns are ignored
+    #[serde(alias = "edgelist")]
+    Snap,
+    /// METIS adjacency lists; vertex sizes and weights as well as edge weights are skipped
+    Metis,
+    /// Square matrix in Matrix Market coordinate format; values are ignored
+    #[serde(alias = "mtx")]
+    MatrixMarket,
+}
+
+impl FromStr for InputFormat {
+    type Err = serde_json::Error;
+
+    /// Accepts the same names as deserialisation, including aliases
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_owned()))
+    }
+}
+
+/// Sizes announced in the header of an input, if its format has one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeclaredSize {
+    pub number_of_nodes: Option<NumNodes>,
+    /// Number of distinct edges; unset if the header counts them differently
+    pub number_of_edges: Option<NumEdges>,
+}
+
+fn invalid_data(message: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+fn parse_value<T: FromStr>(token: Option<&str>, name: &str) -> Result<T> {
+    let Some(token) = token else {
+        return Err(invalid_data(format!(
+            "Premature end of line when parsing {name}"
+        )));
+    };
+    token
+        .parse()
+        .map_err(|_| invalid_data(format!("Invalid value {token:?} for {name}")))
+}
+
+/// Parses an edge from the first two columns of `line`, whose node ids start at `first_id`
+fn parse_edge(line: &str, first_id: Node) -> Result<Edge> {
+    let mut parts = line.split_ascii_whitespace();
+    let u: Node = parse_value(parts.next(), "source node")?;
+    let v: Node = parse_value(parts.next(), "target node")?;
+
+    if u.min(v) < first_id {
+        return Err(invalid_data(format!("Node ids are {first_id}-based")));
+    }
+
+    Ok(Edge(u - first_id, v - first_id))
+}
+
+fn is_comment(line: &str) -> bool {
+    line.trim_start().starts_with('%')
+}
+
+/// Reads from `reader` into `head` until `done(head)` holds, `max_len` bytes were read or
+/// the input ends. Readers may return arbitrarily small chunks, so a single `fill_buf` does
+/// not suffice.
+fn read_head<R: BufRead>(
+    reader: &mut R,
+    head: &mut Vec<u8>,
+    max_len: usize,
+    done: impl Fn(&[u8]) -> bool,
+) -> Result<()> {
+    while head.len() < max_len && !done(head) {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        let len = buf.len().min(max_len - head.len());
+        head.extend_from_slice(&buf[..len]);
+        reader.consume(len);
+    }
+    Ok(())
+}
+
+/// Transparently decompresses gzip input, which is recognised by its magic bytes
+pub fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> Result<Box<dyn BufRead + 'a>> {
+    let mut head = Vec::with_capacity(DETECTION_BYTES);
+    read_head(&mut reader, &mut head, DETECTION_BYTES, |_| false)?;
+
+    let gzip = head.starts_with(&[0x1f, 0x8b]);
+    let reader = Cursor::new(head).chain(reader);
+    if gzip {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Comment character of `head` (if any) and its first line that is neither empty nor a
+/// comment
+fn head_line(head: &str) -> (Option<char>, Option<&str>) {
+    let mut comment = None;
+    for line in head.lines().map(str::trim) {
+        match line.chars().next() {
+            None => {}
+            Some(c @ ('#' | '%')) => {
+                comment.get_or_insert(c);
+            }
+            Some(_) => return (comment, Some(line)),
+        }
+    }
+    (comment, None)
+}
+
+/// Whether numeric input is METIS rather than a SNAP edge list, or `None` if `head` does
+/// not tell. A METIS header `n m [fmt [ncon]]` is followed by exactly `n` adjacency lists
+/// with 1-based neighbours (`2m` entries in total if there are no weights), which can only
+/// be verified if `head` is the `complete` input. Otherwise lists of varying length are
+/// taken as METIS, whereas SNAP lines typically all have the same number of columns.
+fn is_metis(head: &str, complete: bool) -> Option<bool> {
+    let head = match complete {
+        true => head,
+        // only complete lines
+        false => head.rfind('\n').map_or("", |end| &head[..end]),
+    };
+
+    // empty lines are isolated nodes, so only comments are skipped
+    let mut lines = head.lines().filter(|line| !is_comment(line));
+    let header: Vec<_> = lines.next()?.split_ascii_whitespace().collect();
+    let (Some(n), Some(m)) = (
+        header.first().and_then(|t| t.parse::<usize>().ok()),
+        header.get(1).and_then(|t| t.parse::<usize>().ok()),
+    ) else {
+        return Some(false);
+    };
+
+    let fmt = header.get(2).copied().unwrap_or("0");
+    if header.len() > 4
+        || fmt.len() > 3
+        || !fmt.chars().all(|c| c == '0' || c == '1')
+        || header.get(3).is_some_and(|t| t.parse::<usize>().is_err())
+    {
+        return Some(false);
+    }
+    // sizes and weights are not told apart from neighbours
+    let unweighted = !fmt.contains('1');
+
+    let mut adjacency_lists = 0;
+    let mut entries = 0;
+    let mut columns = None;
+    let mut varying_columns = false;
+    for line in lines {
+        adjacency_lists += 1;
+        let mut len = 0;
+        for token in line.split_ascii_whitespace() {
+            len += 1;
+            if unweighted && !token.parse::<usize>().is_ok_and(|v| (1..=n).contains(&v)) {
+                return Some(false);
+            }
+        }
+        entries += len;
+        varying_columns |= *columns.get_or_insert(len) != len;
+    }
+
+    if adjacency_lists > n {
+        Some(false)
+    } else if complete {
+        Some(adjacency_lists == n && (!unweighted || m.checked_mul(2) == Some(entries)))
+    } else {
+        varying_columns.then_some(true)
+    }
+}
+
+/// Guesses the format from the beginning of an input, looking at the first line that is
+/// not a comment. Numeric inputs are SNAP edge lists or METIS files, which are told apart
+/// by [`is_metis`]; `None` means that both are plausible and the format has to be given
+/// explicitly.
+pub fn detect_format(head: &[u8], complete: bool) -> Option<InputFormat> {
+    let head = String::from_utf8_lossy(head);
+    if head
+        .get(..14)
+        .is_some_and(|h| h.eq_ignore_ascii_case("%%MatrixMarket"))
+    {
+        return Some(InputFormat::MatrixMarket);
+    }
+
+    match head_line(&head) {
+        (Some('#'), None) => Some(InputFormat::Snap),
+        (comment, Some(first)) if first.starts_with(|c: char| c.is_ascii_digit()) => {
+            // METIS comments start with `%`
+            if comment == Some('#') || !is_metis(&head, complete)? {
+                Some(InputFormat::Snap)
+            } else {
+                Some(InputFormat::Metis)
+            }
+        }
+        _ => Some(InputFormat::Dimacs),
+    }
+}
+
+/// Reads the edges (with 0-indexed nodes) of an uncompressed input; `None` detects the format
+pub fn read_edges<'a, R: BufRead + 'a>(
+    mut reader: R,
+    format: Option<InputFormat>,
+) -> Result<(DeclaredSize, EdgeIter<'a>)> {
+    if let Some(format) = format {
+        return read_edges_as(reader, format);
+    }
+
+    let mut head = Vec::with_capacity(DETECTION_BYTES);
+    read_head(&mut reader, &mut head, MAX_DETECTION_BYTES, |_| false)?;
+    let complete = reader.fill_buf()?.is_empty();
+
+    let Some(format) = detect_format(&head, complete) else {
+        return Err(invalid_data(
+            "Input may be a METIS file or a SNAP edge list; please specify its format",
+        ));
+    };
+    read_edges_as(Cursor::new(head).chain(reader), format)
+}
+
+fn read_edges_as<'a, R: BufRead + 'a>(
+    reader: R,
+    format: InputFormat,
+) -> Result<(DeclaredSize, EdgeIter<'a>)> {
+    match format {
+        InputFormat::Dimacs => dimacs_edges(reader),
+        InputFormat::Snap => Ok((DeclaredSize::default(), snap_edges(reader))),
+        InputFormat::Metis => metis_edges(reader),
+        InputFormat::MatrixMarket => matrix_market_edges(reader),
+    }
+}
+
+fn dimacs_edges<'a, R: BufRead + 'a>(reader: R) -> Result<(DeclaredSize, EdgeIter<'a>)> {
+    let pace_reader = PaceReader::try_new(reader)?;
+    let declared = DeclaredSize {
+        number_of_nodes: Some(pace_reader.number_of_nodes()),
+        number_of_edges: Some(pace_reader.number_of_edges()),
+    };
+    Ok((declared, Box::new(pace_reader)))
+}
+
+fn snap_edges<'a, R: BufRead + 'a>(reader: R) -> EdgeIter<'a> {
+    Box::new(reader.lines().filter_map(|line| match line {
+        Err(e) => Some(Err(e)),
+        Ok(line) => {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with(['#', '%']) {
+                None
+            } else {
+                Some(parse_edge(line, 0))
+            }
+        }
+    }))
+}
+
+fn metis_edges<'a, R: BufRead + 'a>(reader: R) -> Result<(DeclaredSize, EdgeIter<'a>)> {
+    // empty lines are isolated nodes, so only comments are skipped
+    let mut lines = reader
+        .lines()
+        .filter(|line| !line.as_ref().is_ok_and(|l| is_comment(l)));
+
+    let Some(header) = lines.next().transpose()? else {
+        return Err(invalid_data("No header found"));
+    };
+    let mut parts = header.split_ascii_whitespace();
+    let number_of_nodes: NumNodes = parse_value(parts.next(), "number of nodes")?;
+    let number_of_edges: NumEdges = parse_value(parts.next(), "number of edges")?;
+    let fmt = parts.next().unwrap_or("0");
+    let ncon: usize = parts
+        .next()
+        .map_or(Ok(1), |t| parse_value(Some(t), "ncon"))?;
+
+    // fmt is a binary number with up to three digits: sizes, vertex weights, edge weights
+    if fmt.len() > 3 || !fmt.chars().all(|c| c == '0' || c == '1') {
+        return Err(invalid_data(format!("Invalid METIS fmt {fmt:?}")));
+    }
+    let fmt = format!("{fmt:0>3}").into_bytes();
+    let skip = (fmt[0] == b'1') as usize + if fmt[1] == b'1' { ncon } else { 0 };
+    let step = 1 + (fmt[2] == b'1') as usize;
+
+    let mut u: Node = 0;
+    let edges = lines.take(number_of_nodes as usize).flat_map(move |line| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return vec![Err(e)],
+        };
+        let node = u;
+        u += 1;
+
+        line.split_ascii_whitespace()
+            .skip(skip)
+            .step_by(step)
+            .map(|token| {
+                let v: Node = parse_value(Some(token), "neighbor")?;
+                if v == 0 {
+                    return Err(invalid_data("Node ids are 1-based"));
+                }
+                Ok(Edge(node, v - 1))
+            })
+            .collect()
+    });
+
+    let declared = DeclaredSize {
+        number_of_nodes: Some(number_of_nodes),
+        number_of_edges: Some(number_of_edges),
+    };
+    Ok((declared, Box::new(edges)))
+}
+
+fn matrix_market_edges<'a, R: BufRead + 'a>(reader: R) -> Result<(DeclaredSize, EdgeIter<'a>)> {
+    let mut lines = reader.lines();
+
+    let banner = lines
+        .next()
+        .transpose()?
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let banner: Vec<_> = banner.split_ascii_whitespace().collect();
+    if banner.len() < 3 || banner[0] != "%%matrixmarket" || banner[1] != "matrix" {
+        return Err(invalid_data("Invalid Matrix Market banner"));
+    }
+    if banner[2] != "coordinate" {
+        return Err(invalid_data(
+            "Only coordinate matrices can be read as graph",
+        ));
+    }
+
+    let mut lines = lines.filter(|line| {
+        !line
+            .as_ref()
+            .is_ok_and(|l| l.trim().is_empty() || is_comment(l))
+    });
+
+    let Some(size) = lines.next().transpose()? else {
+        return Err(invalid_data("No size line found"));
+    };
+    let mut parts = size.split_ascii_whitespace();
+    let rows: NumNodes = parse_value(parts.next(), "number of rows")?;
+    let columns: NumNodes = parse_value(parts.next(), "number of columns")?;
+    if rows != columns {
+        return Err(invalid_data("Matrix is not square"));
+    }
+
+    // the number of entries counts both triangles of general matrices, so it is not checked
+    let declared = DeclaredSize {
+        number_of_nodes: Some(rows),
+        number_of_edges: None,
+    };
+    let edges = lines.map(|line| parse_edge(&line?, 1));
+    Ok((declared, Box::new(edges)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    fn read(data: &[u8], format: Option<InputFormat>) -> (DeclaredSize, Vec<Edge>) {
+        let (declared, edges) = read_edges(decompress(data).unwrap(), format).unwrap();
+        (declared, edges.map(|e| e.unwrap().normalized()).collect())
+    }
+
+    #[test]
+    fn detection() {
+        for (head, format) in [
+            (&b"p ds 3 2\n1 2\n"[..], InputFormat::Dimacs),
+            (b"c comment\np ds 3 2\n", InputFormat::Dimacs),
+            (b"# Directed graph\n0 1\n", InputFormat::Snap),
+            (b"0\t1\n1\t2\n", InputFormat::Snap),
+            (b"% metis\n3 2\n2\n1 3\n2\n", InputFormat::Metis),
+            (
+                b"% edge list\n% with comments\n1 2\n2 3\n",
+                InputFormat::Snap,
+            ),
+            (b"3 2\n2\n1 3\n2\n", InputFormat::Metis),
+            (b"3 1 0\n\n3\n2\n", InputFormat::Metis),
+            // edge count does not match the header
+            (b"% metis\n3 2\n2 3\n1 3\n2\n", InputFormat::Snap),
+            // fewer lines than nodes
+            (b"3 2\n1 2\n2 3\n", InputFormat::Snap),
+            (
+                b"%%MatrixMarket matrix coordinate pattern symmetric\n",
+                InputFormat::MatrixMarket,
+            ),
+        ] {
+            assert_eq!(detect_format(head, true), Some(format));
+        }
+    }
+
+    #[test]
+    fn detection_of_incomplete_input() {
+        for (head, format) in [
+            (&b"3 2\n2\n1 3\n2"[..], Some(InputFormat::Metis)),
+            (b"1 2\n2 3\n3 1\n", Some(InputFormat::Snap)),
+            (b"3 4\n1 2\n0 3\n", Some(InputFormat::Snap)),
+            (b"# edges\n3 2\n1 2\n", Some(InputFormat::Snap)),
+            (b"p ds 3 2\n1 2\n", Some(InputFormat::Dimacs)),
+            // a METIS header followed by adjacency lists of two nodes, or two edges
+            (b"3 2\n1 2\n2 3\n", None),
+        ] {
+            assert_eq!(detect_format(head, false), format);
+        }
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!("metis".parse::<InputFormat>().unwrap(), InputFormat::Metis);
+        assert_eq!(
+            "edgelist".parse::<InputFormat>().unwrap(),
+            InputFormat::Snap
+        );
+        assert_eq!(
+            "mtx".parse::<InputFormat>().unwrap(),
+            InputFormat::MatrixMarket
+        );
+        assert!("graphml".parse::<InputFormat>().is_err());
+    }
+
+    #[test]
+    fn formats() {
+        let path = vec![Edge(0, 1), Edge(1, 2)];
+
+        let (declared, edges) = read(b"# path\n0\t1\n1 2 0.5\n", None);
+        assert_eq!(declared, DeclaredSize::default());
+        assert_eq!(edges, path);
+
+        let (declared, mut edges) = read(b"% path\n3 2\n2\n1 3\n2\n", None);
+        assert_eq!(declared.number_of_nodes, Some(3));
+        assert_eq!(declared.number_of_edges, Some(2));
+        edges.sort();
+        edges.dedup();
+        assert_eq!(edges, path);
+
+        // vertex weights and edge weights
+        let (_, mut edges) = read(
+            b"3 2 011\n5 2 7\n5 1 7 3 7\n5 2 7\n",
+            Some(InputFormat::Metis),
+        );
+        edges.sort();
+        edges.dedup();
+        assert_eq!(edges, path);
+
+        let (declared, edges) = read(
+            b"%%MatrixMarket matrix coordinate real symmetric\n% c\n3 3 2\n2 1 1.0\n3 2 1.0\n",
+            None,
+        );
+        assert_eq!(declared.number_of_nodes, Some(3));
+        assert_eq!(edges, path);
+
+        let (_, edges) = read(b"p ds 3 2\n1 2\n2 3\n", None);
+        assert_eq!(edges, path);
+    }
+
+    #[test]
+    fn gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"0 1\n1 2\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let (_, edges) = read(&compressed, None);
+        assert_eq!(edges, vec![Edge(0, 1), Edge(1, 2)]);
+    }
+
+    #[test]
+    fn detection_reads_beyond_the_first_chunk() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(b"% a comment longer than a single chunk of the reader\n3 2\n2\n1 3\n2\n")
+            .unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        // hands out one byte at a time
+        let reader = BufReader::with_capacity(1, &compressed[..]);
+        let (declared, edges) = read_edges(decompress(reader).unwrap(), None).unwrap();
+        assert_eq!(declared.number_of_nodes, Some(3));
+        assert_eq!(edges.count(), 4);
+    }
+
+    #[test]
+    fn errors() {
+        for (data, format) in [
+            (&b"%%MatrixMarket matrix array real general\n"[..], None),
+            (
+                b"%%MatrixMarket matrix coordinate pattern general\n2 3 1\n",
+                None,
+            ),
+            (b"3 2 2\n", Some(InputFormat::Metis)),
+        ] {
+            assert!(read_edges(data, format).is_err());
+        }
+
+        let (_, mut edges) = read_edges(&b"1 x\n"[..], None).unwrap();
+        assert!(edges.next().unwrap().is_err());
+    }
+}
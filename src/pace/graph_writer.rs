@@ -0,0 +1,265 @@
+use std::io::{Result, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::{graph::*, PROBLEM_ID};
+
+/// Magic bytes at the start of [`GraphFormat::Csr`] files
+pub const CSR_MAGIC: &[u8; 8] = b"DSCSR\0\0\x01";
+
+/// File formats a [`Graph`] can be written in. Unless stated otherwise, nodes are
+/// 1-indexed, so their ids match those of the DIMACS instance and of its solutions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    /// PACE DIMACS text (`p ds n m` followed by one edge per line)
+    #[default]
+    Dimacs,
+    /// One edge `u v` per line without header
+    EdgeList,
+    /// METIS adjacency lists; METIS forbids self-loops, so they are dropped
+    Metis,
+    /// Symmetric pattern matrix in Matrix Market coordinate format
+    #[serde(alias = "mtx")]
+    MatrixMarket,
+    GraphMl,
+    /// `{"number_of_nodes", "number_of_edges", "adjacency"}` where `adjacency[i]` lists the
+    /// neighbours of node `i + 1`
+    Json,
+    /// Binary compressed sparse rows, see [`write_csr`]
+    Csr,
+}
+
+impl GraphFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            GraphFormat::Dimacs => "gr",
+            GraphFormat::EdgeList => "edges",
+            GraphFormat::Metis => "graph",
+            GraphFormat::MatrixMarket => "mtx",
+            GraphFormat::GraphMl => "graphml",
+            GraphFormat::Json => "json",
+            GraphFormat::Csr => "csr",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            GraphFormat::GraphMl => "application/xml",
+            GraphFormat::Json => "application/json",
+            GraphFormat::Csr => "application/octet-stream",
+            _ => "text/plain",
+        }
+    }
+}
+
+pub fn write_graph<W: Write>(format: GraphFormat, graph: &Graph, writer: W) -> Result<()> {
+    match format {
+        GraphFormat::Dimacs => write_dimacs(graph, writer),
+        GraphFormat::EdgeList => write_edge_list(graph, writer),
+        GraphFormat::Metis => write_metis(graph, writer),
+        GraphFormat::MatrixMarket => write_matrix_market(graph, writer),
+        GraphFormat::GraphMl => write_graphml(graph, writer),
+        GraphFormat::Json => write_json_adjacency(graph, writer),
+        GraphFormat::Csr => write_csr(graph, writer),
+    }
+}
+
+pub fn write_dimacs<W: Write>(graph: &Graph, mut writer: W) -> Result<()> {
+    writeln!(
+        writer,
+        "p {PROBLEM_ID} {} {}",
+        graph.number_of_nodes(),
+        graph.number_of_edges()
+    )?;
+    write_edge_list(graph, writer)
+}
+
+pub fn write_edge_list<W: Write>(graph: &Graph, mut writer: W) -> Result<()> {
+    for Edge(u, v) in graph.edges() {
+        writeln!(writer, "{} {}", u + 1, v + 1)?;
+    }
+    writer.flush()
+}
+
+pub fn write_metis<W: Write>(graph: &Graph, mut writer: W) -> Result<()> {
+    let self_loops = graph.edges().filter(|Edge(u, v)| u == v).count() as NumEdges;
+    writeln!(
+        writer,
+        "{} {}",
+        graph.number_of_nodes(),
+        graph.number_of_edges() - self_loops
+    )?;
+
+    for u in graph.vertices() {
+        let mut separator = "";
+        for v in graph.neighbors_of(u).filter(|&v| v != u) {
+            write!(writer, "{separator}{}", v + 1)?;
+            separator = " ";
+        }
+        writeln!(writer)?;
+    }
+
+    writer.flush()
+}
+
+pub fn write_matrix_market<W: Write>(graph: &Graph, mut writer: W) -> Result<()> {
+    let n = graph.number_of_nodes();
+    writeln!(writer, "%%MatrixMarket matrix coordinate pattern symmetric")?;
+    writeln!(writer, "{n} {n} {}", graph.number_of_edges())?;
+
+    // symmetric matrices only list the lower triangle
+    for Edge(u, v) in graph.edges() {
+        writeln!(writer, "{} {}", v + 1, u + 1)?;
+    }
+
+    writer.flush()
+}
+
+pub fn write_graphml<W: Write>(graph: &Graph, mut writer: W) -> Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(writer, r#"  <graph id="G" edgedefault="undirected">"#)?;
+
+    for u in graph.vertices() {
+        writeln!(writer, r#"    <node id="n{}"/>"#, u + 1)?;
+    }
+
+    for Edge(u, v) in graph.edges() {
+        writeln!(
+            writer,
+            r#"    <edge source="n{}" target="n{}"/>"#,
+            u + 1,
+            v + 1
+        )?;
+    }
+
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</graphml>")?;
+    writer.flush()
+}
+
+pub fn write_json_adjacency<W: Write>(graph: &Graph, mut writer: W) -> Result<()> {
+    write!(
+        writer,
+        r#"{{"number_of_nodes":{},"number_of_edges":{},"adjacency":["#,
+        graph.number_of_nodes(),
+        graph.number_of_edges()
+    )?;
+
+    for u in graph.vertices() {
+        if u > 0 {
+            write!(writer, ",")?;
+        }
+        write!(writer, "[")?;
+        for (i, v) in graph.neighbors_of(u).enumerate() {
+            if i > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{}", v + 1)?;
+        }
+        write!(writer, "]")?;
+    }
+
+    write!(writer, "]}}")?;
+    writer.flush()
+}
+
+/// Writes the graph as little-endian binary CSR:
+///  - [`CSR_MAGIC`]
+///  - `n: u32` and `m: u64`, the number of nodes and edges
+///  - `n + 1` offsets of type `u64`
+///  - `offsets[n]` 0-indexed neighbours of type `u32`; the neighbours of node `u` are
+///    sorted and stored at `offsets[u]..offsets[u + 1]`
+///
+/// Each edge appears in the rows of both endpoints, except for self-loops.
+pub fn write_csr<W: Write>(graph: &Graph, mut writer: W) -> Result<()> {
+    writer.write_all(CSR_MAGIC)?;
+    writer.write_all(&graph.number_of_nodes().to_le_bytes())?;
+    writer.write_all(&graph.number_of_edges().to_le_bytes())?;
+
+    let mut offset = 0u64;
+    writer.write_all(&offset.to_le_bytes())?;
+    for degree in graph.degrees() {
+        offset += degree as u64;
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+
+    for u in graph.vertices() {
+        for v in graph.neighbors_of(u) {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::instance_reader::PaceReader;
+    use super::*;
+
+    fn graph() -> Graph {
+        // path 1-2-3 with a self-loop at 3 and an isolated node 4
+        Graph::try_from_edges(4, [Edge(0, 1), Edge(2, 1), Edge(2, 2)]).unwrap()
+    }
+
+    fn write(format: GraphFormat) -> String {
+        let mut buffer = Vec::new();
+        write_graph(format, &graph(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn text_formats() {
+        let dimacs = write(GraphFormat::Dimacs);
+        assert_eq!(dimacs, "p ds 4 3\n1 2\n2 3\n3 3\n");
+        let reread = Graph::try_from_pace_reader(PaceReader::try_new(dimacs.as_bytes()).unwrap());
+        assert_eq!(reread.unwrap(), graph());
+
+        assert_eq!(write(GraphFormat::EdgeList), "1 2\n2 3\n3 3\n");
+        assert_eq!(write(GraphFormat::Metis), "4 2\n2\n1 3\n2\n\n");
+        assert_eq!(
+            write(GraphFormat::MatrixMarket),
+            "%%MatrixMarket matrix coordinate pattern symmetric\n4 4 3\n2 1\n3 2\n3 3\n"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&write(GraphFormat::Json)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "number_of_nodes": 4,
+                "number_of_edges": 3,
+                "adjacency": [[2], [1, 3], [2, 3], []],
+            })
+        );
+
+        let graphml = write(GraphFormat::GraphMl);
+        assert_eq!(graphml.matches("<node ").count(), 4);
+        assert!(graphml.contains(r#"<edge source="n3" target="n3"/>"#));
+    }
+
+    #[test]
+    fn csr() {
+        let mut buffer = Vec::new();
+        write_csr(&graph(), &mut buffer).unwrap();
+
+        let (magic, rest) = buffer.split_at(8);
+        assert_eq!(magic, CSR_MAGIC);
+
+        let u32_at = |i: usize| u32::from_le_bytes(rest[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(rest[i..i + 8].try_into().unwrap());
+        assert_eq!(u32_at(0), 4);
+        assert_eq!(u64_at(4), 3);
+
+        let offsets: Vec<u64> = (0..5).map(|i| u64_at(12 + 8 * i)).collect();
+        assert_eq!(offsets, vec![0, 1, 3, 5, 5]);
+
+        let neighbors: Vec<u32> = (0..5).map(|i| u32_at(52 + 4 * i)).collect();
+        assert_eq!(neighbors, vec![1, 0, 2, 1, 2]);
+        assert_eq!(rest.len(), 52 + 4 * 5);
+    }
+}
@@ -137,8 +137,12 @@ impl<R: BufRead> PaceReader<R> {
             let from = parse_next_value!(parts, "Source node");
             let dest = parse_next_value!(parts, "Target node");
 
-            debug_assert!((1..=self.number_of_nodes).contains(&from));
-            debug_assert!((1..=self.number_of_nodes).contains(&dest));
+            // ids beyond the header are left to the caller, which may choose to ignore it
+            raise_error_unless!(
+                from > 0 && dest > 0,
+                ErrorKind::InvalidData,
+                "Node ids are 1-based; found 0"
+            );
 
             Ok(Some(Edge(from, dest)))
         } else {
@@ -0,0 +1,172 @@
+use super::graph::*;
+
+/// Cheap lower bounds on the size of a minimum dominating set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DomsetLowerBounds {
+    /// Each node dominates at most `max_deg + 1` nodes, hence `ceil(n / (max_deg + 1))`
+    pub max_degree: NumNodes,
+
+    /// Size of a greedily computed 2-packing, i.e. a set of nodes with pairwise
+    /// disjoint closed neighbourhoods; each of them needs its own dominator
+    pub packing: NumNodes,
+
+    /// Value of a feasible solution to the dual of the LP relaxation (a fractional packing)
+    pub lp: NumNodes,
+}
+
+impl DomsetLowerBounds {
+    pub fn compute(graph: &Graph) -> Self {
+        Self {
+            max_degree: max_degree_bound(
+                graph.number_of_nodes(),
+                graph.degrees().max().unwrap_or(0),
+            ),
+            packing: packing_bound(graph),
+            lp: lp_bound(graph),
+        }
+    }
+
+    pub fn best(&self) -> NumNodes {
+        self.max_degree.max(self.packing).max(self.lp)
+    }
+}
+
+/// Only needs the maximum degree and can hence be computed without building the graph
+pub fn max_degree_bound(number_of_nodes: NumNodes, max_deg: NumNodes) -> NumNodes {
+    number_of_nodes.div_ceil(max_deg + 1)
+}
+
+fn packing_bound(graph: &Graph) -> NumNodes {
+    // low-degree nodes block fewer candidates, so we try them first
+    let mut order: Vec<Node> = graph.vertices().collect();
+    order.sort_by_key(|&u| graph.degree_of(u));
+
+    let mut blocked = vec![false; graph.number_of_nodes() as usize];
+    let mut size = 0;
+    for u in order {
+        if graph.closed_neighbors_of(u).any(|v| blocked[v as usize]) {
+            continue;
+        }
+
+        for v in graph.closed_neighbors_of(u) {
+            blocked[v as usize] = true;
+        }
+        size += 1;
+    }
+
+    size
+}
+
+/// Tolerance for the floating-point sum of the fractional packing
+const LP_EPSILON: f64 = 1e-6;
+
+fn lp_bound(graph: &Graph) -> NumNodes {
+    // A fractional packing assigns y_v >= 0 to each node such that every closed
+    // neighbourhood carries a total weight of at most one. Setting y_v to the inverse
+    // of the largest closed neighbourhood v belongs to is always feasible; we then
+    // greedily raise every y_v into the remaining slack.
+    let closed_size = |u: Node| (graph.degree_of(u) + 1) as f64;
+
+    let mut weight: Vec<f64> = graph
+        .vertices()
+        .map(|v| {
+            1.0 / graph
+                .closed_neighbors_of(v)
+                .map(closed_size)
+                .fold(1.0, f64::max)
+        })
+        .collect();
+
+    let mut load: Vec<f64> = graph
+        .vertices()
+        .map(|u| {
+            graph
+                .closed_neighbors_of(u)
+                .map(|v| weight[v as usize])
+                .sum()
+        })
+        .collect();
+
+    for v in graph.vertices() {
+        let slack = graph
+            .closed_neighbors_of(v)
+            .map(|u| 1.0 - load[u as usize])
+            .fold(f64::INFINITY, f64::min);
+
+        if slack > LP_EPSILON {
+            weight[v as usize] += slack;
+            for u in graph.closed_neighbors_of(v) {
+                load[u as usize] += slack;
+            }
+        }
+    }
+
+    let total: f64 = weight.iter().sum();
+    (total - LP_EPSILON).ceil().max(0.0) as NumNodes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path(n: NumNodes) -> Graph {
+        Graph::try_from_edges(n, (1..n).map(|u| Edge(u - 1, u))).unwrap()
+    }
+
+    fn star(leaves: NumNodes) -> Graph {
+        Graph::try_from_edges(leaves + 1, (1..=leaves).map(|u| Edge(0, u))).unwrap()
+    }
+
+    #[test]
+    fn path_bounds() {
+        // the domination number of a path on n nodes is ceil(n / 3)
+        for n in 1..30 {
+            let bounds = DomsetLowerBounds::compute(&path(n));
+            assert_eq!(bounds.max_degree, n.div_ceil(3.min(n)), "n={n}");
+            assert!(bounds.best() <= n.div_ceil(3), "n={n}");
+            assert_eq!(bounds.packing, n.div_ceil(3), "n={n}");
+        }
+    }
+
+    #[test]
+    fn star_bounds() {
+        let bounds = DomsetLowerBounds::compute(&star(10));
+        assert_eq!(bounds.max_degree, 1);
+        assert_eq!(bounds.packing, 1);
+        assert_eq!(bounds.lp, 1);
+    }
+
+    #[test]
+    fn isolated_nodes() {
+        let graph = Graph::try_from_edges(4, [Edge(0, 1)]).unwrap();
+        let bounds = DomsetLowerBounds::compute(&graph);
+        assert_eq!(bounds.packing, 3);
+        assert_eq!(bounds.lp, 3);
+        assert_eq!(bounds.best(), 3);
+    }
+
+    #[test]
+    fn bounds_are_valid() {
+        // brute-force the domination number of small pseudo-random graphs
+        let mut random = RandomGraphs::new(987654321);
+
+        for n in [4, 8, 12] {
+            for _ in 0..20 {
+                let graph = random.graph(n, n as NumEdges);
+
+                let domination_number = (0u32..1 << n)
+                    .filter(|set| {
+                        graph
+                            .vertices()
+                            .all(|u| graph.closed_neighbors_of(u).any(|v| set & (1 << v) != 0))
+                    })
+                    .map(|set| set.count_ones())
+                    .min()
+                    .unwrap();
+
+                let bounds = DomsetLowerBounds::compute(&graph);
+                assert!(bounds.best() <= domination_number, "{bounds:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use super::{
+    fingerprint::{fingerprint, Fingerprint},
+    graph::*,
+    lower_bounds::{max_degree_bound, DomsetLowerBounds},
+};
+
+/// Default number of edge scans the diameter computation may spend before it
+/// gives up and reports bounds instead of the exact value.
+pub const DEFAULT_DIAMETER_WORK_LIMIT: u64 = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiameterBounds {
+    pub lower: NumNodes,
+    pub upper: NumNodes,
+}
+
+impl DiameterBounds {
+    pub fn exact(&self) -> Option<NumNodes> {
+        (self.lower == self.upper).then_some(self.lower)
+    }
+}
+
+/// Structural properties of a graph as stored in the `Instance` table.
+///
+/// The diameter of a disconnected graph is the largest diameter of any of its
+/// connected components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GraphMetadata {
+    pub min_deg: NumNodes,
+    pub max_deg: NumNodes,
+    pub num_ccs: NumNodes,
+    pub nodes_largest_cc: NumNodes,
+    pub bipartite: bool,
+    pub diameter: DiameterBounds,
+    /// Best of the [`DomsetLowerBounds`] on the domination number
+    pub lower_bound: NumNodes,
+    /// Isomorphism-invariant hash used to detect likely duplicates
+    pub fingerprint: Fingerprint,
+}
+
+impl GraphMetadata {
+    pub fn compute(graph: &Graph) -> Self {
+        Self::compute_with_work_limit(graph, DEFAULT_DIAMETER_WORK_LIMIT)
+    }
+
+    pub fn compute_with_work_limit(graph: &Graph, diameter_work_limit: u64) -> Self {
+        let mut meta = Self {
+            min_deg: graph.degrees().min().unwrap_or(0),
+            max_deg: graph.degrees().max().unwrap_or(0),
+            bipartite: true,
+            lower_bound: DomsetLowerBounds::compute(graph).best(),
+            fingerprint: fingerprint(graph),
+            ..Default::default()
+        };
+
+        let mut bfs = Bfs::new(graph);
+        let mut visited = vec![false; graph.number_of_nodes() as usize];
+
+        for root in graph.vertices() {
+            if visited[root as usize] {
+                continue;
+            }
+
+            bfs.run(graph, root);
+            meta.num_ccs += 1;
+            meta.nodes_largest_cc = meta.nodes_largest_cc.max(bfs.order.len() as NumNodes);
+
+            for &u in &bfs.order {
+                visited[u as usize] = true;
+            }
+
+            // an edge within a BFS level closes an odd cycle
+            if meta.bipartite {
+                meta.bipartite = bfs.order.iter().all(|&u| {
+                    graph
+                        .neighbors_of(u)
+                        .all(|v| bfs.dist[u as usize] != bfs.dist[v as usize])
+                });
+            }
+
+            let bounds = component_diameter(graph, &mut bfs, diameter_work_limit);
+            meta.diameter.lower = meta.diameter.lower.max(bounds.lower);
+            meta.diameter.upper = meta.diameter.upper.max(bounds.upper);
+        }
+
+        meta
+    }
+}
+
+/// The part of [`GraphMetadata`] that a single pass over the edges determines, using
+/// memory linear in the number of nodes rather than in the number of edges. Meant for
+/// graphs too large to be built; the remaining properties are left to a later backfill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamedMetadata {
+    pub min_deg: NumNodes,
+    pub max_deg: NumNodes,
+    pub num_ccs: NumNodes,
+    pub nodes_largest_cc: NumNodes,
+    pub bipartite: bool,
+    /// The max-degree bound of [`DomsetLowerBounds`], the only one not needing the graph
+    pub lower_bound: NumNodes,
+}
+
+impl StreamedMetadata {
+    /// `edges` must not contain duplicates, as is the case for normalised instances
+    pub fn try_compute(
+        number_of_nodes: NumNodes,
+        edges: impl IntoIterator<Item = std::io::Result<Edge>>,
+    ) -> std::io::Result<Self> {
+        let n = number_of_nodes as usize;
+        let mut degrees = vec![0 as NumNodes; n];
+        let mut components = ParityUnionFind::new(n);
+        let mut bipartite = true;
+
+        for edge in edges {
+            let Edge(u, v) = edge?;
+            if u.max(v) >= number_of_nodes {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Edge contains node id larger than the number of nodes",
+                ));
+            }
+
+            degrees[u as usize] += 1;
+            if u != v {
+                degrees[v as usize] += 1;
+            }
+
+            bipartite &= components.join_with_odd_parity(u, v);
+        }
+
+        let max_deg = degrees.iter().copied().max().unwrap_or(0);
+        let (num_ccs, nodes_largest_cc) = components.component_sizes();
+
+        Ok(Self {
+            min_deg: degrees.iter().copied().min().unwrap_or(0),
+            max_deg,
+            num_ccs,
+            nodes_largest_cc,
+            bipartite,
+            lower_bound: max_degree_bound(number_of_nodes, max_deg),
+        })
+    }
+}
+
+/// Union-find that also tracks the parity of the path between each node and its root,
+/// which tells whether the components seen so far can be 2-coloured
+struct ParityUnionFind {
+    parent: Vec<Node>,
+    /// Parity of the path to the parent
+    parity: Vec<bool>,
+    size: Vec<NumNodes>,
+}
+
+impl ParityUnionFind {
+    fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n as Node).collect(),
+            parity: vec![false; n],
+            size: vec![1; n],
+        }
+    }
+
+    /// Returns the root of `u` and the parity of the path to it; compresses the path
+    fn find(&mut self, u: Node) -> (Node, bool) {
+        let mut root = u;
+        let mut parity = false;
+        while self.parent[root as usize] != root {
+            parity ^= self.parity[root as usize];
+            root = self.parent[root as usize];
+        }
+
+        // point every node of the path directly to the root
+        let mut node = u;
+        let mut node_parity = parity;
+        while self.parent[node as usize] != root && node != root {
+            let next = self.parent[node as usize];
+            let next_parity = node_parity ^ self.parity[node as usize];
+            self.parent[node as usize] = root;
+            self.parity[node as usize] = node_parity;
+            node = next;
+            node_parity = next_parity;
+        }
+
+        (root, parity)
+    }
+
+    /// Merges the components of `u` and `v` such that they receive different colours;
+    /// returns `false` if they already have the same colour
+    fn join_with_odd_parity(&mut self, u: Node, v: Node) -> bool {
+        let (ru, pu) = self.find(u);
+        let (rv, pv) = self.find(v);
+        if ru == rv {
+            return pu != pv;
+        }
+
+        let (small, large) = if self.size[ru as usize] < self.size[rv as usize] {
+            (ru, rv)
+        } else {
+            (rv, ru)
+        };
+
+        self.parent[small as usize] = large;
+        self.parity[small as usize] = !(pu ^ pv);
+        self.size[large as usize] += self.size[small as usize];
+        true
+    }
+
+    /// Number of components and size of the largest one
+    fn component_sizes(&self) -> (NumNodes, NumNodes) {
+        (0..self.parent.len())
+            .filter(|&u| self.parent[u] == u as Node)
+            .fold((0, 0), |(num, largest), root| {
+                (num + 1, largest.max(self.size[root]))
+            })
+    }
+}
+
+const UNREACHED: NumNodes = NumNodes::MAX;
+
+/// Reusable BFS buffers; only the entries touched by the previous run are reset.
+struct Bfs {
+    dist: Vec<NumNodes>,
+    order: Vec<Node>,
+    work: u64,
+}
+
+impl Bfs {
+    fn new(graph: &Graph) -> Self {
+        Self {
+            dist: vec![UNREACHED; graph.number_of_nodes() as usize],
+            order: Vec::new(),
+            work: 0,
+        }
+    }
+
+    /// Visits the component of `source` and returns the eccentricity of `source`.
+    /// Afterwards `order` holds the component in BFS order, i.e. sorted by distance.
+    fn run(&mut self, graph: &Graph, source: Node) -> NumNodes {
+        for &u in &self.order {
+            self.dist[u as usize] = UNREACHED;
+        }
+        self.order.clear();
+
+        self.dist[source as usize] = 0;
+        self.order.push(source);
+
+        let mut head = 0;
+        while let Some(&u) = self.order.get(head) {
+            head += 1;
+            let next = self.dist[u as usize] + 1;
+            for v in graph.neighbors_of(u) {
+                if self.dist[v as usize] == UNREACHED {
+                    self.dist[v as usize] = next;
+                    self.order.push(v);
+                }
+            }
+            self.work += graph.degree_of(u) as u64;
+        }
+
+        self.dist[*self.order.last().unwrap() as usize]
+    }
+
+    fn farthest(&self) -> Node {
+        *self.order.last().unwrap()
+    }
+}
+
+/// Computes the diameter of the component last visited by `bfs` using the iFUB scheme
+/// (Crescenzi et al.): after a double sweep, it performs a BFS from a high-degree
+/// node and processes its levels bottom-up until lower and upper bound meet.
+fn component_diameter(graph: &Graph, bfs: &mut Bfs, work_limit: u64) -> DiameterBounds {
+    // double sweep for an initial lower bound
+    let mut lower = bfs.run(graph, bfs.farthest());
+
+    let center = bfs
+        .order
+        .iter()
+        .copied()
+        .max_by_key(|&u| graph.degree_of(u))
+        .unwrap();
+
+    let ecc_center = bfs.run(graph, center);
+    lower = lower.max(ecc_center);
+    let mut upper = 2 * ecc_center;
+
+    let levels: Vec<(Node, NumNodes)> = bfs
+        .order
+        .iter()
+        .map(|&u| (u, bfs.dist[u as usize]))
+        .collect();
+
+    let mut remaining = levels.as_slice();
+    let mut level = ecc_center;
+    while lower < upper && level > 0 {
+        while let Some((&(u, dist), rest)) = remaining.split_last() {
+            if dist != level {
+                break;
+            }
+
+            if bfs.work > work_limit {
+                return DiameterBounds { lower, upper };
+            }
+
+            lower = lower.max(bfs.run(graph, u));
+            remaining = rest;
+        }
+
+        // every pair of nodes in shallower levels is at most 2(level - 1) apart
+        upper = lower.max(2 * (level - 1));
+        level -= 1;
+    }
+
+    DiameterBounds { lower, upper }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path(n: NumNodes) -> Graph {
+        Graph::try_from_edges(n, (1..n).map(|u| Edge(u - 1, u))).unwrap()
+    }
+
+    fn cycle(n: NumNodes) -> Graph {
+        Graph::try_from_edges(n, (0..n).map(|u| Edge(u, (u + 1) % n))).unwrap()
+    }
+
+    #[test]
+    fn path_metadata() {
+        let meta = GraphMetadata::compute(&path(10));
+        assert_eq!(meta.min_deg, 1);
+        assert_eq!(meta.max_deg, 2);
+        assert_eq!(meta.num_ccs, 1);
+        assert_eq!(meta.nodes_largest_cc, 10);
+        assert!(meta.bipartite);
+        assert_eq!(meta.diameter.exact(), Some(9));
+    }
+
+    fn streamed(graph: &Graph) -> StreamedMetadata {
+        StreamedMetadata::try_compute(graph.number_of_nodes(), graph.edges().map(Ok)).unwrap()
+    }
+
+    #[test]
+    fn streamed_matches_graph_metadata() {
+        let mut random = RandomGraphs::new(7654321);
+
+        let mut graphs = vec![path(10), cycle(6), cycle(7)];
+        for n in [5, 20, 50] {
+            for _ in 0..10 {
+                let m = n / 2 + random.below(n);
+                graphs.push(random.graph(n, m as NumEdges));
+            }
+        }
+
+        for graph in graphs {
+            let full = GraphMetadata::compute(&graph);
+            let streamed = streamed(&graph);
+
+            assert_eq!(streamed.min_deg, full.min_deg);
+            assert_eq!(streamed.max_deg, full.max_deg);
+            assert_eq!(streamed.num_ccs, full.num_ccs);
+            assert_eq!(streamed.nodes_largest_cc, full.nodes_largest_cc);
+            assert_eq!(streamed.bipartite, full.bipartite);
+            assert!(streamed.lower_bound <= full.lower_bound);
+        }
+
+        assert!(
+            StreamedMetadata::try_compute(3, [Ok(Edge(0, 3))]).is_err(),
+            "node ids are checked"
+        );
+    }
+
+    #[test]
+    fn cycle_bipartite() {
+        assert!(GraphMetadata::compute(&cycle(6)).bipartite);
+        assert!(!GraphMetadata::compute(&cycle(7)).bipartite);
+        assert_eq!(GraphMetadata::compute(&cycle(7)).diameter.exact(), Some(3));
+    }
+
+    #[test]
+    fn disconnected() {
+        // triangle, path of length 3, and an isolated node
+        let graph = Graph::try_from_edges(
+            8,
+            [
+                Edge(0, 1),
+                Edge(1, 2),
+                Edge(2, 0),
+                Edge(3, 4),
+                Edge(4, 5),
+                Edge(5, 6),
+            ],
+        )
+        .unwrap();
+
+        let meta = GraphMetadata::compute(&graph);
+        assert_eq!(meta.min_deg, 0);
+        assert_eq!(meta.max_deg, 2);
+        assert_eq!(meta.num_ccs, 3);
+        assert_eq!(meta.nodes_largest_cc, 4);
+        assert!(!meta.bipartite);
+        assert_eq!(meta.diameter.exact(), Some(3));
+    }
+
+    #[test]
+    fn diameter_matches_all_pairs_bfs() {
+        // a few deterministic pseudo-random graphs
+        let mut random = RandomGraphs::new(1234567);
+
+        for n in [5, 20, 50] {
+            for _ in 0..10 {
+                let graph = random.graph(n, (n + n / 5) as NumEdges);
+
+                let mut bfs = Bfs::new(&graph);
+                let naive = graph.vertices().map(|u| bfs.run(&graph, u)).max().unwrap();
+
+                let meta = GraphMetadata::compute(&graph);
+                assert_eq!(meta.diameter.exact(), Some(naive));
+            }
+        }
+    }
+
+    #[test]
+    fn diameter_work_limit() {
+        let bounds = GraphMetadata::compute_with_work_limit(&cycle(100), 0).diameter;
+        assert!(bounds.lower <= 50 && 50 <= bounds.upper);
+    }
+}
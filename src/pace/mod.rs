@@ -1,8 +1,15 @@
 pub const PROBLEM_ID: &str = "ds";
 
+pub mod codec;
+pub mod fingerprint;
 pub mod graph;
+pub mod graph_reader;
+pub mod graph_writer;
 pub mod instance_reader;
 pub mod instance_writer;
+pub mod lower_bounds;
+pub mod metadata;
+pub mod normalize;
 
 pub mod solution;
-pub use solution::Solution;
+pub use solution::{DomsetReport, Solution, SolutionComparison};
@@ -0,0 +1,484 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use sha1::{Digest, Sha1};
+
+use super::{
+    graph::*,
+    graph_reader::{decompress, read_edges, DeclaredSize, InputFormat},
+    PROBLEM_ID,
+};
+
+pub type Result<T> = std::io::Result<T>;
+
+/// Edges kept in memory before a sorted run is spilled to disk (128 MiB worth of edges)
+pub const DEFAULT_MAX_EDGES_IN_MEMORY: usize = 1 << 24;
+
+#[derive(Clone, Debug)]
+pub struct NormalizeOptions {
+    /// Reject instances whose edges do not match the number of nodes and edges in the header
+    pub check_header: bool,
+    /// Format of the input passed to [`normalize_input`]; detected if unset
+    pub format: Option<InputFormat>,
+    pub max_edges_in_memory: usize,
+    /// Directory for the sorted runs of instances exceeding `max_edges_in_memory`
+    pub temp_dir: PathBuf,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            check_header: true,
+            format: None,
+            max_edges_in_memory: DEFAULT_MAX_EDGES_IN_MEMORY,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NormalizedInstance {
+    pub number_of_nodes: NumNodes,
+    pub number_of_edges: NumEdges,
+    /// SHA1 of the normalised DIMACS text as lower-case hex
+    pub hash: String,
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// File in a temporary directory that is deleted once dropped
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl TempFile {
+    pub fn create_in(dir: &Path) -> Result<Self> {
+        let path = dir.join(format!("stride-{}.tmp", uuid::Uuid::new_v4().simple()));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(Self { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Sorted and duplicate-free sequence of edges stored as pairs of little-endian u32
+struct SortedRun {
+    file: TempFile,
+    len: u64,
+}
+
+struct RunWriter {
+    file: TempFile,
+    writer: BufWriter<File>,
+    len: u64,
+}
+
+impl RunWriter {
+    fn create_in(dir: &Path) -> Result<Self> {
+        let file = TempFile::create_in(dir)?;
+        let writer = BufWriter::new(file.file().try_clone()?);
+        Ok(Self {
+            file,
+            writer,
+            len: 0,
+        })
+    }
+
+    fn push(&mut self, Edge(u, v): Edge) -> Result<()> {
+        self.writer.write_all(&u.to_le_bytes())?;
+        self.writer.write_all(&v.to_le_bytes())?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<SortedRun> {
+        self.writer.flush()?;
+        Ok(SortedRun {
+            file: self.file,
+            len: self.len,
+        })
+    }
+}
+
+struct RunReader {
+    // keeps the file alive until the run is consumed
+    _file: TempFile,
+    reader: BufReader<File>,
+    remaining: u64,
+}
+
+impl SortedRun {
+    fn into_edges(self) -> Result<RunReader> {
+        let mut file = self.file.file().try_clone()?;
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(RunReader {
+            _file: self.file,
+            reader: BufReader::new(file),
+            remaining: self.len,
+        })
+    }
+}
+
+impl Iterator for RunReader {
+    type Item = Result<Edge>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let mut buf = [0u8; 8];
+        Some(self.reader.read_exact(&mut buf).map(|_| {
+            Edge(
+                Node::from_le_bytes(buf[..4].try_into().unwrap()),
+                Node::from_le_bytes(buf[4..].try_into().unwrap()),
+            )
+        }))
+    }
+}
+
+fn spill(buffer: &mut Vec<Edge>, dir: &Path) -> Result<SortedRun> {
+    buffer.sort_unstable();
+    buffer.dedup();
+
+    let mut writer = RunWriter::create_in(dir)?;
+    for &edge in buffer.iter() {
+        writer.push(edge)?;
+    }
+    buffer.clear();
+
+    writer.finish()
+}
+
+/// Merges `runs` into a single run and drops duplicates occurring in several runs
+fn merge_runs(runs: Vec<SortedRun>, dir: &Path) -> Result<SortedRun> {
+    let mut sources = runs
+        .into_iter()
+        .map(SortedRun::into_edges)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut heap = BinaryHeap::with_capacity(sources.len());
+    for (i, source) in sources.iter_mut().enumerate() {
+        if let Some(edge) = source.next() {
+            heap.push(Reverse((edge?, i)));
+        }
+    }
+
+    let mut writer = RunWriter::create_in(dir)?;
+    let mut last = None;
+    while let Some(Reverse((edge, i))) = heap.pop() {
+        if last != Some(edge) {
+            writer.push(edge)?;
+            last = Some(edge);
+        }
+
+        if let Some(next) = sources[i].next() {
+            heap.push(Reverse((next?, i)));
+        }
+    }
+
+    writer.finish()
+}
+
+/// Bit set of the nodes incident to at least one edge
+#[derive(Default)]
+struct NodeSet {
+    words: Vec<u64>,
+}
+
+impl NodeSet {
+    fn insert(&mut self, u: Node) {
+        let word = u as usize / 64;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (u % 64);
+    }
+
+    fn into_ranks(self) -> NodeRanks {
+        let mut count = 0;
+        let prefix = self
+            .words
+            .iter()
+            .map(|w| {
+                let rank = count;
+                count += w.count_ones() as Node;
+                rank
+            })
+            .collect();
+
+        NodeRanks {
+            words: self.words,
+            prefix,
+            len: count,
+        }
+    }
+}
+
+/// Maps each member of a [`NodeSet`] to the number of smaller members
+struct NodeRanks {
+    words: Vec<u64>,
+    prefix: Vec<Node>,
+    len: NumNodes,
+}
+
+impl NodeRanks {
+    fn rank(&self, u: Node) -> Node {
+        let word = u as usize / 64;
+        let below = self.words[word] & ((1u64 << (u % 64)) - 1);
+        self.prefix[word] + below.count_ones() as Node
+    }
+}
+
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha1,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads a PACE instance from `reader` and writes its normalised form to `writer`.
+///
+/// The normalised form lists each distinct edge once as `u v` with `u <= v` in
+/// lexicographic order; isolated nodes are removed and the remaining ones renumbered
+/// consecutively. Edges are sorted in memory as long as there are at most
+/// `max_edges_in_memory` of them and by an external merge sort otherwise, so memory
+/// usage is bounded by that limit plus one bit per node id.
+pub fn normalize_pace<R: BufRead, W: Write>(
+    reader: R,
+    writer: W,
+    options: &NormalizeOptions,
+) -> Result<NormalizedInstance> {
+    let (declared, edges) = read_edges(reader, Some(InputFormat::Dimacs))?;
+    normalize_edges(declared, edges, writer, options)
+}
+
+/// Like [`normalize_pace`], but reads any [`InputFormat`], possibly gzip-compressed
+pub fn normalize_input<R: BufRead, W: Write>(
+    reader: R,
+    writer: W,
+    options: &NormalizeOptions,
+) -> Result<NormalizedInstance> {
+    let (declared, edges) = read_edges(decompress(reader)?, options.format)?;
+    normalize_edges(declared, edges, writer, options)
+}
+
+/// Normalises the (0-indexed) `edges`; `declared` is checked if `options.check_header` is set
+pub fn normalize_edges<W: Write>(
+    declared: DeclaredSize,
+    edges: impl Iterator<Item = Result<Edge>>,
+    writer: W,
+    options: &NormalizeOptions,
+) -> Result<NormalizedInstance> {
+    let max_edges_in_memory = options.max_edges_in_memory.max(1);
+    let expected_edges = declared.number_of_edges.unwrap_or(0) as usize;
+
+    let mut used_nodes = NodeSet::default();
+    let mut buffer = Vec::with_capacity(max_edges_in_memory.min(expected_edges));
+    let mut runs = Vec::new();
+
+    for edge in edges {
+        let edge = edge?.normalized();
+        if options.check_header
+            && declared
+                .number_of_nodes
+                .is_some_and(|n| edge.max_node() >= n)
+        {
+            return Err(invalid_data(
+                "Edge contains node id that is larger than the number of nodes in the header",
+            ));
+        }
+
+        used_nodes.insert(edge.0);
+        used_nodes.insert(edge.1);
+
+        buffer.push(edge);
+        if buffer.len() >= max_edges_in_memory {
+            runs.push(spill(&mut buffer, &options.temp_dir)?);
+        }
+    }
+
+    let (number_of_edges, edges): (NumEdges, Box<dyn Iterator<Item = Result<Edge>>>) =
+        if runs.is_empty() {
+            buffer.sort_unstable();
+            buffer.dedup();
+            (
+                buffer.len() as NumEdges,
+                Box::new(buffer.into_iter().map(Ok)),
+            )
+        } else {
+            if !buffer.is_empty() {
+                runs.push(spill(&mut buffer, &options.temp_dir)?);
+            }
+            drop(buffer);
+
+            let merged = merge_runs(runs, &options.temp_dir)?;
+            (merged.len, Box::new(merged.into_edges()?))
+        };
+
+    if options.check_header
+        && declared
+            .number_of_edges
+            .is_some_and(|m| m != number_of_edges)
+    {
+        return Err(invalid_data(
+            "Number of edges after deduplication does not match the number of edges in the header",
+        ));
+    }
+
+    if number_of_edges == 0 {
+        return Err(invalid_data("Instance has no edges"));
+    }
+
+    // relabeling by rank is monotone, so the edges remain sorted
+    let ranks = used_nodes.into_ranks();
+
+    let mut writer = HashingWriter {
+        inner: writer,
+        hasher: Sha1::new(),
+    };
+
+    writeln!(writer, "p {PROBLEM_ID} {} {number_of_edges}", ranks.len)?;
+    for edge in edges {
+        let Edge(u, v) = edge?;
+        writeln!(writer, "{} {}", ranks.rank(u) + 1, ranks.rank(v) + 1)?;
+    }
+    writer.flush()?;
+
+    Ok(NormalizedInstance {
+        number_of_nodes: ranks.len,
+        number_of_edges,
+        hash: format!("{:x}", writer.hasher.finalize()),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::instance_reader::PaceReader;
+    use super::*;
+
+    fn normalize(data: &str, options: &NormalizeOptions) -> Result<(NormalizedInstance, String)> {
+        let mut output = Vec::new();
+        let info = normalize_pace(data.as_bytes(), &mut output, options)?;
+        Ok((info, String::from_utf8(output).unwrap()))
+    }
+
+    #[test]
+    fn normalize_in_memory() {
+        let (info, output) = normalize(
+            "c comment\np ds 7 5\n7 5\n2 5\n5 7\n5 2\n2 2\n",
+            &NormalizeOptions {
+                check_header: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(output, "p ds 3 3\n1 1\n1 2\n2 3\n");
+        assert_eq!(info.number_of_nodes, 3);
+        assert_eq!(info.number_of_edges, 3);
+
+        let mut hasher = Sha1::new();
+        hasher.update(output.as_bytes());
+        assert_eq!(info.hash, format!("{:x}", hasher.finalize()));
+
+        // the header counts the duplicates
+        assert!(normalize("p ds 7 5\n7 5\n2 5\n5 7\n5 2\n2 2\n", &Default::default()).is_err());
+        assert!(normalize("p ds 3 1\n1 4\n", &Default::default()).is_err());
+        assert!(normalize("p ds 3 0\n", &Default::default()).is_err());
+    }
+
+    #[test]
+    fn external_sort_matches_in_memory() {
+        let n = 200;
+        let mut data = String::new();
+        let mut num_lines = 0;
+        for i in 0..1000u32 {
+            // plenty of duplicates spread over several runs
+            let (u, v) = ((i * 7919) % n + 1, (i * 104729 + 13) % n + 1);
+            data.push_str(&format!("{u} {v}\n"));
+            num_lines += 1;
+        }
+        let data = format!("p ds {} {num_lines}\n{data}", 2 * n);
+
+        let in_memory = NormalizeOptions {
+            check_header: false,
+            ..Default::default()
+        };
+        let external = NormalizeOptions {
+            max_edges_in_memory: 64,
+            ..in_memory.clone()
+        };
+
+        let expected = normalize(&data, &in_memory).unwrap();
+        assert_eq!(normalize(&data, &external).unwrap(), expected);
+
+        let reader = PaceReader::try_new(expected.1.as_bytes()).unwrap();
+        assert_eq!(reader.number_of_nodes(), expected.0.number_of_nodes);
+        let edges: Vec<_> = reader.map(|e| e.unwrap()).collect();
+        assert!(edges.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(edges.len() as NumEdges, expected.0.number_of_edges);
+    }
+
+    #[test]
+    fn other_formats() {
+        let expected = normalize("p ds 3 2\n1 2\n2 3\n", &Default::default()).unwrap();
+
+        for data in [
+            &b"# snap\n10 20\n20 30\n"[..],
+            b"% metis\n3 2\n2\n1 3\n2\n",
+            b"%%MatrixMarket matrix coordinate pattern general\n4 4 4\n1 2\n2 1\n4 2\n2 4\n",
+        ] {
+            let mut output = Vec::new();
+            let info = normalize_input(data, &mut output, &Default::default()).unwrap();
+            assert_eq!((info, String::from_utf8(output).unwrap()), expected);
+        }
+
+        // the header of METIS files is checked, too
+        let mut output = Vec::new();
+        assert!(normalize_input(
+            &b"% metis\n3 5\n2\n1 3\n2\n"[..],
+            &mut output,
+            &Default::default()
+        )
+        .is_err());
+    }
+}
@@ -1,10 +1,7 @@
 use sha1::{digest::Output, Digest, Sha1};
 
 use super::graph::*;
-use std::{
-    collections::HashSet,
-    io::{BufRead, Write},
-};
+use std::io::{BufRead, Write};
 
 pub type Result<T> = std::io::Result<T>;
 
@@ -12,6 +9,40 @@ pub struct Solution {
     pub solution: Vec<Node>,
 }
 
+/// Outcome of [`Solution::verify_domset`]; all node ids are 0-indexed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DomsetReport {
+    pub score: NumNodes,
+
+    pub num_undominated: NumNodes,
+    pub undominated: Vec<Node>,
+
+    pub num_redundant: NumNodes,
+    pub redundant: Vec<Node>,
+}
+
+impl DomsetReport {
+    pub fn is_valid(&self) -> bool {
+        self.num_undominated == 0
+    }
+}
+
+/// Outcome of [`Solution::compare`]; all node ids are 0-indexed and sorted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SolutionComparison {
+    pub num_common: NumNodes,
+    pub only_in_first: Vec<Node>,
+    pub only_in_second: Vec<Node>,
+
+    /// Nodes dominated by `only_in_first` but not by `only_in_second`
+    pub dominated_only_by_first: Vec<Node>,
+    /// Nodes dominated by `only_in_second` but not by `only_in_first`
+    pub dominated_only_by_second: Vec<Node>,
+
+    /// Size of the intersection over the size of the union; 1 if both are empty
+    pub jaccard: f64,
+}
+
 impl Solution {
     pub fn from_0indexed_vec(solution: Vec<Node>) -> Self {
         Self { solution }
@@ -98,26 +129,69 @@ impl Solution {
     }
 
     /// Verifies that the solution is a valid dominating set for the given graph.
-    pub fn valid_domset_for_instance(
-        &self,
-        n: NumNodes,
-        edges: impl Iterator<Item = Edge>,
-    ) -> Result<bool> {
-        // TODO: we are building a complete adj list; we should refactor that out!
-        let mut adjlist = (0..n).map(|_| Vec::new()).collect::<Vec<_>>();
-        for Edge(u, v) in edges {
-            if u.max(v) >= n {
+    pub fn valid_domset_for_instance(&self, graph: &Graph) -> Result<bool> {
+        Ok(self.verify_domset(graph, 0)?.is_valid())
+    }
+
+    /// Checks the solution against `graph` and reports which nodes are left undominated
+    /// and which solution nodes could be removed individually without losing any domination.
+    /// At most `max_reported` nodes are listed per category; the totals are always exact.
+    pub fn verify_domset(&self, graph: &Graph, max_reported: usize) -> Result<DomsetReport> {
+        let n = graph.number_of_nodes();
+
+        // number of solution nodes in the closed neighbourhood of each node
+        let mut covered_by = vec![0 as NumNodes; n as usize];
+        for &u in &self.solution {
+            if u >= n {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
-                    "Edge contains node id larger than the number of nodes",
+                    "Node id in solution larger than the number of nodes",
                 ));
             }
 
-            adjlist[u as usize].push(v);
-            adjlist[v as usize].push(u);
+            for v in graph.closed_neighbors_of(u) {
+                covered_by[v as usize] += 1;
+            }
         }
 
-        let mut covered = HashSet::<Node>::with_capacity(n as usize);
+        let mut report = DomsetReport {
+            score: self.solution.len() as NumNodes,
+            ..Default::default()
+        };
+
+        for u in graph.vertices() {
+            if covered_by[u as usize] == 0 {
+                report.num_undominated += 1;
+                if report.undominated.len() < max_reported {
+                    report.undominated.push(u);
+                }
+            }
+        }
+
+        for &u in &self.solution {
+            if graph
+                .closed_neighbors_of(u)
+                .all(|v| covered_by[v as usize] > 1)
+            {
+                report.num_redundant += 1;
+                if report.redundant.len() < max_reported {
+                    report.redundant.push(u);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Greedily drops nodes whose closed neighbourhood stays dominated by the remaining
+    /// ones, in the order they appear in the solution. The result is a minimal dominating
+    /// set if `self` is a valid one (but not necessarily a minimum one).
+    pub fn prune_redundant(&self, graph: &Graph) -> Result<Solution> {
+        let n = graph.number_of_nodes();
+
+        // number of solution nodes in the closed neighbourhood of each node
+        let mut covered_by = vec![0 as NumNodes; n as usize];
+        let mut in_solution = vec![false; n as usize];
         for &u in &self.solution {
             if u >= n {
                 return Err(std::io::Error::new(
@@ -126,11 +200,94 @@ impl Solution {
                 ));
             }
 
-            covered.insert(u);
-            covered.extend(adjlist[u as usize].iter().copied());
+            if !std::mem::replace(&mut in_solution[u as usize], true) {
+                for v in graph.closed_neighbors_of(u) {
+                    covered_by[v as usize] += 1;
+                }
+            }
+        }
+
+        let mut pruned = Vec::with_capacity(self.solution.len());
+        for &u in &self.solution {
+            if !in_solution[u as usize] {
+                continue; // duplicate or already removed
+            }
+
+            if graph
+                .closed_neighbors_of(u)
+                .all(|v| covered_by[v as usize] > 1)
+            {
+                in_solution[u as usize] = false;
+                for v in graph.closed_neighbors_of(u) {
+                    covered_by[v as usize] -= 1;
+                }
+            } else {
+                in_solution[u as usize] = false; // skip later duplicates
+                pruned.push(u);
+            }
+        }
+
+        Ok(Solution { solution: pruned })
+    }
+
+    /// Compares two solutions of `graph`; duplicate nodes are ignored.
+    pub fn compare(&self, other: &Solution, graph: &Graph) -> Result<SolutionComparison> {
+        const FIRST: u8 = 1;
+        const SECOND: u8 = 2;
+
+        let n = graph.number_of_nodes();
+        let mut membership = vec![0u8; n as usize];
+        for (solution, flag) in [(self, FIRST), (other, SECOND)] {
+            for &u in &solution.solution {
+                if u >= n {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Node id in solution larger than the number of nodes",
+                    ));
+                }
+                membership[u as usize] |= flag;
+            }
         }
 
-        Ok(covered.len() == n as usize)
+        let mut comparison = SolutionComparison::default();
+
+        // nodes dominated by the exclusive parts of either solution
+        let mut dominated = vec![0u8; n as usize];
+        for u in graph.vertices() {
+            let flag = membership[u as usize];
+            match flag {
+                FIRST => comparison.only_in_first.push(u),
+                SECOND => comparison.only_in_second.push(u),
+                0 => continue,
+                _ => {
+                    comparison.num_common += 1;
+                    continue;
+                }
+            }
+
+            for v in graph.closed_neighbors_of(u) {
+                dominated[v as usize] |= flag;
+            }
+        }
+
+        for u in graph.vertices() {
+            match dominated[u as usize] {
+                FIRST => comparison.dominated_only_by_first.push(u),
+                SECOND => comparison.dominated_only_by_second.push(u),
+                _ => {}
+            }
+        }
+
+        let union = comparison.num_common as usize
+            + comparison.only_in_first.len()
+            + comparison.only_in_second.len();
+        comparison.jaccard = if union == 0 {
+            1.0
+        } else {
+            comparison.num_common as f64 / union as f64
+        };
+
+        Ok(comparison)
     }
 
     pub fn compute_digest(&self) -> Output<Sha1> {
@@ -298,17 +455,102 @@ mod test {
 
     #[test]
     fn test_domset_verifier() {
-        let edges = [Edge(0, 1), Edge(2, 3)];
+        let graph = Graph::try_from_edges(4, [Edge(0, 1), Edge(2, 3)]).unwrap();
 
         assert!(!Solution { solution: vec![0] }
-            .valid_domset_for_instance(4, edges.iter().copied())
+            .valid_domset_for_instance(&graph)
             .unwrap());
 
         assert!(Solution {
             solution: vec![0, 2]
         }
-        .valid_domset_for_instance(4, edges.iter().copied())
+        .valid_domset_for_instance(&graph)
         .unwrap());
+
+        assert!(Solution { solution: vec![4] }
+            .valid_domset_for_instance(&graph)
+            .is_err());
+    }
+
+    #[test]
+    fn test_domset_report() {
+        // path 0-1-2-3-4 and isolated node 5
+        let graph =
+            Graph::try_from_edges(6, [Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 4)]).unwrap();
+
+        let report = Solution {
+            solution: vec![1, 2],
+        }
+        .verify_domset(&graph, 2)
+        .unwrap();
+
+        assert!(!report.is_valid());
+        assert_eq!(report.score, 2);
+        assert_eq!(report.num_undominated, 2);
+        assert_eq!(report.undominated, vec![4, 5]);
+        assert_eq!(report.num_redundant, 0);
+
+        let report = Solution {
+            solution: vec![0, 1, 3, 5],
+        }
+        .verify_domset(&graph, 1)
+        .unwrap();
+
+        assert!(report.is_valid());
+        assert_eq!(report.num_undominated, 0);
+        assert_eq!(report.num_redundant, 2);
+        assert_eq!(report.redundant, vec![0]);
+    }
+
+    #[test]
+    fn prune_redundant() {
+        // path 0-1-2-3-4 and isolated node 5
+        let graph =
+            Graph::try_from_edges(6, [Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 4)]).unwrap();
+
+        let solution = Solution::from_0indexed_vec(vec![0, 1, 3, 1, 4, 5]);
+        let pruned = solution.prune_redundant(&graph).unwrap();
+        assert_eq!(pruned.solution, vec![1, 4, 5]);
+
+        let report = pruned.verify_domset(&graph, 10).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.num_redundant, 0);
+
+        // minimal solutions are kept as they are
+        let minimal = Solution::from_0indexed_vec(vec![1, 3, 5]);
+        assert_eq!(
+            minimal.prune_redundant(&graph).unwrap().solution,
+            vec![1, 3, 5]
+        );
+
+        let out_of_range = Solution::from_0indexed_vec(vec![6]);
+        assert!(out_of_range.prune_redundant(&graph).is_err());
+    }
+
+    #[test]
+    fn compare() {
+        // path 0-1-2-3-4 and isolated node 5
+        let graph =
+            Graph::try_from_edges(6, [Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 4)]).unwrap();
+
+        let first = Solution::from_0indexed_vec(vec![1, 3, 5]);
+        let second = Solution::from_0indexed_vec(vec![5, 0, 3, 4, 3]);
+
+        let comparison = first.compare(&second, &graph).unwrap();
+        assert_eq!(comparison.num_common, 2);
+        assert_eq!(comparison.only_in_first, vec![1]);
+        assert_eq!(comparison.only_in_second, vec![0, 4]);
+        assert_eq!(comparison.dominated_only_by_first, vec![2]);
+        assert_eq!(comparison.dominated_only_by_second, vec![3, 4]);
+        assert_eq!(comparison.jaccard, 0.4);
+
+        let comparison = first.compare(&first, &graph).unwrap();
+        assert_eq!(comparison.jaccard, 1.0);
+        assert!(comparison.only_in_first.is_empty());
+        assert!(comparison.dominated_only_by_second.is_empty());
+
+        let out_of_range = Solution::from_0indexed_vec(vec![6]);
+        assert!(first.compare(&out_of_range, &graph).is_err());
     }
 
     #[test]
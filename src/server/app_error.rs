@@ -1,30 +1,216 @@
 use axum::{
-    http::StatusCode,
+    extract::{
+        multipart::{MultipartError, MultipartRejection},
+        rejection::{BytesRejection, JsonRejection, PathRejection, QueryRejection},
+    },
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
+    Json,
 };
+use tracing::error;
 
-// Make our own error that wraps `anyhow::Error`.
+use super::blob_store::BlobTooLarge;
+
+// Our own error type that wraps `anyhow::Error` and remembers how it should be reported.
+// Client errors (4xx) are permanent, i.e. retrying the same request will fail again,
+// while internal errors (5xx) may be transient.
 #[derive(Debug)]
-pub struct AppError(anyhow::Error);
+pub enum AppError {
+    BadRequest(anyhow::Error),
+    Unauthorized(anyhow::Error),
+    Forbidden(anyhow::Error),
+    NotFound(anyhow::Error),
+    Conflict(anyhow::Error),
+    PayloadTooLarge(anyhow::Error),
+    Internal(anyhow::Error),
+}
+
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Machine-readable identifier of the error class
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, AppError::Internal(_))
+    }
+
+    fn inner(&self) -> &anyhow::Error {
+        match self {
+            AppError::BadRequest(e)
+            | AppError::Unauthorized(e)
+            | AppError::Forbidden(e)
+            | AppError::NotFound(e)
+            | AppError::Conflict(e)
+            | AppError::PayloadTooLarge(e)
+            | AppError::Internal(e) => e,
+        }
+    }
+
+    /// JSON body of the form `{status, code, message, details}`; `details` lists the
+    /// causes of the error, if there are any. Some handlers replace it by structured data
+    /// and refine the code, e.g. for invalid solutions.
+    pub fn to_json(&self) -> serde_json::Value {
+        let details: Vec<String> = self
+            .inner()
+            .chain()
+            .skip(1)
+            .map(|e| e.to_string())
+            .collect();
+
+        serde_json::json!({
+            "status": "error",
+            "code": self.code(),
+            "message": self.inner().to_string(),
+            "details": (!details.is_empty()).then_some(details),
+        })
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner().fmt(f)
+    }
+}
 
 // Tell axum how to convert `AppError` into a response.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Something went wrong: {}", self.0),
-        )
-            .into_response()
+        if let AppError::Internal(e) = &self {
+            error!("Internal error: {e:?}");
+        }
+
+        let mut response = (self.status_code(), Json(self.to_json())).into_response();
+        if let AppError::Unauthorized(_) = &self {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+
+        response
+    }
+}
+
+fn classify_sqlx_error(err: &sqlx::Error) -> fn(anyhow::Error) -> AppError {
+    match err {
+        sqlx::Error::RowNotFound => AppError::NotFound,
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => AppError::Conflict,
+        // e.g. a solution referring to an instance that does not exist
+        sqlx::Error::Database(db_err) if db_err.is_foreign_key_violation() => AppError::BadRequest,
+        _ => AppError::Internal,
+    }
+}
+
+fn classify_rejection(status: StatusCode) -> fn(anyhow::Error) -> AppError {
+    match status {
+        StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge,
+        s if s.is_client_error() => AppError::BadRequest,
+        _ => AppError::Internal,
     }
 }
 
 // This enables using `?` on functions that return `Result<_, anyhow::Error>` to turn them into
-// `Result<_, AppError>`. That way you don't need to do that manually.
+// `Result<_, AppError>`. That way you don't need to do that manually. Errors we know to be
+// caused by the request (e.g. missing rows, duplicates, malformed input) are classified
+// accordingly, everything else is an internal error.
 impl<E> From<E> for AppError
 where
     E: Into<anyhow::Error>,
 {
     fn from(err: E) -> Self {
-        Self(err.into())
+        let err: anyhow::Error = err.into();
+
+        let variant = if let Some(e) = err.downcast_ref::<sqlx::Error>() {
+            classify_sqlx_error(e)
+        } else if let Some(e) = err.downcast_ref::<std::io::Error>() {
+            // the PACE readers report malformed input as invalid data
+            if e.kind() == std::io::ErrorKind::InvalidData {
+                AppError::BadRequest
+            } else {
+                AppError::Internal
+            }
+        } else if let Some(e) = err.downcast_ref::<serde_json::Error>() {
+            if e.is_io() {
+                AppError::Internal
+            } else {
+                AppError::BadRequest
+            }
+        } else if let Some(e) = err.downcast_ref::<JsonRejection>() {
+            classify_rejection(e.status())
+        } else if let Some(e) = err.downcast_ref::<BytesRejection>() {
+            classify_rejection(e.status())
+        } else if let Some(e) = err.downcast_ref::<QueryRejection>() {
+            classify_rejection(e.status())
+        } else if let Some(e) = err.downcast_ref::<PathRejection>() {
+            classify_rejection(e.status())
+        } else if let Some(e) = err.downcast_ref::<MultipartRejection>() {
+            classify_rejection(e.status())
+        } else if let Some(e) = err.downcast_ref::<MultipartError>() {
+            classify_rejection(e.status())
+        } else if err.is::<BlobTooLarge>() {
+            AppError::PayloadTooLarge
+        } else {
+            AppError::Internal
+        };
+
+        variant(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn classification() {
+        assert_eq!(
+            AppError::from(sqlx::Error::RowNotFound).status_code(),
+            StatusCode::NOT_FOUND
+        );
+
+        let io = std::io::Error::new(std::io::ErrorKind::InvalidData, "bad header");
+        assert_eq!(AppError::from(io).status_code(), StatusCode::BAD_REQUEST);
+
+        let json = serde_json::from_str::<u32>("x").unwrap_err();
+        assert_eq!(AppError::from(json).code(), "bad_request");
+
+        let other = AppError::from(anyhow::anyhow!("boom"));
+        assert_eq!(other.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(other.is_retryable());
+    }
+
+    #[test]
+    fn json_body() {
+        let err = AppError::from(anyhow::anyhow!("inner").context("outer"));
+        let json = err.to_json();
+
+        assert_eq!(json["status"], "error");
+        assert_eq!(json["code"], "internal_error");
+        assert_eq!(json["message"], "outer");
+        assert_eq!(json["details"], serde_json::json!(["inner"]));
+
+        let err = AppError::Conflict(anyhow::anyhow!("duplicate"));
+        assert_eq!(err.to_json()["details"], serde_json::Value::Null);
+        assert_eq!(err.into_response().status(), StatusCode::CONFLICT);
     }
 }
@@ -1,18 +1,100 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
 use sqlx::MySqlPool;
 
+use super::blob_store::{BlobStore, MySqlBlobStore};
+
 pub type DbPool = MySqlPool;
 pub type DbTransaction<'a> = sqlx::Transaction<'a, sqlx::MySql>;
 
 pub struct AppState {
     db: DbPool,
+    blob_store: Arc<dyn BlobStore>,
+    require_solution_upload_token: bool,
+    accept_unregistered_solvers: bool,
+    publish_best_solutions: bool,
+    best_solution_embargo: Duration,
+    temp_dir: PathBuf,
 }
 
 impl AppState {
     pub fn new(db: DbPool) -> Self {
-        Self { db }
+        Self {
+            blob_store: Arc::new(MySqlBlobStore::new(db.clone())),
+            db,
+            require_solution_upload_token: false,
+            accept_unregistered_solvers: true,
+            publish_best_solutions: false,
+            best_solution_embargo: Duration::ZERO,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// If set, solution uploads need a bearer token with the `solution-upload` scope
+    pub fn with_solution_upload_token_required(mut self, required: bool) -> Self {
+        self.require_solution_upload_token = required;
+        self
+    }
+
+    /// If unset, uploads and annotations need a solver that was registered with a key
+    pub fn with_unregistered_solvers_accepted(mut self, accepted: bool) -> Self {
+        self.accept_unregistered_solvers = accepted;
+        self
+    }
+
+    /// Whether best-known solutions can be downloaded for instances without own setting
+    pub fn with_best_solutions_published(mut self, published: bool) -> Self {
+        self.publish_best_solutions = published;
+        self
+    }
+
+    /// Best-known solutions are only published once they were uploaded this long ago
+    pub fn with_best_solution_embargo(mut self, embargo: Duration) -> Self {
+        self.best_solution_embargo = embargo;
+        self
+    }
+
+    /// Where instance and solution payloads are kept; defaults to the database itself
+    pub fn with_blob_store(mut self, blob_store: Arc<dyn BlobStore>) -> Self {
+        self.blob_store = blob_store;
+        self
+    }
+
+    /// Where uploads too large for memory are spilled to; defaults to the system's
+    pub fn with_temp_dir(mut self, temp_dir: PathBuf) -> Self {
+        self.temp_dir = temp_dir;
+        self
     }
 
     pub fn db(&self) -> &DbPool {
         &self.db
     }
+
+    pub fn blob_store(&self) -> &dyn BlobStore {
+        self.blob_store.as_ref()
+    }
+
+    pub fn require_solution_upload_token(&self) -> bool {
+        self.require_solution_upload_token
+    }
+
+    pub fn accept_unregistered_solvers(&self) -> bool {
+        self.accept_unregistered_solvers
+    }
+
+    pub fn publish_best_solutions(&self) -> bool {
+        self.publish_best_solutions
+    }
+
+    pub fn best_solution_embargo(&self) -> Duration {
+        self.best_solution_embargo
+    }
+
+    pub fn temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
 }
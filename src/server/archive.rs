@@ -0,0 +1,468 @@
+//! Tar and zip archives written with the `tar` and `zip` crates. Entries are passed as
+//! complete buffers and written immediately, so the archive can be streamed while it is built.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::io::{Error, Result, Write};
+use std::sync::{Arc, Mutex};
+
+use axum::body::{Body, Bytes};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::warn;
+use zip::{
+    write::{SimpleFileOptions, StreamWriter},
+    CompressionMethod, ZipWriter,
+};
+
+/// Number of chunks a streamed archive may run ahead of the client
+const CHANNEL_CAPACITY: usize = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    #[default]
+    Tar,
+    /// Entries are deflated individually; zip64 records are added where sizes, offsets or
+    /// the number of entries exceed the limits of the original format
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "application/x-tar",
+            ArchiveFormat::Zip => "application/zip",
+        }
+    }
+}
+
+pub enum ArchiveWriter<W: Write> {
+    Tar {
+        builder: tar::Builder<W>,
+        mtime: u64,
+    },
+    /// The output cannot seek, so sizes and checksums follow each entry in a data descriptor
+    Zip {
+        writer: Box<ZipWriter<StreamWriter<W>>>,
+        options: SimpleFileOptions,
+    },
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// All entries are stamped with `mtime`
+    pub fn new(format: ArchiveFormat, writer: W, mtime: DateTime<Utc>) -> Self {
+        match format {
+            ArchiveFormat::Tar => ArchiveWriter::Tar {
+                builder: tar::Builder::new(writer),
+                mtime: mtime.timestamp().max(0) as u64,
+            },
+            ArchiveFormat::Zip => ArchiveWriter::Zip {
+                writer: Box::new(ZipWriter::new_stream(writer)),
+                options: SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .last_modified_time(dos_time(mtime))
+                    .unix_permissions(0o644),
+            },
+        }
+    }
+
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        match self {
+            ArchiveWriter::Tar { builder, mtime } => {
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(*mtime);
+                builder.append_data(&mut header, name, data)
+            }
+            ArchiveWriter::Zip { writer, options } => {
+                let large = data.len() as u64 >= u32::MAX as u64;
+                writer.start_file(name, options.large_file(large))?;
+                writer.write_all(data)
+            }
+        }
+    }
+
+    /// Writes the archive trailer and returns the underlying writer
+    pub fn finish(self) -> Result<W> {
+        let mut writer = match self {
+            ArchiveWriter::Tar { builder, .. } => builder.into_inner()?,
+            ArchiveWriter::Zip { writer, .. } => writer.finish()?.into_inner(),
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+/// Zip timestamp of `mtime`; the DOS format cannot represent dates before 1980
+fn dos_time(mtime: DateTime<Utc>) -> zip::DateTime {
+    let mtime = mtime.max(DateTime::from_timestamp(315532800, 0).unwrap());
+    zip::DateTime::from_date_and_time(
+        mtime.year() as u16,
+        mtime.month() as u8,
+        mtime.day() as u8,
+        mtime.hour() as u8,
+        mtime.minute() as u8,
+        mtime.second() as u8,
+    )
+    .unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveCompression {
+    #[default]
+    None,
+    /// Compresses the whole archive, e.g. into a `.tar.gz`
+    Gzip,
+    Zstd,
+}
+
+/// Query parameters shared by all archive downloads
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct ArchiveOptions {
+    #[serde(default)]
+    pub archive: ArchiveFormat,
+
+    #[serde(default)]
+    pub compression: ArchiveCompression,
+}
+
+impl ArchiveOptions {
+    pub fn file_name(&self, stem: &str) -> String {
+        match self.compression {
+            ArchiveCompression::None => format!("{stem}.{}", self.archive.extension()),
+            ArchiveCompression::Gzip => format!("{stem}.{}.gz", self.archive.extension()),
+            ArchiveCompression::Zstd => format!("{stem}.{}.zst", self.archive.extension()),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self.compression {
+            ArchiveCompression::None => self.archive.content_type(),
+            ArchiveCompression::Gzip => "application/gzip",
+            ArchiveCompression::Zstd => "application/zstd",
+        }
+    }
+}
+
+/// Output of an archive, which is drained after each entry
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Receives the archive bytes and compresses them into a [`SharedBuffer`]
+enum Sink {
+    Plain(SharedBuffer),
+    Gzip(GzEncoder<SharedBuffer>),
+    Zstd(zstd::stream::write::Encoder<'static, SharedBuffer>),
+}
+
+impl Sink {
+    fn new(compression: ArchiveCompression, output: SharedBuffer) -> Result<Self> {
+        Ok(match compression {
+            ArchiveCompression::None => Sink::Plain(output),
+            ArchiveCompression::Gzip => Sink::Gzip(GzEncoder::new(output, Compression::default())),
+            ArchiveCompression::Zstd => Sink::Zstd(zstd::stream::write::Encoder::new(
+                output,
+                zstd::DEFAULT_COMPRESSION_LEVEL,
+            )?),
+        })
+    }
+
+    /// Writes the trailer of the compressed stream
+    fn finish(self) -> Result<()> {
+        match self {
+            Sink::Plain(_) => Ok(()),
+            Sink::Gzip(encoder) => encoder.finish().map(drop),
+            Sink::Zstd(encoder) => encoder.finish().map(drop),
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            Sink::Plain(buffer) => buffer.write(buf),
+            Sink::Gzip(encoder) => encoder.write(buf),
+            Sink::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Sink::Plain(buffer) => buffer.flush(),
+            Sink::Gzip(encoder) => encoder.flush(),
+            Sink::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+type ChunkSender = mpsc::Sender<Result<Bytes>>;
+
+/// Archive whose bytes are forwarded to a response body after each entry
+pub struct ArchiveStream {
+    /// Unset only while an entry is added, or after that failed
+    writer: Option<ArchiveWriter<Sink>>,
+    output: SharedBuffer,
+    sender: ChunkSender,
+}
+
+impl ArchiveStream {
+    /// Returns `false` once the client went away, so building can stop early
+    pub async fn add_file(&mut self, name: &str, data: impl Into<Vec<u8>>) -> Result<bool> {
+        let mut writer = self
+            .writer
+            .take()
+            .ok_or_else(|| Error::other("Archive is incomplete after an error"))?;
+        let (name, data) = (name.to_string(), data.into());
+
+        // compressing large entries takes a while
+        let writer = tokio::task::spawn_blocking(move || {
+            writer.add_file(&name, &data)?;
+            Ok::<_, Error>(writer)
+        })
+        .await??;
+        self.writer = Some(writer);
+
+        let chunk = self.output.take();
+        Ok(chunk.is_empty() || self.sender.send(Ok(chunk.into())).await.is_ok())
+    }
+
+    async fn finish(self) -> Result<()> {
+        if let Some(writer) = self.writer {
+            writer.finish()?.finish()?;
+        }
+
+        let rest = self.output.take();
+        if !rest.is_empty() {
+            let _ = self.sender.send(Ok(rest.into())).await;
+        }
+        Ok(())
+    }
+}
+
+/// Spawns `build` to fill an archive and returns the body streaming it. Errors abort the
+/// body, so clients cannot mistake a truncated archive for a complete one.
+pub fn stream_archive<F, E>(
+    options: ArchiveOptions,
+    build: impl FnOnce(ArchiveStream) -> F,
+) -> Result<Body>
+where
+    F: Future<Output = std::result::Result<ArchiveStream, E>> + Send + 'static,
+    E: From<Error> + Display + Send + 'static,
+{
+    let (sender, mut receiver) = mpsc::channel(CHANNEL_CAPACITY);
+    let output = SharedBuffer::default();
+    let sink = Sink::new(options.compression, output.clone())?;
+    let stream = ArchiveStream {
+        writer: Some(ArchiveWriter::new(options.archive, sink, Utc::now())),
+        output,
+        sender: sender.clone(),
+    };
+
+    let build = build(stream);
+    tokio::spawn(async move {
+        let result = match build.await {
+            Ok(stream) => stream.finish().await.map_err(E::from),
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            warn!("Failed to build archive: {e}");
+            let _ = sender.send(Err(Error::other(e.to_string()))).await;
+        }
+    });
+
+    Ok(Body::from_stream(futures::stream::poll_fn(move |cx| {
+        receiver.poll_recv(cx)
+    })))
+}
+
+/// Returns the names and contents of the entries of an uncompressed tar archive
+#[cfg(test)]
+pub(crate) fn read_tar(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+    use std::io::Read;
+
+    let mut archive = tar::Archive::new(archive);
+    let entries = archive.entries().unwrap().map(|entry| {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        (name, data)
+    });
+    entries.collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read};
+
+    use http_body_util::BodyExt;
+    use zip::ZipArchive;
+
+    use super::*;
+
+    const FILES: [(&str, &[u8]); 3] = [
+        ("1.gr", b"p ds 2 1\n1 2\n"),
+        ("empty", b""),
+        ("manifest.json", &[b'x'; 600]),
+    ];
+
+    fn build(format: ArchiveFormat) -> Vec<u8> {
+        let mtime = DateTime::from_timestamp(1734364800, 0).unwrap();
+        let mut writer = ArchiveWriter::new(format, Vec::new(), mtime);
+        for (name, data) in FILES {
+            writer.add_file(name, data).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn u16_at(data: &[u8], i: usize) -> usize {
+        u16::from_le_bytes(data[i..i + 2].try_into().unwrap()) as usize
+    }
+
+    fn u32_at(data: &[u8], i: usize) -> usize {
+        u32::from_le_bytes(data[i..i + 4].try_into().unwrap()) as usize
+    }
+
+    fn u64_at(data: &[u8], i: usize) -> usize {
+        u64::from_le_bytes(data[i..i + 8].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn tar() {
+        let archive = build(ArchiveFormat::Tar);
+        assert_eq!(archive.len() % 512, 0);
+
+        let entries = read_tar(&archive);
+        assert_eq!(entries.len(), FILES.len());
+        for ((name, data), (expected_name, expected_data)) in entries.iter().zip(FILES) {
+            assert_eq!(name, expected_name);
+            assert_eq!(data, expected_data);
+        }
+
+        let mut header = tar::Archive::new(&archive[..]);
+        let first = header.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(first.header().mtime().unwrap(), 1734364800);
+        assert_eq!(first.header().mode().unwrap(), 0o644);
+
+        // names beyond the 100 bytes of the header go to an extension entry
+        let long_name = "x".repeat(150);
+        let mut writer = ArchiveWriter::new(ArchiveFormat::Tar, Vec::new(), Utc::now());
+        writer.add_file(&long_name, b"data").unwrap();
+        let archive = writer.finish().unwrap();
+        assert_eq!(read_tar(&archive), vec![(long_name, b"data".to_vec())]);
+    }
+
+    #[test]
+    fn zip() {
+        let archive = build(ArchiveFormat::Zip);
+
+        let mut zip = ZipArchive::new(Cursor::new(archive)).unwrap();
+        assert_eq!(zip.len(), FILES.len());
+        for (i, (name, data)) in FILES.into_iter().enumerate() {
+            let mut file = zip.by_index(i).unwrap();
+            assert_eq!(file.name(), name);
+            assert_eq!(file.compression(), CompressionMethod::Deflated);
+            assert_eq!(file.unix_mode(), Some(0o100644));
+
+            let mut decompressed = Vec::new();
+            file.read_to_end(&mut decompressed).unwrap();
+            assert_eq!(decompressed, data);
+        }
+    }
+
+    #[test]
+    fn zip64_entries() {
+        let num_entries = u16::MAX as usize + 1;
+        let mut writer = ArchiveWriter::new(ArchiveFormat::Zip, Vec::new(), Utc::now());
+        for i in 0..num_entries {
+            writer.add_file(&format!("{i}"), b"").unwrap();
+        }
+        let archive = writer.finish().unwrap();
+
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(&archive, end), 0x06054b50);
+        assert_eq!(u16_at(&archive, end + 10), 0xffff);
+
+        let locator = end - 20;
+        assert_eq!(u32_at(&archive, locator), 0x07064b50);
+        let record = u64_at(&archive, locator + 8);
+        assert_eq!(u32_at(&archive, record), 0x06064b50);
+        assert_eq!(u64_at(&archive, record + 32), num_entries);
+
+        assert_eq!(
+            ZipArchive::new(Cursor::new(archive)).unwrap().len(),
+            num_entries
+        );
+    }
+
+    #[test]
+    fn zstd() {
+        let mtime = DateTime::from_timestamp(1734364800, 0).unwrap();
+        let output = SharedBuffer::default();
+        let sink = Sink::new(ArchiveCompression::Zstd, output.clone()).unwrap();
+        let mut writer = ArchiveWriter::new(ArchiveFormat::Tar, sink, mtime);
+        for (name, data) in FILES {
+            writer.add_file(name, data).unwrap();
+        }
+        writer.finish().unwrap().finish().unwrap();
+
+        let archive = zstd::decode_all(&output.take()[..]).unwrap();
+        assert_eq!(archive, build(ArchiveFormat::Tar));
+    }
+
+    #[tokio::test]
+    async fn streamed() {
+        let options = ArchiveOptions {
+            archive: ArchiveFormat::Tar,
+            compression: ArchiveCompression::Gzip,
+        };
+        let body = stream_archive(options, |mut archive| async move {
+            for (name, data) in FILES {
+                archive.add_file(name, data).await?;
+            }
+            Ok::<_, Error>(archive)
+        })
+        .unwrap();
+
+        let compressed = body.collect().await.unwrap().to_bytes();
+        let mut archive = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_end(&mut archive)
+            .unwrap();
+
+        let entries = read_tar(&archive);
+        let names: Vec<_> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["1.gr", "empty", "manifest.json"]);
+    }
+}
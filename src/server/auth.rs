@@ -0,0 +1,344 @@
+use std::{fmt::Display, str::FromStr, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha1::{Digest, Sha1};
+use sqlx::types::chrono::{DateTime, Utc};
+use tracing::debug;
+
+use super::{
+    app_error::AppError,
+    app_state::{AppState, DbPool},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Grants access to every endpoint, including all other scopes
+    Admin,
+    InstanceUpload,
+    SolutionUpload,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::Admin, Scope::InstanceUpload, Scope::SolutionUpload];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Admin => "admin",
+            Scope::InstanceUpload => "instance-upload",
+            Scope::SolutionUpload => "solution-upload",
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s.trim())
+            .ok_or_else(|| anyhow::anyhow!("Unknown scope {s:?}"))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub tid: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl TokenInfo {
+    pub fn grants(&self, scope: Scope) -> bool {
+        self.revoked_at.is_none()
+            && (self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope))
+    }
+}
+
+/// Only the SHA1 digest of a secret is stored; the secret itself is shown once when minting
+pub fn hash_secret(token: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(token.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Random secret of 64 hex digits used for API tokens and solver keys
+pub fn generate_secret() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+fn parse_scopes(scopes: &str) -> anyhow::Result<Vec<Scope>> {
+    scopes
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(Scope::from_str)
+        .collect()
+}
+
+#[derive(sqlx::FromRow)]
+struct TokenRow {
+    tid: i32,
+    name: String,
+    scopes: String,
+    created_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<TokenRow> for TokenInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(row: TokenRow) -> Result<Self, Self::Error> {
+        Ok(TokenInfo {
+            tid: row.tid,
+            name: row.name,
+            scopes: parse_scopes(&row.scopes)?,
+            created_at: row.created_at,
+            revoked_at: row.revoked_at,
+        })
+    }
+}
+
+/// Creates a new token and returns its id together with the secret
+pub async fn mint_token(
+    db: &DbPool,
+    name: &str,
+    scopes: &[Scope],
+) -> anyhow::Result<(i32, String)> {
+    if scopes.is_empty() {
+        anyhow::bail!("A token needs at least one scope");
+    }
+
+    let token = generate_secret();
+
+    let scopes = scopes
+        .iter()
+        .map(Scope::as_str)
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let tid =
+        sqlx::query(r#"INSERT INTO ApiToken (token_hash, name, scopes) VALUES (UNHEX(?), ?, ?)"#)
+            .bind(hash_secret(&token))
+            .bind(name)
+            .bind(scopes)
+            .execute(db)
+            .await?
+            .last_insert_id();
+
+    Ok((tid as i32, token))
+}
+
+/// Marks the token as revoked; returns `false` if there was no active token with this id
+pub async fn revoke_token(db: &DbPool, tid: i32) -> anyhow::Result<bool> {
+    let result = sqlx::query(
+        r#"UPDATE ApiToken SET revoked_at = CURRENT_TIMESTAMP WHERE tid = ? AND revoked_at IS NULL"#,
+    )
+    .bind(tid)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn list_tokens(db: &DbPool) -> anyhow::Result<Vec<TokenInfo>> {
+    sqlx::query_as::<_, TokenRow>(
+        r#"SELECT tid, name, scopes, created_at, revoked_at FROM ApiToken ORDER BY tid"#,
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(TokenInfo::try_from)
+    .collect()
+}
+
+pub async fn lookup_token(db: &DbPool, token: &str) -> anyhow::Result<Option<TokenInfo>> {
+    sqlx::query_as::<_, TokenRow>(
+        r#"SELECT tid, name, scopes, created_at, revoked_at FROM ApiToken WHERE token_hash = UNHEX(?)"#,
+    )
+    .bind(hash_secret(token))
+    .fetch_optional(db)
+    .await?
+    .map(TokenInfo::try_from)
+    .transpose()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolverKeyCheck {
+    Valid,
+    Invalid,
+    Unregistered,
+}
+
+/// Checks `key` against the key registered for `solver`
+pub async fn check_solver_key(
+    db: &DbPool,
+    solver: &uuid::Uuid,
+    key: Option<&str>,
+) -> anyhow::Result<SolverKeyCheck> {
+    let key_hash = sqlx::query_scalar::<_, String>(
+        r#"SELECT LOWER(HEX(key_hash)) FROM Solver WHERE solver_uuid = UNHEX(?)"#,
+    )
+    .bind(solver.simple().to_string())
+    .fetch_optional(db)
+    .await?;
+
+    Ok(match (key_hash, key) {
+        (None, _) => SolverKeyCheck::Unregistered,
+        (Some(expected), Some(key)) if hash_secret(key) == expected => SolverKeyCheck::Valid,
+        (Some(_), _) => SolverKeyCheck::Invalid,
+    })
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Whether the request carries a valid bearer token granting `scope`; for public endpoints
+/// that unlock additional actions to privileged callers
+pub async fn headers_grant(db: &DbPool, headers: &HeaderMap, scope: Scope) -> anyhow::Result<bool> {
+    let Some(token) = bearer_token(headers) else {
+        return Ok(false);
+    };
+
+    Ok(lookup_token(db, token)
+        .await?
+        .is_some_and(|info| info.grants(scope)))
+}
+
+fn unauthorized(message: &'static str) -> Response {
+    AppError::Unauthorized(anyhow::anyhow!(message)).into_response()
+}
+
+/// Middleware rejecting requests that do not carry a bearer token granting `scope`
+pub async fn authorize(
+    scope: Scope,
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // solution uploads remain public unless the server is configured otherwise
+    if scope == Scope::SolutionUpload && !app_state.require_solution_upload_token() {
+        return next.run(request).await;
+    }
+
+    let Some(token) = bearer_token(request.headers()) else {
+        return unauthorized("Missing bearer token");
+    };
+
+    let info = match lookup_token(app_state.db(), token).await {
+        Ok(Some(info)) => info,
+        Ok(None) => return unauthorized("Invalid bearer token"),
+        Err(e) => return AppError::from(e).into_response(),
+    };
+
+    if !info.grants(scope) {
+        debug!("Token {} lacks scope {scope}", info.tid);
+        return AppError::Forbidden(anyhow::anyhow!("Token does not grant the {scope} scope"))
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::router::create_router;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    #[test]
+    fn scope_roundtrip() {
+        for scope in Scope::ALL {
+            assert_eq!(Scope::from_str(scope.as_str()).unwrap(), scope);
+        }
+
+        assert_eq!(
+            parse_scopes("admin, solution-upload").unwrap(),
+            vec![Scope::Admin, Scope::SolutionUpload]
+        );
+        assert!(parse_scopes("admin,root").is_err());
+    }
+
+    #[sqlx::test]
+    async fn mint_lookup_revoke(pool: DbPool) -> sqlx::Result<()> {
+        let (tid, token) = mint_token(&pool, "uploader", &[Scope::InstanceUpload])
+            .await
+            .unwrap();
+
+        let info = lookup_token(&pool, &token).await.unwrap().unwrap();
+        assert_eq!(info.tid, tid);
+        assert!(info.grants(Scope::InstanceUpload));
+        assert!(!info.grants(Scope::Admin));
+
+        assert!(lookup_token(&pool, "not-a-token").await.unwrap().is_none());
+
+        assert!(revoke_token(&pool, tid).await.unwrap());
+        assert!(!revoke_token(&pool, tid).await.unwrap());
+
+        let info = lookup_token(&pool, &token).await.unwrap().unwrap();
+        assert!(!info.grants(Scope::InstanceUpload));
+
+        Ok(())
+    }
+
+    async fn status_of(pool: DbPool, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri("/api/debug_restart");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        create_router(Arc::new(AppState::new(pool)))
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[sqlx::test]
+    async fn admin_routes_require_token(pool: DbPool) -> sqlx::Result<()> {
+        let (_, upload_token) = mint_token(&pool, "uploader", &[Scope::InstanceUpload])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            status_of(pool.clone(), None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_of(pool.clone(), Some("invalid")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_of(pool.clone(), Some(&upload_token)).await,
+            StatusCode::FORBIDDEN
+        );
+
+        Ok(())
+    }
+}
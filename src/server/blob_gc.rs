@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use super::{
+    app_state::AppState,
+    blob_store::{BlobKind, BlobSource},
+};
+use crate::pace::codec::BlobEncoding;
+
+/// Payloads are only collected once they were written at least this long ago, which
+/// leaves the transaction that inserts their row plenty of time to commit
+pub const GRACE_PERIOD: Duration = Duration::from_secs(3600);
+
+/// Pause between two collections of the background task
+const COLLECT_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Pending writes handled by one transaction of [`collect_orphaned_blobs`]
+const BATCH_SIZE: u32 = 256;
+
+/// Writes a payload on behalf of a transaction that is about to insert its row. Payloads
+/// are written before their rows, so rows never refer to missing payloads; the write is
+/// recorded in `BlobPending` for [`collect_orphaned_blobs`] to remove the payload again
+/// should the transaction roll back or fail.
+pub async fn put_pending_blob(
+    app_data: &AppState,
+    kind: BlobKind,
+    hash: &str,
+    encoding: BlobEncoding,
+    data: BlobSource<'_>,
+) -> anyhow::Result<()> {
+    let record = r#"INSERT INTO BlobPending (kind, hash) VALUES (?, UNHEX(?)) ON DUPLICATE KEY UPDATE created_at = CURRENT_TIMESTAMP"#;
+
+    sqlx::query(record)
+        .bind(kind.as_str())
+        .bind(hash)
+        .execute(app_data.db())
+        .await?;
+
+    app_data
+        .blob_store()
+        .put(kind, hash, encoding, data)
+        .await?;
+
+    // the grace period starts once the payload is complete
+    sqlx::query(record)
+        .bind(kind.as_str())
+        .bind(hash)
+        .execute(app_data.db())
+        .await?;
+
+    Ok(())
+}
+
+/// Hands over a payload whose row is deleted by the transaction of `conn` to
+/// [`collect_orphaned_blobs`]. Deleting it right away would race with uploads of the same
+/// payload that do not find the row anymore and write it again.
+pub async fn release_blob(
+    conn: &mut sqlx::MySqlConnection,
+    kind: BlobKind,
+    hash: &str,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"INSERT INTO BlobPending (kind, hash) VALUES (?, UNHEX(?)) ON DUPLICATE KEY UPDATE created_at = CURRENT_TIMESTAMP"#,
+    )
+    .bind(kind.as_str())
+    .bind(hash)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Counts the instances or solutions referring to the payload. The locking read waits
+/// for transactions that are about to add a reference and sees them once they committed.
+fn references_query(kind: BlobKind) -> &'static str {
+    match kind {
+        BlobKind::Instance => {
+            "SELECT COUNT(*) FROM Instance i JOIN InstanceData d ON i.data_did = d.did WHERE d.hash = UNHEX(?) FOR SHARE"
+        }
+        BlobKind::Solution => "SELECT COUNT(*) FROM Solution WHERE solution_hash = UNHEX(?) FOR SHARE",
+    }
+}
+
+/// Removes payloads (and their rows) that were written by [`put_pending_blob`] or released
+/// by [`release_blob`] at least `grace_period` ago but are referenced by no instance or
+/// solution. Returns the number of removed payloads.
+pub async fn collect_orphaned_blobs(
+    app_data: &AppState,
+    grace_period: Duration,
+) -> anyhow::Result<u64> {
+    let mut removed = 0;
+    for kind in [BlobKind::Instance, BlobKind::Solution] {
+        loop {
+            let mut tx = app_data.db().begin().await?;
+
+            // the lock makes concurrent writes of the same payload wait until we are done
+            let pending = sqlx::query_scalar::<_, String>(
+                r#"SELECT LOWER(HEX(hash)) FROM BlobPending WHERE kind = ? AND created_at <= NOW() - INTERVAL ? SECOND ORDER BY created_at LIMIT ? FOR UPDATE"#,
+            )
+            .bind(kind.as_str())
+            .bind(grace_period.as_secs())
+            .bind(BATCH_SIZE)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for hash in &pending {
+                let referenced = sqlx::query_scalar::<_, i64>(references_query(kind))
+                    .bind(hash)
+                    .fetch_one(&mut *tx)
+                    .await?
+                    > 0;
+
+                if !referenced {
+                    sqlx::query(&format!(
+                        "DELETE FROM {} WHERE hash = UNHEX(?)",
+                        kind.table()
+                    ))
+                    .bind(hash)
+                    .execute(&mut *tx)
+                    .await?;
+                    app_data.blob_store().delete(kind, hash).await?;
+                    removed += 1;
+                }
+
+                sqlx::query(r#"DELETE FROM BlobPending WHERE kind = ? AND hash = UNHEX(?)"#)
+                    .bind(kind.as_str())
+                    .bind(hash)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+
+            if pending.len() < BATCH_SIZE as usize {
+                break;
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Periodically collects orphaned payloads; meant to run in the background
+pub async fn collect_orphaned_blobs_periodically(app_data: std::sync::Arc<AppState>) {
+    loop {
+        match collect_orphaned_blobs(&app_data, GRACE_PERIOD).await {
+            Ok(0) => {}
+            Ok(num) => debug!("Removed {num} orphaned blobs"),
+            Err(e) => warn!("Collecting orphaned blobs failed: {e:?}"),
+        }
+
+        tokio::time::sleep(COLLECT_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::app_state::DbPool;
+
+    const REFERENCED: &str = "1d229271928d3f9e2bb0375bd6ce5db6c6d348d9";
+    const ORPHANED: &str = "6ce3fe1479a10edb2f1bdd6d181a5b0e210abe1a";
+
+    #[sqlx::test(fixtures(path = "handlers/fixtures", scripts("instances", "solutions")))]
+    async fn orphans_are_collected(pool: DbPool) -> sqlx::Result<()> {
+        let app_data = AppState::new(pool.clone());
+
+        for hash in [REFERENCED, ORPHANED] {
+            put_pending_blob(
+                &app_data,
+                BlobKind::Solution,
+                hash,
+                BlobEncoding::Plain,
+                BlobSource::Memory(b"[1,2]"),
+            )
+            .await
+            .unwrap();
+        }
+
+        // still within the grace period
+        assert_eq!(
+            collect_orphaned_blobs(&app_data, GRACE_PERIOD)
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(
+            collect_orphaned_blobs(&app_data, Duration::ZERO)
+                .await
+                .unwrap(),
+            1
+        );
+
+        let store = app_data.blob_store();
+        assert!(store
+            .get(BlobKind::Solution, REFERENCED)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(store
+            .get(BlobKind::Solution, ORPHANED)
+            .await
+            .unwrap()
+            .is_none());
+
+        let pending = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM BlobPending")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(pending, 0);
+
+        Ok(())
+    }
+}
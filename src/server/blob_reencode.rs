@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use super::app_state::DbPool;
+use crate::pace::codec::{decode_solution, encode_instance, encode_solution, BlobEncoding};
+
+/// Rows fetched and re-encoded at once
+const BATCH_SIZE: u32 = 64;
+
+/// Pause between two batches, so the background task does not starve regular requests
+const BATCH_PAUSE: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReencodeStats {
+    pub rows: u64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+#[derive(sqlx::FromRow)]
+struct BlobRow {
+    id: i32,
+    data: Vec<u8>,
+}
+
+type Encoder = fn(&[u8]) -> anyhow::Result<(BlobEncoding, Vec<u8>)>;
+
+fn encode_instance_blob(data: &[u8]) -> anyhow::Result<(BlobEncoding, Vec<u8>)> {
+    Ok(encode_instance(data)?)
+}
+
+fn encode_solution_blob(data: &[u8]) -> anyhow::Result<(BlobEncoding, Vec<u8>)> {
+    Ok(encode_solution(&decode_solution(
+        BlobEncoding::Plain,
+        data,
+    )?))
+}
+
+/// Re-encodes all `Plain` blobs of `table`; rows that fail to encode are logged and skipped
+async fn reencode_table(
+    db: &DbPool,
+    table: &str,
+    id_column: &str,
+    encode: Encoder,
+) -> anyhow::Result<ReencodeStats> {
+    let select = format!(
+        "SELECT {id_column} AS id, data FROM {table} WHERE encoding = ? AND data IS NOT NULL AND {id_column} > ? ORDER BY {id_column} LIMIT ?"
+    );
+    // a concurrent writer may have replaced the row in the meantime
+    let update =
+        format!("UPDATE {table} SET data = ?, encoding = ? WHERE {id_column} = ? AND encoding = ?");
+
+    let mut stats = ReencodeStats::default();
+    let mut last_id = 0;
+
+    loop {
+        let rows = sqlx::query_as::<_, BlobRow>(&select)
+            .bind(BlobEncoding::Plain.id())
+            .bind(last_id)
+            .bind(BATCH_SIZE)
+            .fetch_all(db)
+            .await?;
+
+        let Some(last) = rows.last() else {
+            break;
+        };
+        last_id = last.id;
+
+        for row in rows {
+            let bytes_before = row.data.len() as u64;
+            let (encoding, encoded) =
+                match tokio::task::spawn_blocking(move || encode(&row.data)).await? {
+                    Ok(encoded) => encoded,
+                    Err(e) => {
+                        warn!("Cannot re-encode row {} of {table}: {e}", row.id);
+                        continue;
+                    }
+                };
+
+            stats.rows += 1;
+            stats.bytes_before += bytes_before;
+            stats.bytes_after += encoded.len() as u64;
+
+            sqlx::query(&update)
+                .bind(encoded)
+                .bind(encoding.id())
+                .bind(row.id)
+                .bind(BlobEncoding::Plain.id())
+                .execute(db)
+                .await?;
+        }
+
+        tokio::time::sleep(BATCH_PAUSE).await;
+    }
+
+    Ok(stats)
+}
+
+/// Re-encodes the instance and solution blobs stored before encodings were introduced.
+/// Meant to run in the background; the server keeps decoding `Plain` blobs meanwhile.
+pub async fn reencode_blobs(db: &DbPool) -> anyhow::Result<()> {
+    for (table, id_column, encode) in [
+        ("InstanceData", "did", encode_instance_blob as Encoder),
+        ("SolutionData", "sdid", encode_solution_blob as Encoder),
+    ] {
+        info!("Start re-encoding {table}");
+        let stats = reencode_table(db, table, id_column, encode).await?;
+        info!(
+            "Re-encoded {} rows of {table}, shrinking them from {} to {} bytes",
+            stats.rows, stats.bytes_before, stats.bytes_after
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::{app_state::AppState, handlers::solution_upload::read_instance_data};
+
+    #[sqlx::test(fixtures(path = "handlers/fixtures", scripts("instances", "solutions")))]
+    async fn reencode(pool: DbPool) -> sqlx::Result<()> {
+        let app_data = AppState::new(pool.clone());
+        let before = read_instance_data(&app_data, 2).await.unwrap();
+
+        reencode_blobs(&pool).await.unwrap();
+
+        for table in ["InstanceData", "SolutionData"] {
+            let plain = sqlx::query_scalar::<_, i64>(&format!(
+                "SELECT COUNT(*) FROM {table} WHERE encoding = 0"
+            ))
+            .fetch_one(&pool)
+            .await?;
+            assert_eq!(plain, 0);
+        }
+
+        assert_eq!(read_instance_data(&app_data, 2).await.unwrap(), before);
+
+        let (data, encoding) = sqlx::query_as::<_, (Vec<u8>, u8)>(
+            r#"SELECT data, encoding FROM SolutionData WHERE hash = UNHEX('1d229271928d3f9e2bb0375bd6ce5db6c6d348d9')"#,
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(
+            decode_solution(BlobEncoding::try_from(encoding).unwrap(), &data).unwrap(),
+            vec![1, 2]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use async_trait::async_trait;
+
+use super::{check_hash, BlobKind, BlobSource, BlobStore};
+use crate::pace::codec::BlobEncoding;
+
+/// Keeps payloads as files `<root>/<kind>/<first two hex digits>/<hash>`
+pub struct FilesystemBlobStore {
+    root: PathBuf,
+}
+
+impl FilesystemBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, kind: BlobKind, hash: &str) -> PathBuf {
+        self.root.join(kind.as_str()).join(&hash[..2]).join(hash)
+    }
+}
+
+#[async_trait]
+impl BlobStore for FilesystemBlobStore {
+    async fn put(
+        &self,
+        kind: BlobKind,
+        hash: &str,
+        _encoding: BlobEncoding,
+        data: BlobSource<'_>,
+    ) -> anyhow::Result<()> {
+        check_hash(hash)?;
+        let path = self.path(kind, hash);
+        let dir = path.parent().unwrap();
+        tokio::fs::create_dir_all(dir).await?;
+
+        // readers must never observe a partially written blob, so write next to the
+        // target and move the file into place afterwards
+        let tmp_path = dir.join(format!(".{hash}.{}", uuid::Uuid::new_v4().simple()));
+        let written = match data {
+            BlobSource::Memory(data) => tokio::fs::write(&tmp_path, data).await,
+            BlobSource::File(source) => tokio::fs::copy(source, &tmp_path).await.map(|_| ()),
+        };
+
+        if let Err(e) = written.and(tokio::fs::rename(&tmp_path, &path).await) {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    async fn get(&self, kind: BlobKind, hash: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        check_hash(hash)?;
+        match tokio::fs::read(self.path(kind, hash)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, kind: BlobKind, hash: &str) -> anyhow::Result<()> {
+        check_hash(hash)?;
+        match tokio::fs::remove_file(self.path(kind, hash)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn local_path(&self, kind: BlobKind, hash: &str) -> Option<PathBuf> {
+        check_hash(hash).ok()?;
+        Some(self.path(kind, hash))
+    }
+
+    fn prefers_plain_instances(&self) -> bool {
+        // plain files can be served directly, including range requests
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pace::normalize::TempFile;
+
+    const HASH: &str = "6ce3fe1479a10edb2f1bdd6d181a5b0e210abe1a";
+
+    #[tokio::test]
+    async fn put_get_delete() {
+        let root = std::env::temp_dir().join(format!("blobs-{}", uuid::Uuid::new_v4().simple()));
+        let store = FilesystemBlobStore::new(&root);
+
+        assert_eq!(store.get(BlobKind::Instance, HASH).await.unwrap(), None);
+
+        store
+            .put(
+                BlobKind::Instance,
+                HASH,
+                BlobEncoding::Plain,
+                BlobSource::Memory(b"p ds 2 1\n1 2\n"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get(BlobKind::Instance, HASH).await.unwrap().unwrap(),
+            b"p ds 2 1\n1 2\n"
+        );
+        assert_eq!(store.get(BlobKind::Solution, HASH).await.unwrap(), None);
+
+        let source = TempFile::create_in(&std::env::temp_dir()).unwrap();
+        std::fs::write(source.path(), b"p ds 3 1\n1 3\n").unwrap();
+        store
+            .put(
+                BlobKind::Instance,
+                HASH,
+                BlobEncoding::Plain,
+                BlobSource::File(source.path()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read(store.local_path(BlobKind::Instance, HASH).unwrap()).unwrap(),
+            b"p ds 3 1\n1 3\n"
+        );
+
+        store.delete(BlobKind::Instance, HASH).await.unwrap();
+        store.delete(BlobKind::Instance, HASH).await.unwrap();
+        assert_eq!(store.get(BlobKind::Instance, HASH).await.unwrap(), None);
+
+        assert!(store.get(BlobKind::Instance, "../x").await.is_err());
+        assert!(store.local_path(BlobKind::Instance, "../x").is_none());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use axum::{http::HeaderValue, response::Response};
+
+use crate::pace::codec::BlobEncoding;
+
+pub mod filesystem;
+pub mod mysql;
+pub mod s3;
+
+pub use filesystem::FilesystemBlobStore;
+pub use mysql::MySqlBlobStore;
+pub use s3::{S3BlobStore, S3Config};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlobKind {
+    Instance,
+    Solution,
+}
+
+impl BlobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlobKind::Instance => "instances",
+            BlobKind::Solution => "solutions",
+        }
+    }
+
+    /// Table holding one row (hash, encoding, and possibly the data) per blob
+    pub fn table(&self) -> &'static str {
+        match self {
+            BlobKind::Instance => "InstanceData",
+            BlobKind::Solution => "SolutionData",
+        }
+    }
+}
+
+pub enum BlobSource<'a> {
+    Memory(&'a [u8]),
+    /// A file that may be too large to be read into memory at once
+    File(&'a Path),
+}
+
+/// Storage of instance and solution payloads keyed by their SHA1 hash (40 lower-case hex
+/// digits). Independently of the store, every blob has a row in [`BlobKind::table`] that
+/// records its encoding; stores other than MySQL leave the `data` column of that row empty,
+/// as does MySQL for payloads it splits into chunks.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Stores the payload of blob `hash`, replacing any previous payload
+    async fn put(
+        &self,
+        kind: BlobKind,
+        hash: &str,
+        encoding: BlobEncoding,
+        data: BlobSource<'_>,
+    ) -> anyhow::Result<()>;
+
+    async fn get(&self, kind: BlobKind, hash: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    async fn delete(&self, kind: BlobKind, hash: &str) -> anyhow::Result<()>;
+
+    /// Streams the payload, or the byte range requested by `range` (the value of an HTTP
+    /// `Range` header), without reading it into memory. Yields `None` if the blob is
+    /// missing or the store cannot do so; callers then fall back to [`BlobStore::get`].
+    async fn serve(
+        &self,
+        _kind: BlobKind,
+        _hash: &str,
+        _range: Option<&HeaderValue>,
+    ) -> anyhow::Result<Option<Response>> {
+        Ok(None)
+    }
+
+    /// Path of the payload, if the store keeps it as a local file that can be served as is
+    fn local_path(&self, _kind: BlobKind, _hash: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// If set, instances are stored as plain text instead of the most compact encoding
+    fn prefers_plain_instances(&self) -> bool {
+        false
+    }
+}
+
+/// Reported by stores that cannot hold a payload of this size
+#[derive(Debug)]
+pub struct BlobTooLarge;
+
+impl std::fmt::Display for BlobTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Payload exceeds the maximum blob size of the store")
+    }
+}
+
+impl std::error::Error for BlobTooLarge {}
+
+pub fn check_hash(hash: &str) -> anyhow::Result<()> {
+    if hash.len() != 40 || !hash.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f')) {
+        anyhow::bail!("Invalid blob hash {hash:?}");
+    }
+    Ok(())
+}
+
+/// Returns the payload of a blob whose row contained `inline` as data. Rows written by the
+/// MySQL store carry their payload, which also keeps blobs stored before switching to
+/// another store readable.
+pub async fn load_blob(
+    store: &dyn BlobStore,
+    kind: BlobKind,
+    hash: &str,
+    inline: Option<Vec<u8>>,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    match inline {
+        Some(data) => Ok(Some(data)),
+        None => store.get(kind, hash).await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hashes() {
+        assert!(check_hash("6ce3fe1479a10edb2f1bdd6d181a5b0e210abe1a").is_ok());
+        assert!(check_hash("6CE3FE1479A10EDB2F1BDD6D181A5B0E210ABE1A").is_err());
+        assert!(check_hash("../../../../../../../../../../etc/passwd").is_err());
+        assert!(check_hash("").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::{BlobKind, BlobSource, BlobStore};
+use crate::{pace::codec::BlobEncoding, server::app_state::DbPool};
+
+/// Larger payloads are split into rows of `BlobChunk` of at most this size, which keeps
+/// every statement well below `max_allowed_packet`
+const CHUNK_SIZE: usize = 4 << 20;
+
+/// Keeps small payloads in the `data` column of the blob rows and larger ones as chunks
+/// in `BlobChunk`, in which case `data` is NULL
+pub struct MySqlBlobStore {
+    db: DbPool,
+}
+
+impl MySqlBlobStore {
+    pub fn new(db: DbPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl BlobStore for MySqlBlobStore {
+    async fn put(
+        &self,
+        kind: BlobKind,
+        hash: &str,
+        encoding: BlobEncoding,
+        data: BlobSource<'_>,
+    ) -> anyhow::Result<()> {
+        let upsert = format!(
+            "INSERT INTO {} (hash, data, encoding) VALUES (UNHEX(?), ?, ?) ON DUPLICATE KEY UPDATE data = VALUES(data), encoding = VALUES(encoding)",
+            kind.table()
+        );
+
+        // the transaction hides partially written blobs
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query("DELETE FROM BlobChunk WHERE kind = ? AND hash = UNHEX(?)")
+            .bind(kind.as_str())
+            .bind(hash)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut reader: Pin<Box<dyn AsyncRead + Send + '_>> = match data {
+            BlobSource::Memory(data) if data.len() <= CHUNK_SIZE => {
+                sqlx::query(&upsert)
+                    .bind(hash)
+                    .bind(data)
+                    .bind(encoding.id())
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                return Ok(());
+            }
+            BlobSource::Memory(data) => Box::pin(data),
+            BlobSource::File(path) => Box::pin(tokio::fs::File::open(path).await?),
+        };
+
+        sqlx::query(&upsert)
+            .bind(hash)
+            .bind(None::<&[u8]>)
+            .bind(encoding.id())
+            .execute(&mut *tx)
+            .await?;
+
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        for seq in 0u32.. {
+            chunk.clear();
+            (&mut reader)
+                .take(CHUNK_SIZE as u64)
+                .read_to_end(&mut chunk)
+                .await?;
+
+            if chunk.is_empty() {
+                break;
+            }
+
+            sqlx::query("INSERT INTO BlobChunk (kind, hash, seq, data) VALUES (?, UNHEX(?), ?, ?)")
+                .bind(kind.as_str())
+                .bind(hash)
+                .bind(seq)
+                .bind(&chunk)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get(&self, kind: BlobKind, hash: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let inline = sqlx::query_scalar::<_, Option<Vec<u8>>>(&format!(
+            "SELECT data FROM {} WHERE hash = UNHEX(?)",
+            kind.table()
+        ))
+        .bind(hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        match inline {
+            None => return Ok(None),
+            Some(Some(data)) => return Ok(Some(data)),
+            Some(None) => {}
+        }
+
+        let mut chunks = sqlx::query_scalar::<_, Vec<u8>>(
+            "SELECT data FROM BlobChunk WHERE kind = ? AND hash = UNHEX(?) ORDER BY seq",
+        )
+        .bind(kind.as_str())
+        .bind(hash)
+        .fetch(&self.db);
+
+        let mut data = None;
+        while let Some(chunk) = chunks.try_next().await? {
+            data.get_or_insert_with(Vec::new).extend_from_slice(&chunk);
+        }
+
+        Ok(data)
+    }
+
+    async fn delete(&self, kind: BlobKind, hash: &str) -> anyhow::Result<()> {
+        // inline payloads are removed together with their row
+        sqlx::query("DELETE FROM BlobChunk WHERE kind = ? AND hash = UNHEX(?)")
+            .bind(kind.as_str())
+            .bind(hash)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HASH: &str = "6ce3fe1479a10edb2f1bdd6d181a5b0e210abe1a";
+
+    #[sqlx::test]
+    async fn large_blobs_are_chunked(pool: DbPool) -> sqlx::Result<()> {
+        let store = MySqlBlobStore::new(pool.clone());
+
+        let small = vec![1, 2, 3];
+        store
+            .put(
+                BlobKind::Solution,
+                HASH,
+                BlobEncoding::NodeList,
+                BlobSource::Memory(&small),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get(BlobKind::Solution, HASH).await.unwrap(),
+            Some(small)
+        );
+
+        let large: Vec<u8> = (0..2 * CHUNK_SIZE + 5).map(|i| i as u8).collect();
+        store
+            .put(
+                BlobKind::Instance,
+                HASH,
+                BlobEncoding::Plain,
+                BlobSource::Memory(&large),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get(BlobKind::Instance, HASH).await.unwrap(),
+            Some(large)
+        );
+
+        let chunks = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM BlobChunk")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(chunks, 3);
+
+        store.delete(BlobKind::Instance, HASH).await.unwrap();
+        let chunks = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM BlobChunk")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(chunks, 0);
+
+        Ok(())
+    }
+}
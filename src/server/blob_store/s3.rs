@@ -0,0 +1,486 @@
+use std::{io, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, Response, StatusCode},
+};
+use futures::StreamExt;
+use object_store::{
+    aws::{AmazonS3, AmazonS3Builder},
+    buffered::BufWriter,
+    path::Path,
+    ClientOptions, GetOptions, GetRange, ObjectStore, PutPayload,
+};
+use tokio::{io::AsyncWriteExt, time::timeout};
+
+use super::{check_hash, BlobKind, BlobSource, BlobStore, BlobTooLarge};
+use crate::pace::codec::BlobEncoding;
+
+/// Files are uploaded in parts of this size; smaller payloads are sent with a single PUT
+const PART_SIZE: usize = 10 << 20;
+
+/// S3 accepts at most this many parts per multipart upload
+const MAX_PARTS: u64 = 10_000;
+
+/// Limit for establishing a connection, including the TLS handshake
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Limit for a complete request to the service, except for downloads streamed to clients
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Limit for every part of a download streamed to a client
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Idle connections are kept for reuse for at most this long, which is below the
+/// keep-alive timeout of common S3 implementations
+const IDLE_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Debug)]
+pub struct S3Config {
+    /// Base URL of the service, e.g. `https://s3.eu-central-1.amazonaws.com`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+impl S3Config {
+    /// Reads the credentials from `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`
+    pub fn from_env(endpoint: String, bucket: String, region: String) -> anyhow::Result<Self> {
+        Ok(Self {
+            endpoint,
+            bucket,
+            region,
+            access_key: std::env::var("AWS_ACCESS_KEY_ID")?,
+            secret_key: std::env::var("AWS_SECRET_ACCESS_KEY")?,
+        })
+    }
+
+    fn build(&self, options: ClientOptions) -> anyhow::Result<AmazonS3> {
+        Ok(AmazonS3Builder::new()
+            .with_endpoint(self.endpoint.trim_end_matches('/'))
+            .with_bucket_name(&self.bucket)
+            .with_region(&self.region)
+            .with_access_key_id(&self.access_key)
+            .with_secret_access_key(&self.secret_key)
+            .with_virtual_hosted_style_request(false)
+            .with_client_options(
+                options
+                    .with_allow_http(self.endpoint.starts_with("http://"))
+                    .with_connect_timeout(CONNECT_TIMEOUT)
+                    .with_pool_idle_timeout(IDLE_TIMEOUT),
+            )
+            .build()?)
+    }
+}
+
+/// Keeps payloads as objects `<bucket>/<kind>/<hash>` of an S3-compatible service, which is
+/// addressed path-style
+pub struct S3BlobStore {
+    store: Arc<AmazonS3>,
+    /// Same service without a limit on the request duration, since streaming a payload to a
+    /// client takes as long as the client needs to read it
+    streaming: AmazonS3,
+}
+
+impl S3BlobStore {
+    pub fn new(config: S3Config) -> anyhow::Result<Self> {
+        Ok(Self {
+            store: Arc::new(config.build(ClientOptions::new().with_timeout(REQUEST_TIMEOUT))?),
+            streaming: config.build(ClientOptions::new().with_timeout_disabled())?,
+        })
+    }
+
+    fn object_path(kind: BlobKind, hash: &str) -> Path {
+        Path::from(format!("{}/{hash}", kind.as_str()))
+    }
+
+    async fn put_file(&self, path: Path, source: &std::path::Path) -> anyhow::Result<()> {
+        let mut file = tokio::fs::File::open(source).await?;
+        if file.metadata().await?.len() > MAX_PARTS * PART_SIZE as u64 {
+            return Err(BlobTooLarge.into());
+        }
+
+        let mut writer = BufWriter::with_capacity(self.store.clone(), path, PART_SIZE);
+        let written = async {
+            tokio::io::copy(&mut file, &mut writer).await?;
+            writer.shutdown().await
+        }
+        .await;
+
+        if let Err(e) = written {
+            let _ = writer.abort().await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put(
+        &self,
+        kind: BlobKind,
+        hash: &str,
+        _encoding: BlobEncoding,
+        data: BlobSource<'_>,
+    ) -> anyhow::Result<()> {
+        check_hash(hash)?;
+        let path = Self::object_path(kind, hash);
+
+        match data {
+            BlobSource::Memory(data) => {
+                self.store
+                    .put(&path, PutPayload::from(data.to_vec()))
+                    .await?;
+            }
+            BlobSource::File(source) => self.put_file(path, source).await?,
+        }
+        Ok(())
+    }
+
+    async fn get(&self, kind: BlobKind, hash: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        check_hash(hash)?;
+        match self.store.get(&Self::object_path(kind, hash)).await {
+            Ok(result) => Ok(Some(result.bytes().await?.to_vec())),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, kind: BlobKind, hash: &str) -> anyhow::Result<()> {
+        check_hash(hash)?;
+        match self.store.delete(&Self::object_path(kind, hash)).await {
+            Err(object_store::Error::NotFound { .. }) => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    async fn serve(
+        &self,
+        kind: BlobKind,
+        hash: &str,
+        range: Option<&HeaderValue>,
+    ) -> anyhow::Result<Option<axum::response::Response>> {
+        check_hash(hash)?;
+        let path = Self::object_path(kind, hash);
+        let range = range.and_then(parse_range);
+        let options = GetOptions {
+            range: range.clone(),
+            ..Default::default()
+        };
+
+        let result = match self.streaming.get_opts(&path, options).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => {
+                // the service rejects unsatisfiable ranges, which are reported to the client
+                // along with the actual size
+                if let (Some(range), Ok(meta)) = (&range, self.store.head(&path).await) {
+                    if !is_satisfiable(range, meta.size) {
+                        return Ok(Some(
+                            Response::builder()
+                                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                                .header(header::CONTENT_RANGE, format!("bytes */{}", meta.size))
+                                .body(Body::empty())?,
+                        ));
+                    }
+                }
+                return Err(e.into());
+            }
+        };
+
+        let mut served = Response::builder()
+            .header(header::CONTENT_LENGTH, result.range.len())
+            .header(header::ACCEPT_RANGES, "bytes")
+            .header(
+                header::LAST_MODIFIED,
+                result
+                    .meta
+                    .last_modified
+                    .format("%a, %d %b %Y %H:%M:%S GMT")
+                    .to_string(),
+            );
+        if let Some(e_tag) = &result.meta.e_tag {
+            served = served.header(header::ETAG, e_tag);
+        }
+        if range.is_some() {
+            served = served.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!(
+                    "bytes {}-{}/{}",
+                    result.range.start,
+                    result.range.end - 1,
+                    result.meta.size
+                ),
+            );
+        }
+
+        // every part has to arrive within READ_TIMEOUT, and the download ends at the first error
+        let payload = result.into_stream();
+        let parts = futures::stream::unfold(Some(payload), |payload| async move {
+            let mut payload = payload?;
+            match timeout(READ_TIMEOUT, payload.next()).await {
+                Err(_) => Some((Err(io::Error::other("Reading from S3 timed out")), None)),
+                Ok(Some(Ok(part))) => Some((Ok(part), Some(payload))),
+                Ok(Some(Err(e))) => Some((Err(io::Error::other(e)), None)),
+                Ok(None) => None,
+            }
+        });
+
+        Ok(Some(served.body(Body::from_stream(parts))?))
+    }
+
+    fn prefers_plain_instances(&self) -> bool {
+        // plain objects can be streamed to clients, including range requests
+        true
+    }
+}
+
+/// Parses a `Range` header with a single byte range. Anything else yields `None`, in which
+/// case the complete payload is served as permitted by RFC 9110.
+fn parse_range(value: &HeaderValue) -> Option<GetRange> {
+    let (start, end) = value
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes=")?
+        .split_once('-')?;
+
+    match (start.trim(), end.trim()) {
+        ("", suffix) => Some(GetRange::Suffix(suffix.parse().ok()?)),
+        (start, "") => Some(GetRange::Offset(start.parse().ok()?)),
+        (start, end) => {
+            let (start, end): (usize, usize) = (start.parse().ok()?, end.parse().ok()?);
+            (start <= end).then_some(GetRange::Bounded(start..end.checked_add(1)?))
+        }
+    }
+}
+
+fn is_satisfiable(range: &GetRange, size: usize) -> bool {
+    match range {
+        GetRange::Bounded(range) => range.start < size,
+        GetRange::Offset(start) => *start < size,
+        GetRange::Suffix(len) => *len > 0 && size > 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        sync::Mutex,
+    };
+
+    use axum::{
+        body::Bytes,
+        extract::{DefaultBodyLimit, Query, State},
+        http::{HeaderMap, Method, Uri},
+        response::IntoResponse,
+        routing::any,
+        Router,
+    };
+    use http_body_util::BodyExt;
+
+    use super::*;
+
+    const HASH: &str = "6ce3fe1479a10edb2f1bdd6d181a5b0e210abe1a";
+
+    #[derive(Default)]
+    struct FakeS3 {
+        objects: HashMap<String, Vec<u8>>,
+        /// Parts of multipart uploads by object and part number
+        parts: BTreeMap<(String, u32), Vec<u8>>,
+    }
+
+    type Shared = Arc<Mutex<FakeS3>>;
+
+    async fn fake_s3(
+        State(s3): State<Shared>,
+        method: Method,
+        uri: Uri,
+        Query(query): Query<HashMap<String, String>>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> axum::response::Response {
+        let authorization = headers[header::AUTHORIZATION].to_str().unwrap();
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=key/"));
+        assert!(authorization.contains("/us-east-1/s3/aws4_request"));
+
+        let mut s3 = s3.lock().unwrap();
+        let key = uri.path().to_string();
+        let e_tag = [(header::ETAG, "\"etag\"")];
+
+        match method {
+            Method::POST if query.contains_key("uploads") => {
+                "<InitiateMultipartUploadResult><UploadId>upload</UploadId></InitiateMultipartUploadResult>"
+                    .into_response()
+            }
+            Method::POST if query.contains_key("uploadId") => {
+                let parts: Vec<_> = s3
+                    .parts
+                    .iter()
+                    .filter(|((object, _), _)| *object == key)
+                    .flat_map(|(_, part)| part.clone())
+                    .collect();
+                s3.parts.retain(|(object, _), _| *object != key);
+                s3.objects.insert(key, parts);
+                "<CompleteMultipartUploadResult><ETag>\"etag\"</ETag></CompleteMultipartUploadResult>"
+                    .into_response()
+            }
+            Method::PUT => {
+                match query.get("partNumber") {
+                    Some(part) => s3.parts.insert((key, part.parse().unwrap()), body.to_vec()),
+                    None => s3.objects.insert(key, body.to_vec()),
+                };
+                (e_tag, StatusCode::OK).into_response()
+            }
+            Method::GET | Method::HEAD => {
+                let Some(data) = s3.objects.get(&key) else {
+                    return StatusCode::NOT_FOUND.into_response();
+                };
+                let Some(range) = headers.get(header::RANGE) else {
+                    let length = [(header::CONTENT_LENGTH, data.len())];
+                    return (e_tag, length, data.clone()).into_response();
+                };
+
+                let (from, to) = range
+                    .to_str()
+                    .unwrap()
+                    .strip_prefix("bytes=")
+                    .unwrap()
+                    .split_once('-')
+                    .unwrap();
+                let from: usize = from.parse().unwrap();
+                let to = to.parse().unwrap_or(usize::MAX).min(data.len() - 1);
+                if from >= data.len() {
+                    return StatusCode::RANGE_NOT_SATISFIABLE.into_response();
+                }
+                (
+                    StatusCode::PARTIAL_CONTENT,
+                    e_tag,
+                    [(
+                        header::CONTENT_RANGE,
+                        format!("bytes {from}-{to}/{}", data.len()),
+                    )],
+                    data[from..=to].to_vec(),
+                )
+                    .into_response()
+            }
+            Method::DELETE => {
+                s3.objects.remove(&key);
+                StatusCode::NO_CONTENT.into_response()
+            }
+            _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+        }
+    }
+
+    #[tokio::test]
+    async fn roundtrip_against_fake_service() {
+        let s3 = Shared::default();
+        let app = Router::new()
+            .route("/*key", any(fake_s3))
+            .layer(DefaultBodyLimit::disable())
+            .with_state(s3.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let store = S3BlobStore::new(S3Config {
+            endpoint: format!("http://{addr}"),
+            bucket: "stride".into(),
+            region: "us-east-1".into(),
+            access_key: "key".into(),
+            secret_key: "secret".into(),
+        })
+        .unwrap();
+
+        assert_eq!(store.get(BlobKind::Solution, HASH).await.unwrap(), None);
+
+        store
+            .put(
+                BlobKind::Solution,
+                HASH,
+                BlobEncoding::NodeList,
+                BlobSource::Memory(&[1, 2, 3]),
+            )
+            .await
+            .unwrap();
+        assert!(s3
+            .lock()
+            .unwrap()
+            .objects
+            .contains_key(&format!("/stride/solutions/{HASH}")));
+        assert_eq!(
+            store.get(BlobKind::Solution, HASH).await.unwrap().unwrap(),
+            vec![1, 2, 3]
+        );
+
+        // large enough for a multipart upload
+        let source = crate::pace::normalize::TempFile::create_in(&std::env::temp_dir()).unwrap();
+        let large: Vec<u8> = (0..2 * PART_SIZE + 100).map(|i| i as u8).collect();
+        std::fs::write(source.path(), &large).unwrap();
+        store
+            .put(
+                BlobKind::Instance,
+                HASH,
+                BlobEncoding::Plain,
+                BlobSource::File(source.path()),
+            )
+            .await
+            .unwrap();
+        assert!(s3.lock().unwrap().parts.is_empty());
+        assert_eq!(
+            store.get(BlobKind::Instance, HASH).await.unwrap().unwrap(),
+            large
+        );
+
+        let range = HeaderValue::from_static("bytes=10-19");
+        let response = store
+            .serve(BlobKind::Instance, HASH, Some(&range))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[header::CONTENT_RANGE],
+            format!("bytes 10-19/{}", large.len())
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], &large[10..20]);
+
+        let range = HeaderValue::from_str(&format!("bytes={}-", large.len())).unwrap();
+        let response = store
+            .serve(BlobKind::Instance, HASH, Some(&range))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            response.headers()[header::CONTENT_RANGE],
+            format!("bytes */{}", large.len())
+        );
+
+        store.delete(BlobKind::Solution, HASH).await.unwrap();
+        store.delete(BlobKind::Solution, HASH).await.unwrap();
+        assert_eq!(store.get(BlobKind::Solution, HASH).await.unwrap(), None);
+        assert!(store
+            .serve(BlobKind::Solution, HASH, None)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn parse_ranges() {
+        let parse = |value| parse_range(&HeaderValue::from_static(value));
+        assert_eq!(parse("bytes=10-19"), Some(GetRange::Bounded(10..20)));
+        assert_eq!(parse("bytes=10-"), Some(GetRange::Offset(10)));
+        assert_eq!(parse("bytes=-5"), Some(GetRange::Suffix(5)));
+        assert_eq!(parse("bytes=19-10"), None);
+        assert_eq!(parse("bytes=0-1,5-6"), None);
+        assert_eq!(parse("items=0-1"), None);
+    }
+}
@@ -1,8 +1,9 @@
-use crate::server::app_error::AppError;
+pub use crate::server::app_error::AppError;
 pub use crate::server::app_state::AppState;
 
-pub use axum::extract::{Query, State};
-pub use axum::{response::IntoResponse, Json};
+pub use crate::server::extract::{Json, Path, Query};
+pub use axum::extract::State;
+pub use axum::response::IntoResponse;
 pub use serde::{Deserialize, Serialize};
 pub use std::sync::Arc;
 
@@ -11,11 +12,43 @@ pub type HandlerResult<T> = Result<T, AppError>;
 #[macro_export]
 macro_rules! error_bad_request {
     ($message:expr) => {
-        Err(anyhow::anyhow!($message).into())
+        Err($crate::server::app_error::AppError::BadRequest(
+            anyhow::anyhow!($message),
+        ))
     };
 }
 pub use error_bad_request;
 
+#[macro_export]
+macro_rules! error_forbidden {
+    ($message:expr) => {
+        Err($crate::server::app_error::AppError::Forbidden(
+            anyhow::anyhow!($message),
+        ))
+    };
+}
+pub use error_forbidden;
+
+#[macro_export]
+macro_rules! error_not_found {
+    ($message:expr) => {
+        Err($crate::server::app_error::AppError::NotFound(
+            anyhow::anyhow!($message),
+        ))
+    };
+}
+pub use error_not_found;
+
+#[macro_export]
+macro_rules! error_conflict {
+    ($message:expr) => {
+        Err($crate::server::app_error::AppError::Conflict(
+            anyhow::anyhow!($message),
+        ))
+    };
+}
+pub use error_conflict;
+
 #[cfg(test)]
 pub mod test {
     use std::sync::Arc;
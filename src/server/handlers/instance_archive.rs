@@ -0,0 +1,206 @@
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::HeaderValue;
+
+use super::common::*;
+use super::instance_download::fetch_dimacs_file;
+use super::instance_list::{matching_instances_query, FilterOptions};
+use crate::server::archive::{stream_archive, ArchiveOptions, ArchiveStream};
+
+#[derive(Debug, Default, Deserialize, Serialize, sqlx::FromRow)]
+struct ManifestEntry {
+    #[sqlx(default)]
+    file: String,
+    iid: i32,
+    nodes: u32,
+    edges: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    best_score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lower_bound: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    difficulty: Option<f64>,
+    /// Score of the selected run, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct Manifest {
+    filter: FilterOptions,
+    instances: Vec<ManifestEntry>,
+}
+
+async fn fetch_manifest(opts: FilterOptions, app_data: &AppState) -> HandlerResult<Manifest> {
+    let select = if opts.has_run() {
+        "SELECT i.iid, i.nodes, i.edges, i.name, i.description, i.best_score, i.lower_bound, i.difficulty, s.score"
+    } else {
+        "SELECT i.iid, i.nodes, i.edges, i.name, i.description, i.best_score, i.lower_bound, i.difficulty, NULL AS score"
+    };
+
+    let mut instances = matching_instances_query(select, &opts)?
+        .build_query_as::<ManifestEntry>()
+        .fetch_all(app_data.db())
+        .await?;
+
+    for entry in &mut instances {
+        entry.file = format!("{}.gr", entry.iid);
+    }
+
+    Ok(Manifest {
+        filter: opts,
+        instances,
+    })
+}
+
+async fn write_archive(
+    mut archive: ArchiveStream,
+    manifest: Manifest,
+    app_data: Arc<AppState>,
+) -> HandlerResult<ArchiveStream> {
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    if !archive.add_file("manifest.json", manifest_json).await? {
+        return Ok(archive);
+    }
+
+    // instances are loaded one at a time, so at most one of them is held in memory
+    for entry in &manifest.instances {
+        let document = fetch_dimacs_file(entry.iid as u32, &app_data).await?;
+        if !archive.add_file(&entry.file, document).await? {
+            break;
+        }
+    }
+
+    Ok(archive)
+}
+
+/// Streams all instances matching the filter (ignoring pagination) as an archive of
+/// `<iid>.gr` files, preceded by a `manifest.json` listing their metadata
+pub async fn instance_archive_handler(
+    Query(opts): Query<FilterOptions>,
+    Query(options): Query<ArchiveOptions>,
+    State(app_data): State<Arc<AppState>>,
+) -> HandlerResult<impl IntoResponse> {
+    opts.check_validity()?;
+
+    // fetch the metadata upfront so that invalid filters are still reported as errors
+    let manifest = fetch_manifest(opts, &app_data).await?;
+
+    let body = stream_archive(options, |archive| {
+        write_archive(archive, manifest, app_data)
+    })?;
+
+    let content_disposition = HeaderValue::from_str(&format!(
+        "attachment; filename=\"{}\"",
+        options.file_name("instances")
+    ))?;
+
+    Ok((
+        [
+            (CONTENT_DISPOSITION, content_disposition),
+            (
+                CONTENT_TYPE,
+                HeaderValue::from_static(options.content_type()),
+            ),
+        ],
+        body,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::server::{
+        app_state::DbPool,
+        archive::{read_tar, ArchiveCompression, ArchiveFormat},
+    };
+
+    async fn download(pool: DbPool, opts: FilterOptions, options: ArchiveOptions) -> Vec<u8> {
+        let response = instance_archive_handler(
+            Query(opts),
+            Query(options),
+            State(Arc::new(AppState::new(pool))),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        response
+            .into_body()
+            .collect()
+            .await
+            .unwrap()
+            .to_bytes()
+            .to_vec()
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn tar_archive(pool: DbPool) -> sqlx::Result<()> {
+        let mut opts = FilterOptions::default();
+        opts.iid = Some(2);
+
+        let archive = download(pool.clone(), opts.clone(), ArchiveOptions::default()).await;
+        let entries = read_tar(&archive);
+        assert_eq!(entries.len(), 2);
+
+        let (name, manifest) = &entries[0];
+        assert_eq!(name, "manifest.json");
+        let manifest: serde_json::Value = serde_json::from_slice(manifest).unwrap();
+        assert_eq!(manifest["instances"][0]["iid"], 2);
+        assert_eq!(manifest["instances"][0]["file"], "2.gr");
+        assert_eq!(manifest["instances"][0]["nodes"], 3);
+
+        let (name, instance) = &entries[1];
+        assert_eq!(name, "2.gr");
+        let instance = String::from_utf8(instance.clone()).unwrap();
+        assert!(instance.starts_with("c {\"iid\":2"));
+        assert!(instance.contains("p ds 3 2\n"));
+
+        let compressed = download(
+            pool,
+            opts,
+            ArchiveOptions {
+                compression: ArchiveCompression::Gzip,
+                ..Default::default()
+            },
+        )
+        .await;
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&compressed[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(read_tar(&decompressed), entries);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn zip_archive(pool: DbPool) -> sqlx::Result<()> {
+        let archive = download(
+            pool,
+            FilterOptions::default(),
+            ArchiveOptions {
+                archive: ArchiveFormat::Zip,
+                ..Default::default()
+            },
+        )
+        .await;
+
+        // end of central directory record lists the manifest and every instance
+        let end = archive.len() - 22;
+        assert_eq!(&archive[end..end + 4], b"PK\x05\x06");
+        let num_entries = u16::from_le_bytes([archive[end + 10], archive[end + 11]]);
+        assert!(num_entries >= 3);
+        assert!(archive.starts_with(b"PK\x03\x04"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use sqlx::MySql;
+use tracing::{debug, warn};
+
+use super::{
+    common::*, instance_difficulty::update_instance_difficulty, solution_upload::read_instance_data,
+};
+use crate::pace::metadata::{GraphMetadata, StreamedMetadata};
+
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct ComputeMetaRequest {
+    /// Recompute instances that already carry metadata
+    #[serde(default)]
+    overwrite: bool,
+
+    /// Maximum number of instances processed by this request
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+pub async fn update_instance_metadata<'e, E>(
+    executor: E,
+    iid: u32,
+    meta: &GraphMetadata,
+) -> HandlerResult<()>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    // keep a previously known diameter if we only obtained bounds this time; the bounds
+    // are only stored without an exact value (MySQL assigns from left to right)
+    sqlx::query(
+        r#"UPDATE Instance SET
+            min_deg=?, max_deg=?, num_ccs=?, nodes_largest_cc=?, bipartite=?, diameter=COALESCE(?, diameter),
+            diameter_lower=IF(diameter IS NULL, ?, NULL), diameter_upper=IF(diameter IS NULL, ?, NULL),
+            lower_bound=?, fingerprint=?
+           WHERE iid=?"#,
+    )
+    .bind(meta.min_deg)
+    .bind(meta.max_deg)
+    .bind(meta.num_ccs)
+    .bind(meta.nodes_largest_cc)
+    .bind(meta.bipartite)
+    .bind(meta.diameter.exact())
+    .bind(meta.diameter.lower)
+    .bind(meta.diameter.upper)
+    .bind(meta.lower_bound)
+    .bind(&meta.fingerprint[..])
+    .bind(iid)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Stores the metadata of an instance too large to be built on upload; fingerprint and
+/// diameter remain unset, so the instance is completed by a later backfill
+pub async fn update_instance_streamed_metadata<'e, E>(
+    executor: E,
+    iid: u32,
+    meta: &StreamedMetadata,
+) -> HandlerResult<()>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"UPDATE Instance SET
+            min_deg=?, max_deg=?, num_ccs=?, nodes_largest_cc=?, bipartite=?, lower_bound=?
+           WHERE iid=?"#,
+    )
+    .bind(meta.min_deg)
+    .bind(meta.max_deg)
+    .bind(meta.num_ccs)
+    .bind(meta.nodes_largest_cc)
+    .bind(meta.bipartite)
+    .bind(meta.lower_bound)
+    .bind(iid)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+const MISSING_METADATA_CONDITION: &str = r#"min_deg IS NULL OR max_deg IS NULL OR num_ccs IS NULL
+    OR nodes_largest_cc IS NULL OR bipartite IS NULL OR lower_bound IS NULL
+    OR fingerprint IS NULL"#;
+
+pub async fn instance_compute_meta_handler(
+    State(app_data): State<Arc<AppState>>,
+    Json(body): Json<ComputeMetaRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let mut builder = sqlx::QueryBuilder::new("SELECT iid FROM Instance ");
+    if !body.overwrite {
+        builder.push(" WHERE ");
+        builder.push(MISSING_METADATA_CONDITION);
+    }
+    builder.push(" ORDER BY iid ");
+    if let Some(limit) = body.limit {
+        builder.push(" LIMIT ");
+        builder.push_bind(limit);
+    }
+
+    let iids = builder
+        .build_query_scalar::<i32>()
+        .fetch_all(app_data.db())
+        .await?;
+
+    let mut updated = Vec::with_capacity(iids.len());
+    let mut failed = Vec::new();
+    for iid in iids {
+        let iid = iid as u32;
+        let graph = match read_instance_data(&app_data, iid).await {
+            Ok(graph) => graph,
+            Err(e) => {
+                warn!("Cannot read instance {iid} to compute metadata: {e:?}");
+                failed.push(iid);
+                continue;
+            }
+        };
+
+        let meta = tokio::task::spawn_blocking(move || GraphMetadata::compute(&graph)).await?;
+        debug!("Computed metadata of instance {iid}: {meta:?}");
+
+        update_instance_metadata(app_data.db(), iid, &meta).await?;
+        update_instance_difficulty(&mut *app_data.db().acquire().await?, iid).await?;
+        updated.push(iid);
+    }
+
+    let remaining = sqlx::query_scalar::<_, i64>(&format!(
+        "SELECT COUNT(*) FROM Instance WHERE {MISSING_METADATA_CONDITION}"
+    ))
+    .fetch_one(app_data.db())
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "status": "ok",
+        "updated": updated,
+        "failed": failed,
+        "remaining": remaining,
+    })))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pace::graph::{Edge, Graph};
+    use crate::server::app_state::DbPool;
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn compute_meta(pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(pool));
+
+        let response = super::instance_compute_meta_handler(
+            State(state.clone()),
+            Json(ComputeMetaRequest::default()),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert!(response.status().is_success());
+
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            min_deg: u32,
+            max_deg: u32,
+            num_ccs: u32,
+            nodes_largest_cc: u32,
+            bipartite: bool,
+            diameter: u32,
+            lower_bound: u32,
+        }
+
+        let row = sqlx::query_as::<_, Row>(
+            "SELECT min_deg, max_deg, num_ccs, nodes_largest_cc, bipartite, diameter, lower_bound FROM Instance WHERE iid = 2",
+        )
+        .fetch_one(state.db())
+        .await?;
+
+        assert_eq!(row.min_deg, 1);
+        assert_eq!(row.max_deg, 2);
+        assert_eq!(row.num_ccs, 1);
+        assert_eq!(row.nodes_largest_cc, 3);
+        assert!(row.bipartite);
+        assert_eq!(row.diameter, 2);
+        assert_eq!(row.lower_bound, 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn diameter_bounds(pool: DbPool) -> sqlx::Result<()> {
+        let diameter = || async {
+            sqlx::query_as::<_, (Option<u32>, Option<u32>, Option<u32>)>(
+                "SELECT diameter, diameter_lower, diameter_upper FROM Instance WHERE iid = 2",
+            )
+            .fetch_one(&pool)
+            .await
+        };
+
+        // without any work, only bounds are known
+        let cycle = Graph::try_from_edges(100, (0..100).map(|u| Edge(u, (u + 1) % 100))).unwrap();
+        let meta = GraphMetadata::compute_with_work_limit(&cycle, 0);
+        assert!(meta.diameter.exact().is_none());
+
+        update_instance_metadata(&pool, 2, &meta).await.unwrap();
+        let (exact, lower, upper) = diameter().await?;
+        assert_eq!(exact, None);
+        assert_eq!(lower, Some(meta.diameter.lower));
+        assert_eq!(upper, Some(meta.diameter.upper));
+
+        // the exact value replaces them
+        update_instance_metadata(&pool, 2, &GraphMetadata::compute(&cycle))
+            .await
+            .unwrap();
+        assert_eq!(diameter().await?, (Some(50), None, None));
+
+        // and is kept if a later computation only obtains bounds
+        update_instance_metadata(&pool, 2, &meta).await.unwrap();
+        assert_eq!(diameter().await?, (Some(50), None, None));
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
-use axum::extract::Path;
 use serde_json::json;
 
 use super::common::*;
+use crate::server::{blob_gc::release_blob, blob_store::BlobKind};
 
 pub async fn instance_delete_handler(
     Path(id): Path<u32>,
@@ -14,8 +14,13 @@ pub async fn instance_delete_handler(
         .execute(&mut *tx)
         .await?;
 
+    sqlx::query(r#"DELETE FROM ScheduledInstance WHERE instance_iid=?"#)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
     let solution_data_hashes = sqlx::query_as::<_, (String,)>(
-        r#"SELECT HEX(solution_hash) FROM Solution WHERE instance_iid=? AND solution_hash IS NOT NULL"#,
+        r#"SELECT LOWER(HEX(solution_hash)) FROM Solution WHERE instance_iid=? AND solution_hash IS NOT NULL"#,
     )
     .bind(id)
     .fetch_all(&mut *tx)
@@ -39,14 +44,17 @@ pub async fn instance_delete_handler(
                 .bind(&hash)
                 .execute(&mut *tx)
                 .await?;
+            release_blob(&mut tx, BlobKind::Solution, &hash).await?;
         }
     }
 
     // delete data, if not used by any other instance
-    let data_did = sqlx::query_scalar::<_, i32>(r#"SELECT data_did FROM Instance WHERE iid=?"#)
-        .bind(id)
-        .fetch_one(&mut *tx)
-        .await?;
+    let (data_did, data_hash) = sqlx::query_as::<_, (i32, String)>(
+        r#"SELECT i.data_did, LOWER(HEX(d.hash)) FROM Instance i JOIN InstanceData d ON d.did = i.data_did WHERE i.iid=?"#,
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
 
     sqlx::query(r#"DELETE FROM Instance WHERE iid=?"#)
         .bind(id)
@@ -59,11 +67,12 @@ pub async fn instance_delete_handler(
             .fetch_one(&mut *tx)
             .await?;
 
-    if usages_of_did == 1 {
+    if usages_of_did == 0 {
         sqlx::query(r#"DELETE FROM InstanceData WHERE did=?"#)
             .bind(data_did)
             .execute(&mut *tx)
             .await?;
+        release_blob(&mut tx, BlobKind::Instance, &data_hash).await?;
     }
 
     tx.commit().await?;
@@ -76,8 +85,11 @@ pub async fn instance_delete_handler(
 
 #[cfg(test)]
 mod test {
+    use std::time::Duration;
+
     use super::*;
     use crate::server::app_state::DbPool;
+    use crate::server::blob_gc::collect_orphaned_blobs;
 
     #[sqlx::test(fixtures("instances", "solutions"))]
     async fn instance_delete_handler(pool: DbPool) -> sqlx::Result<()> {
@@ -99,6 +111,25 @@ mod test {
 
         assert_eq!(0, count);
 
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM InstanceData WHERE did NOT IN (SELECT data_did FROM Instance)",
+        )
+        .fetch_one(state.db())
+        .await?;
+
+        assert_eq!(0, count);
+
+        // the payloads are left to the collector, which finds them unreferenced
+        let released: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM BlobPending")
+            .fetch_one(state.db())
+            .await?;
+        assert!(released > 0);
+
+        let removed = collect_orphaned_blobs(&state, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(removed as i64, released);
+
         Ok(())
     }
 }
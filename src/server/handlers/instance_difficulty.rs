@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+use sqlx::MySqlConnection;
+use tracing::{debug, warn};
+
+use super::{common::*, solution_upload::SolverResultType};
+use crate::server::app_state::DbPool;
+
+/// Median running time (in seconds) that contributes half of the maximal time penalty
+const DIFFICULTY_TIME_SCALE: f64 = 60.0;
+
+/// Pause between two passes over the instances whose difficulty is outdated
+const DIFFICULTY_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunRecord {
+    pub result: Option<SolverResultType>,
+    pub score: Option<u32>,
+    pub seconds_computed: Option<f64>,
+}
+
+/// Summarises the solver runs on a single instance.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DifficultyStats {
+    pub runs: usize,
+    /// Fraction of all runs that timed out
+    pub timeout_rate: f64,
+    /// Fraction of all runs that did not produce a solution matching the best known score
+    pub miss_rate: f64,
+    pub median_seconds: Option<f64>,
+}
+
+impl DifficultyStats {
+    pub fn from_runs(runs: &[RunRecord], best_score: Option<u32>) -> Self {
+        if runs.is_empty() {
+            return Self::default();
+        }
+
+        let num_runs = runs.len() as f64;
+        let timeouts = runs
+            .iter()
+            .filter(|r| r.result == Some(SolverResultType::Timeout))
+            .count();
+
+        let hits = runs
+            .iter()
+            .filter(|r| r.result == Some(SolverResultType::Valid))
+            .filter(|r| r.score.is_some() && r.score == best_score)
+            .count();
+
+        let mut seconds: Vec<f64> = runs.iter().filter_map(|r| r.seconds_computed).collect();
+        seconds.sort_by(f64::total_cmp);
+        let median_seconds = (!seconds.is_empty())
+            .then(|| (seconds[(seconds.len() - 1) / 2] + seconds[seconds.len() / 2]) / 2.0);
+
+        Self {
+            runs: runs.len(),
+            timeout_rate: timeouts as f64 / num_runs,
+            miss_rate: (runs.len() - hits) as f64 / num_runs,
+            median_seconds,
+        }
+    }
+
+    /// Combines the statistics into a single value in `[0, 1]`; higher is harder.
+    /// Returns `None` if there are no runs to judge the instance by.
+    pub fn difficulty(&self) -> Option<f64> {
+        if self.runs == 0 {
+            return None;
+        }
+
+        let time_penalty = self
+            .median_seconds
+            .map_or(0.0, |s| s.max(0.0) / (s.max(0.0) + DIFFICULTY_TIME_SCALE));
+
+        Some((self.timeout_rate + self.miss_rate + time_penalty) / 3.0)
+    }
+}
+
+/// Recomputes `Instance.difficulty` from all solutions stored for the instance
+pub async fn update_instance_difficulty(conn: &mut MySqlConnection, iid: u32) -> HandlerResult<()> {
+    let best_score =
+        sqlx::query_scalar::<_, Option<u32>>(r#"SELECT best_score FROM Instance WHERE iid = ?"#)
+            .bind(iid)
+            .fetch_one(&mut *conn)
+            .await?;
+
+    let runs: Vec<RunRecord> = sqlx::query_as::<_, (Option<u32>, Option<u32>, Option<f64>)>(
+        r#"SELECT error_code, score, seconds_computed FROM Solution WHERE instance_iid = ?"#,
+    )
+    .bind(iid)
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|(error_code, score, seconds_computed)| RunRecord {
+        result: error_code.and_then(|c| SolverResultType::try_from(c).ok()),
+        score,
+        seconds_computed,
+    })
+    .collect();
+
+    let difficulty = DifficultyStats::from_runs(&runs, best_score).difficulty();
+
+    sqlx::query(r#"UPDATE Instance SET difficulty = ? WHERE iid = ?"#)
+        .bind(difficulty)
+        .bind(iid)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Flags the difficulty of an instance for recomputation after a new result was stored.
+/// Recomputing it needs all solutions of the instance and is thus left to
+/// [`refresh_outdated_difficulties`] rather than done on upload.
+pub async fn mark_difficulty_outdated(conn: &mut MySqlConnection, iid: u32) -> HandlerResult<()> {
+    sqlx::query(r#"UPDATE Instance SET difficulty_outdated = TRUE WHERE iid = ?"#)
+        .bind(iid)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Recomputes the difficulty of all flagged instances and returns their number
+pub async fn update_outdated_difficulties(db: &DbPool) -> HandlerResult<usize> {
+    let iids = sqlx::query_scalar::<_, i32>(
+        r#"SELECT iid FROM Instance WHERE difficulty_outdated ORDER BY iid"#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    for &iid in &iids {
+        let mut tx = db.begin().await?;
+
+        // the flag is cleared first, so results uploaded meanwhile set it again
+        sqlx::query(r#"UPDATE Instance SET difficulty_outdated = FALSE WHERE iid = ?"#)
+            .bind(iid)
+            .execute(&mut *tx)
+            .await?;
+        update_instance_difficulty(&mut tx, iid as u32).await?;
+
+        tx.commit().await?;
+    }
+
+    Ok(iids.len())
+}
+
+/// Periodically recomputes outdated difficulties; meant to run in the background
+pub async fn refresh_outdated_difficulties(db: DbPool) {
+    loop {
+        match update_outdated_difficulties(&db).await {
+            Ok(0) => {}
+            Ok(num) => debug!("Recomputed the difficulty of {num} instances"),
+            Err(e) => warn!("Recomputing difficulties failed: {e:?}"),
+        }
+
+        tokio::time::sleep(DIFFICULTY_REFRESH_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(result: SolverResultType, score: Option<u32>, seconds: f64) -> RunRecord {
+        RunRecord {
+            result: Some(result),
+            score,
+            seconds_computed: Some(seconds),
+        }
+    }
+
+    #[test]
+    fn difficulty_stats() {
+        assert_eq!(DifficultyStats::from_runs(&[], Some(3)).difficulty(), None);
+
+        let runs = [
+            run(SolverResultType::Valid, Some(3), 1.0),
+            run(SolverResultType::Valid, Some(4), 2.0),
+            run(SolverResultType::Timeout, None, 100.0),
+            run(SolverResultType::Infeasible, None, 3.0),
+        ];
+
+        let stats = DifficultyStats::from_runs(&runs, Some(3));
+        assert_eq!(stats.runs, 4);
+        assert_eq!(stats.timeout_rate, 0.25);
+        assert_eq!(stats.miss_rate, 0.75);
+        assert_eq!(stats.median_seconds, Some(2.5));
+
+        let difficulty = stats.difficulty().unwrap();
+        assert!(0.0 < difficulty && difficulty < 1.0);
+    }
+
+    #[test]
+    fn difficulty_is_monotone() {
+        let easy = [run(SolverResultType::Valid, Some(3), 1.0); 4];
+        let slow = [run(SolverResultType::Valid, Some(3), 600.0); 4];
+        let missed = [run(SolverResultType::Valid, Some(4), 1.0); 4];
+        let timeout = [run(SolverResultType::Timeout, None, 1.0); 4];
+
+        let difficulty = |runs: &[RunRecord]| {
+            DifficultyStats::from_runs(runs, Some(3))
+                .difficulty()
+                .unwrap()
+        };
+
+        assert!(difficulty(&easy) < difficulty(&slow));
+        assert!(difficulty(&easy) < difficulty(&missed));
+        assert!(difficulty(&missed) < difficulty(&timeout));
+    }
+
+    #[sqlx::test(fixtures("instances", "solutions"))]
+    async fn update_difficulty(pool: DbPool) -> sqlx::Result<()> {
+        let mut conn = pool.acquire().await?;
+        for iid in [1, 2] {
+            super::update_instance_difficulty(&mut conn, iid)
+                .await
+                .unwrap();
+        }
+
+        let difficulties =
+            sqlx::query_scalar::<_, Option<f64>>(r#"SELECT difficulty FROM Instance ORDER BY iid"#)
+                .fetch_all(&pool)
+                .await?;
+
+        // instance 2 has no solutions and hence no difficulty
+        assert!(difficulties[0].is_some_and(|d| (0.0..=1.0).contains(&d)));
+        assert_eq!(difficulties[1], None);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("instances", "solutions"))]
+    async fn update_outdated(pool: DbPool) -> sqlx::Result<()> {
+        super::mark_difficulty_outdated(&mut *pool.acquire().await?, 1)
+            .await
+            .unwrap();
+
+        assert_eq!(super::update_outdated_difficulties(&pool).await.unwrap(), 1);
+        assert_eq!(super::update_outdated_difficulties(&pool).await.unwrap(), 0);
+
+        let (difficulty, outdated) = sqlx::query_as::<_, (Option<f64>, bool)>(
+            r#"SELECT difficulty, difficulty_outdated FROM Instance WHERE iid = 1"#,
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert!(difficulty.is_some());
+        assert!(!outdated);
+
+        Ok(())
+    }
+}
@@ -1,11 +1,30 @@
-use axum::extract::Path;
+use axum::body::Body;
+use axum::extract::Request;
 
-use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE, RANGE};
 use axum::http::HeaderValue;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
 
 use super::common::*;
-use crate::server::app_state::DbPool;
+use crate::pace::codec::{decode_instance, BlobEncoding};
+use crate::pace::graph::Graph;
+use crate::pace::graph_writer::{write_graph, GraphFormat};
+use crate::pace::instance_reader::PaceReader;
+use crate::server::blob_store::{load_blob, BlobKind};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct InstanceDownloadOptions {
+    /// Deliver the stored DIMACS data without the header comment; supports range requests
+    /// if the blob store keeps the instance in plain form
+    #[serde(default)]
+    raw: bool,
+
+    /// Formats other than DIMACS are converted from the stored graph and carry no header
+    #[serde(default)]
+    format: GraphFormat,
+}
 
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow)]
 #[allow(non_snake_case)]
@@ -14,7 +33,9 @@ struct InstanceModel {
     name: Option<String>,
     description: Option<String>,
     submitted_by: Option<String>,
+    hash: String,
     data: Option<Vec<u8>>,
+    encoding: u8,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -28,28 +49,39 @@ struct InstanceResponseHeader {
     submitted_by: Option<String>,
 }
 
-async fn fetch_instance(id: u32, db_pool: &DbPool) -> HandlerResult<(InstanceModel, String)> {
+async fn fetch_instance_model(id: u32, app_data: &AppState) -> HandlerResult<InstanceModel> {
     // attempt to fetch instance from database
-    let mut instance = sqlx::query_as!(
+    Ok(sqlx::query_as!(
         InstanceModel,
         r#"SELECT 
-            i.iid, i.name, i.description, i.submitted_by, d.data 
+            i.iid, i.name, i.description, i.submitted_by, LOWER(HEX(d.hash)) AS "hash!", d.data, d.encoding
            FROM `Instance` i 
            JOIN `InstanceData` d ON i.data_did = d.did
            WHERE i.iid = ? LIMIT 1"#,
         id as i32,
     )
-    .fetch_one(db_pool)
-    .await?;
+    .fetch_one(app_data.db())
+    .await?)
+}
 
-    let data = instance.data.take();
+async fn fetch_instance(id: u32, app_data: &AppState) -> HandlerResult<(InstanceModel, String)> {
+    let mut instance = fetch_instance_model(id, app_data).await?;
 
-    if data.is_none() {
-        return error_bad_request!("Instance data is missing");
-    }
+    let inline = instance.data.take();
+    let Some(data) = load_blob(
+        app_data.blob_store(),
+        BlobKind::Instance,
+        &instance.hash,
+        inline,
+    )
+    .await?
+    else {
+        return error_not_found!("Instance data is missing");
+    };
 
     // decode it into utf-8
-    let data_string = String::from_utf8(data.unwrap())?;
+    let data = decode_instance(BlobEncoding::try_from(instance.encoding)?, &data)?;
+    let data_string = String::from_utf8(data.into_owned())?;
 
     Ok((instance, data_string))
 }
@@ -80,41 +112,118 @@ fn dimacs_file_from_instance_and_data(
     Ok(document)
 }
 
+/// Returns the DIMACS file with header comment, exactly as delivered by the download handler
+pub(super) async fn fetch_dimacs_file(id: u32, app_data: &AppState) -> HandlerResult<String> {
+    let (instance, data) = fetch_instance(id, app_data).await?;
+    dimacs_file_from_instance_and_data(&instance, data)
+}
+
+fn convert_instance(data: &str, format: GraphFormat) -> HandlerResult<Vec<u8>> {
+    let graph = Graph::try_from_pace_reader(PaceReader::try_new(data.as_bytes())?)?;
+
+    let mut document = Vec::with_capacity(data.len());
+    write_graph(format, &graph, &mut document)?;
+    Ok(document)
+}
+
+/// Serves the plain payload directly from the blob store, if possible. `ServeFile` takes
+/// care of range and conditional requests for local files; other stores stream the
+/// payload and handle the range themselves.
+async fn serve_plain_blob(
+    id: u32,
+    app_data: &AppState,
+    request: Request,
+) -> HandlerResult<Option<Response>> {
+    let instance = fetch_instance_model(id, app_data).await?;
+    if instance.data.is_some() || BlobEncoding::try_from(instance.encoding)? != BlobEncoding::Plain
+    {
+        return Ok(None);
+    }
+
+    let store = app_data.blob_store();
+    let mut response = match store.local_path(BlobKind::Instance, &instance.hash) {
+        Some(path) => ServeFile::new(path).oneshot(request).await?.map(Body::new),
+        None => {
+            let range = request.headers().get(RANGE);
+            match store
+                .serve(BlobKind::Instance, &instance.hash, range)
+                .await?
+            {
+                Some(response) => response,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    // blobs are stored without extension, so the content type cannot be guessed
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+    Ok(Some(response))
+}
+
 pub async fn instance_download_handler(
     Path(id): Path<u32>,
     State(data): State<Arc<AppState>>,
+    Query(options): Query<InstanceDownloadOptions>,
+    request: Request,
 ) -> HandlerResult<impl IntoResponse> {
-    let (instance, data) = fetch_instance(id, data.db()).await?;
-    let document = dimacs_file_from_instance_and_data(&instance, data);
-
-    let header_line = format!("attachment; filename=\"{id}.gr\"");
+    let header_line = format!(
+        "attachment; filename=\"{id}.{}\"",
+        options.format.extension()
+    );
     let content_disposition = HeaderValue::from_str(&header_line)?;
+    let content_type = HeaderValue::from_static(options.format.content_type());
+
+    if options.raw && options.format == GraphFormat::Dimacs {
+        if let Some(mut response) = serve_plain_blob(id, &data, request).await? {
+            response
+                .headers_mut()
+                .insert(CONTENT_DISPOSITION, content_disposition);
+            return Ok(response);
+        }
+    }
+
+    let (instance, data) = fetch_instance(id, &data).await?;
+    let document = match options.format {
+        GraphFormat::Dimacs if options.raw => data.into_bytes(),
+        GraphFormat::Dimacs => dimacs_file_from_instance_and_data(&instance, data)?.into_bytes(),
+        format => tokio::task::spawn_blocking(move || convert_instance(&data, format)).await??,
+    };
 
     Ok((
         [
             (CONTENT_DISPOSITION, content_disposition),
-            (CONTENT_TYPE, HeaderValue::from_static("text/plain")),
+            (CONTENT_TYPE, content_type),
         ],
         document,
-    ))
+    )
+        .into_response())
 }
 
 #[cfg(test)]
 mod tests {
-    use axum::http::StatusCode;
+    use axum::http::{header::RANGE, StatusCode};
+    use http_body_util::BodyExt;
 
     use super::*;
+    use crate::server::{
+        app_state::DbPool,
+        blob_store::{BlobSource, BlobStore, FilesystemBlobStore},
+    };
 
     #[sqlx::test(fixtures("instances"))]
     async fn fetch_instance(pool: DbPool) -> sqlx::Result<()> {
-        let (instance, data) = super::fetch_instance(1, &pool).await.unwrap();
+        let app_data = AppState::new(pool);
+        let (instance, data) = super::fetch_instance(1, &app_data).await.unwrap();
         assert_eq!(instance.iid, 1);
         assert_eq!(instance.submitted_by.unwrap(), "tester");
         assert!(data.starts_with("p ds"));
 
-        let _ = super::fetch_instance(2, &pool).await.unwrap();
+        let _ = super::fetch_instance(2, &app_data).await.unwrap();
 
-        assert!(super::fetch_instance(3, &pool).await.is_err());
+        assert!(super::fetch_instance(3, &app_data).await.is_err());
 
         Ok(())
     }
@@ -126,7 +235,9 @@ mod tests {
             name: Some(String::from("name")),
             description: None,
             submitted_by: None,
+            hash: String::new(),
             data: None,
+            encoding: 0,
         };
         let data = "HelloWorld";
 
@@ -144,7 +255,14 @@ mod tests {
     #[sqlx::test(fixtures("instances"))]
     async fn download_handler(pool: DbPool) -> sqlx::Result<()> {
         let state = State(Arc::new(AppState::new(pool)));
-        let response = instance_download_handler(Path(1), state).await.unwrap();
+        let response = instance_download_handler(
+            Path(1),
+            state,
+            Query(Default::default()),
+            Request::new(Body::empty()),
+        )
+        .await
+        .unwrap();
 
         let (headers, _body) = response.into_response().into_parts();
         assert_eq!(headers.status, StatusCode::OK);
@@ -156,4 +274,108 @@ mod tests {
 
         Ok(())
     }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn download_formats(pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(pool));
+
+        for (format, content_type, expected) in [
+            (GraphFormat::Metis, "text/plain", &b"3 2\n2\n1 3\n2\n"[..]),
+            (GraphFormat::EdgeList, "text/plain", b"1 2\n2 3\n"),
+            (
+                GraphFormat::Json,
+                "application/json",
+                br#"{"number_of_nodes":3,"number_of_edges":2,"adjacency":[[2],[1,3],[2]]}"#,
+            ),
+        ] {
+            let response = instance_download_handler(
+                Path(2),
+                State(state.clone()),
+                Query(InstanceDownloadOptions {
+                    format,
+                    ..Default::default()
+                }),
+                Request::new(Body::empty()),
+            )
+            .await
+            .unwrap()
+            .into_response();
+
+            assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), content_type);
+            assert_eq!(
+                response.headers().get(CONTENT_DISPOSITION).unwrap(),
+                &format!("attachment; filename=\"2.{}\"", format.extension())
+            );
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(&body[..], expected);
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn raw_range_download(pool: DbPool) -> sqlx::Result<()> {
+        let root = std::env::temp_dir().join(format!("blobs-{}", uuid::Uuid::new_v4().simple()));
+        let data = b"p ds 3 2\n1 2\n2 3\n";
+        let hash = "00000000000000000000000000000000000000ff";
+
+        let store = FilesystemBlobStore::new(&root);
+        store
+            .put(
+                BlobKind::Instance,
+                hash,
+                BlobEncoding::Plain,
+                BlobSource::Memory(data),
+            )
+            .await
+            .unwrap();
+
+        sqlx::query(r#"INSERT INTO InstanceData (hash, encoding) VALUES (UNHEX(?), 0)"#)
+            .bind(hash)
+            .execute(&pool)
+            .await?;
+        sqlx::query(r#"UPDATE Instance SET data_did = LAST_INSERT_ID() WHERE iid = 2"#)
+            .execute(&pool)
+            .await?;
+
+        let state = Arc::new(AppState::new(pool).with_blob_store(Arc::new(store)));
+        let download = |range: Option<&'static str>| {
+            let state = state.clone();
+            async move {
+                let mut request = Request::new(Body::empty());
+                if let Some(range) = range {
+                    request
+                        .headers_mut()
+                        .insert(RANGE, HeaderValue::from_static(range));
+                }
+
+                let response = instance_download_handler(
+                    Path(2),
+                    State(state),
+                    Query(InstanceDownloadOptions {
+                        raw: true,
+                        ..Default::default()
+                    }),
+                    request,
+                )
+                .await
+                .unwrap()
+                .into_response();
+
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                (status, body)
+            }
+        };
+
+        assert_eq!(download(None).await, (StatusCode::OK, data[..].into()));
+        assert_eq!(
+            download(Some("bytes=9-12")).await,
+            (StatusCode::PARTIAL_CONTENT, data[9..13].to_vec().into())
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+        Ok(())
+    }
 }
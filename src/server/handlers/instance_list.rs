@@ -3,7 +3,7 @@ use axum::http::{
     HeaderValue,
 };
 use itertools::Itertools;
-use sqlx::{Database, QueryBuilder};
+use sqlx::{Database, MySql, QueryBuilder};
 use sqlx_conditional_queries::conditional_query_as;
 use uuid::Uuid;
 
@@ -49,6 +49,21 @@ pub struct FilterOptions {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub best_score_ub: Option<u32>,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lower_bound_lb: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lower_bound_ub: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gap_lb: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gap_ub: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty_lb: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty_ub: Option<f64>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub min_deg_lb: Option<u32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -116,10 +131,24 @@ pub struct FilterOptions {
     pub seconds_computed_ub: Option<f64>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub search : Option<String>,
+    pub search: Option<String>,
 
     #[serde(default)]
     pub result_status: ResultStatusFilter,
+
+    /// Only instances with a result of a run built from a commit starting with this hash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_git_commit: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_solver_version: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_cpu_model: Option<String>,
+
+    /// `name=value`; only instances with a result of a run that set this parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_parameter: Option<String>,
 }
 
 #[derive(Clone, Deserialize, Serialize, Debug, PartialEq, Eq, Default)]
@@ -131,7 +160,10 @@ pub enum ResultStatusFilter {
     Valid,
     Invalid,
 
+    /// score matches the instance's lower bound and is thus proven to be optimal
     Optimal,
+    /// score matches the best known score of the instance
+    Best,
     Suboptimal,
     Incomplete,
     Timeout,
@@ -158,6 +190,9 @@ pub enum SortBy {
     CreatedAt,
     #[serde(alias = "best_score")]
     BestScore,
+    #[serde(alias = "lower_bound")]
+    LowerBound,
+    Gap,
     Difficulty,
     #[serde(alias = "min_deg")]
     MinDeg,
@@ -200,7 +235,9 @@ impl SortBy {
             SortBy::Edges => "edges",
             SortBy::CreatedAt => "i.created_at",
             SortBy::BestScore => "best_score",
-            SortBy::Difficulty => "best_score", // TODO: this is not what we want
+            SortBy::LowerBound => "lower_bound",
+            SortBy::Gap => "CAST(i.best_score AS SIGNED) - CAST(i.lower_bound AS SIGNED)",
+            SortBy::Difficulty => "difficulty",
             SortBy::MinDeg => "min_deg",
             SortBy::MaxDeg => "max_deg",
             SortBy::AvgDeg => "edges / nodes",
@@ -233,6 +270,8 @@ struct MaxValues {
     nodes: Option<u32>,
     edges: Option<u32>,
     best_score: Option<u32>,
+    lower_bound: Option<u32>,
+    difficulty: Option<f64>,
 
     min_deg: Option<u32>,
     max_deg: Option<u32>,
@@ -273,6 +312,8 @@ struct InstanceModel {
     name: Option<String>,
     description: Option<String>,
     best_score: Option<u32>,
+    lower_bound: Option<u32>,
+    difficulty: Option<f64>,
     tags: Option<String>,
 
     min_deg: Option<u32>,
@@ -280,6 +321,8 @@ struct InstanceModel {
     num_ccs: Option<u32>,
     nodes_largest_cc: Option<u32>,
     diameter: Option<u32>,
+    diameter_lower: Option<u32>,
+    diameter_upper: Option<u32>,
     treewidth: Option<u32>,
     planar: Option<bool>,
     bipartite: Option<bool>,
@@ -309,6 +352,12 @@ struct InstanceResult {
     description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     best_score: Option<NumNodes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lower_bound: Option<NumNodes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gap: Option<NumNodes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    difficulty: Option<f64>,
     tags: Vec<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     min_deg: Option<u32>,
@@ -320,6 +369,11 @@ struct InstanceResult {
     nodes_largest_cc: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     diameter: Option<u32>,
+    /// Bounds on the diameter if its exact value is unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diameter_lower: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diameter_upper: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     treewidth: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -331,9 +385,9 @@ struct InstanceResult {
 }
 
 impl FilterOptions {
-    fn check_validity(&self) -> HandlerResult<()> {
+    pub(super) fn check_validity(&self) -> HandlerResult<()> {
         if self.run.is_some() != self.solver.is_some() {
-            return Err(anyhow::anyhow!("solver and run must be both provided or both not").into());
+            return error_bad_request!("solver and run must be both provided or both not");
         }
 
         if self.run.is_none() {
@@ -346,23 +400,56 @@ impl FilterOptions {
                 || self.seconds_computed_ub.is_some()
                 || self.result_status != ResultStatusFilter::None
             {
-                return Err(anyhow::anyhow!(
+                return error_bad_request!(
                     "solver and run must be provided when filtering by score, score_diff, seconds_computed or status"
-                )
-                .into());
+                );
             }
 
             if SORT_BY_ONLY_WITH_RUN.iter().contains(&self.sort_by) {
-                return Err(anyhow::anyhow!(
-                    "sort-by option requires solver and run to be provided"
-                )
-                .into());
+                return error_bad_request!("sort-by option requires solver and run to be provided");
+            }
+        }
+
+        if let Some(commit) = &self.run_git_commit {
+            if commit.is_empty() || !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+                return error_bad_request!(
+                    "run_git_commit must be a hexadecimal (prefix of a) hash"
+                );
             }
         }
 
+        if self.run_parameter.is_some() && self.run_parameter_path_and_value().is_none() {
+            return error_bad_request!(
+                "run_parameter must have the form name=value, where name consists of letters, digits, '_', '-' and '.'"
+            );
+        }
+
         Ok(())
     }
 
+    fn has_run_metadata_filter(&self) -> bool {
+        self.run_git_commit.is_some()
+            || self.run_solver_version.is_some()
+            || self.run_cpu_model.is_some()
+            || self.run_parameter.is_some()
+    }
+
+    /// Splits `run_parameter` into the JSON path of the parameter and its value
+    fn run_parameter_path_and_value(&self) -> Option<(String, String)> {
+        let (name, value) = self.run_parameter.as_ref()?.split_once('=')?;
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+        valid_name.then(|| (format!("$.\"{name}\""), value.to_string()))
+    }
+
+    /// Whether the results are restricted to the solutions of a single run
+    pub(super) fn has_run(&self) -> bool {
+        self.run.is_some()
+    }
+
     fn solver_and_run_strings(&self) -> Option<(String, String)> {
         let run = self.run?.simple().to_string();
         let solver = self.solver?.simple().to_string();
@@ -408,6 +495,13 @@ where
     append_range_filter!(opts, nodes);
     append_range_filter!(opts, edges);
     append_range_filter!(opts, best_score);
+    append_range_filter!(opts, lower_bound);
+    append_range_filter!(
+        opts,
+        gap,
+        "CAST(i.best_score AS SIGNED) - CAST(i.lower_bound AS SIGNED)"
+    );
+    append_range_filter!(opts, difficulty);
 
     append_range_filter!(opts, min_deg);
     append_range_filter!(opts, max_deg);
@@ -457,6 +551,9 @@ where
                 builder.push(" AND s.score IS NULL ");
             }
             ResultStatusFilter::Optimal => {
+                builder.push(" AND i.lower_bound = s.score ");
+            }
+            ResultStatusFilter::Best => {
                 builder.push(" AND i.best_score = s.score ");
             }
             ResultStatusFilter::Suboptimal => {
@@ -481,8 +578,36 @@ where
         }
     }
 
+    if opts.has_run_metadata_filter() {
+        builder.push(" AND EXISTS (SELECT 1 FROM Solution ms JOIN SolverRun msr ON msr.run_uuid = ms.sr_uuid WHERE ms.instance_iid = i.iid ");
+
+        if let Some(commit) = &opts.run_git_commit {
+            builder.push(" AND msr.git_commit LIKE ");
+            builder.push_bind(format!("{}%", commit.to_ascii_lowercase()));
+        }
+
+        if let Some(version) = &opts.run_solver_version {
+            builder.push(" AND msr.solver_version = ");
+            builder.push_bind(version.clone());
+        }
+
+        if let Some(cpu_model) = &opts.run_cpu_model {
+            builder.push(" AND msr.cpu_model = ");
+            builder.push_bind(cpu_model.clone());
+        }
+
+        if let Some((path, value)) = opts.run_parameter_path_and_value() {
+            builder.push(" AND JSON_UNQUOTE(JSON_EXTRACT(msr.parameters, ");
+            builder.push_bind(path);
+            builder.push(")) = ");
+            builder.push_bind(value);
+        }
+
+        builder.push(") ");
+    }
+
     if let Some(search) = &opts.search {
-        builder.push (" AND (MATCH (i.`name`, i.`description`, i.`submitted_by`) AGAINST (");
+        builder.push(" AND (MATCH (i.`name`, i.`description`, i.`submitted_by`) AGAINST (");
         builder.push_bind(search);
         builder.push(")");
 
@@ -526,8 +651,8 @@ async fn retrieve_instances(
 ) -> HandlerResult<Vec<InstanceModel>> {
     let mut builder = sqlx::QueryBuilder::new(
         r#"SELECT 
-            i.iid, i.nodes, i.edges, i.name, i.description, i.best_score,
-            i.min_deg, i.max_deg, i.num_ccs, i.nodes_largest_cc, i.diameter, i.treewidth, 
+            i.iid, i.nodes, i.edges, i.name, i.description, i.best_score, i.lower_bound, i.difficulty,
+            i.min_deg, i.max_deg, i.num_ccs, i.nodes_largest_cc, i.diameter, i.diameter_lower, i.diameter_upper, i.treewidth, 
             i.planar, i.bipartite,
             GROUP_CONCAT(tag_tid) as tags, "#,
     );
@@ -606,6 +731,8 @@ async fn compute_max_values(
             MAX(num_ccs)   as num_ccs,
             MAX(treewidth) as treewidth,
             MAX(best_score)as best_score,
+            MAX(lower_bound) as lower_bound,
+            MAX(difficulty) as difficulty,
             MAX(nodes_largest_cc) as nodes_largest_cc,
             {#rest}"#,
         #rest = match solver_and_run {
@@ -670,11 +797,19 @@ pub async fn instance_list_handler(
                 name: model.name.to_owned(),
                 description: model.description.to_owned(),
                 best_score: model.best_score,
+                lower_bound: model.lower_bound,
+                gap: model
+                    .best_score
+                    .zip(model.lower_bound)
+                    .map(|(best, lb)| best.saturating_sub(lb)),
+                difficulty: model.difficulty,
                 min_deg: model.min_deg,
                 max_deg: model.max_deg,
                 num_ccs: model.num_ccs,
                 nodes_largest_cc: model.nodes_largest_cc,
                 diameter: model.diameter,
+                diameter_lower: model.diameter_lower,
+                diameter_upper: model.diameter_upper,
                 treewidth: model.treewidth,
                 planar: model.planar,
                 bipartite: model.bipartite,
@@ -702,44 +837,53 @@ pub async fn instance_list_handler(
     Ok(Json(json_response))
 }
 
-pub async fn instance_list_download_handler(
-    Query(opts): Query<FilterOptions>,
-    State(app_data): State<Arc<AppState>>,
-) -> HandlerResult<impl IntoResponse> {
-    opts.check_validity()?;
+/// Builds `<select> FROM Instance i ...` over all instances matching `opts` (ignoring
+/// pagination) in the requested order; `select` may refer to columns of `i` and, in
+/// run-mode, of the solution `s`
+pub(super) fn matching_instances_query<'a>(
+    select: &str,
+    opts: &'a FilterOptions,
+) -> HandlerResult<QueryBuilder<'a, MySql>> {
+    let mut builder = sqlx::QueryBuilder::new(select);
+    builder.push(" FROM `Instance` i ");
 
-    let list_as_string = {
-        let mut builder = sqlx::QueryBuilder::new(r#"SELECT i.iid FROM `Instance` i "#);
+    if opts.run.is_some() {
+        builder.push(" JOIN Solution s ON i.iid = s.instance_iid ");
+    }
 
-        if opts.run.is_some() {
-            builder.push(" JOIN Solution s ON i.iid = s.instance_iid ");
-        }
+    if let Some(tid) = opts.tag {
+        builder.push(" JOIN InstanceTag it ON i.iid = it.instance_iid WHERE it.tag_tid = ");
+        builder.push_bind(tid);
+    } else {
+        builder.push(" WHERE 1=1 ");
+    }
 
-        if let Some(tid) = opts.tag {
-            builder.push(" JOIN InstanceTag it ON i.iid = it.instance_iid WHERE it.tag_tid = ");
-            builder.push_bind(tid);
-        } else {
-            builder.push(" WHERE 1=1 ");
-        }
+    builder = append_filters_to_query_builder(builder, opts)?;
 
-        builder = append_filters_to_query_builder(builder, &opts)?;
+    builder.push(" ORDER BY ");
+    builder.push(opts.sort_by.to_sql_fields());
+
+    builder.push(match opts.sort_direction {
+        SortDirection::Desc => " DESC ",
+        SortDirection::Asc => " ASC ",
+    });
 
-        builder.push(" ORDER BY ");
-        builder.push(opts.sort_by.to_sql_fields());
+    Ok(builder)
+}
 
-        builder.push(match opts.sort_direction {
-            SortDirection::Desc => " DESC ",
-            SortDirection::Asc => " ASC ",
-        });
+pub async fn instance_list_download_handler(
+    Query(opts): Query<FilterOptions>,
+    State(app_data): State<Arc<AppState>>,
+) -> HandlerResult<impl IntoResponse> {
+    opts.check_validity()?;
 
-        builder
-            .build_query_scalar::<i32>()
-            .fetch_all(app_data.db())
-            .await?
-            .into_iter()
-            .map(|x| x.to_string())
-            .join("\n")
-    };
+    let list_as_string = matching_instances_query("SELECT i.iid", &opts)?
+        .build_query_scalar::<i32>()
+        .fetch_all(app_data.db())
+        .await?
+        .into_iter()
+        .map(|x| x.to_string())
+        .join("\n");
 
     let document = format!("c {}\n{list_as_string}", serde_json::to_string(&opts)?);
 
@@ -887,6 +1031,7 @@ mod tests {
             ResultStatusFilter::Valid,
             ResultStatusFilter::Invalid,
             ResultStatusFilter::Optimal,
+            ResultStatusFilter::Best,
             ResultStatusFilter::Suboptimal,
             ResultStatusFilter::Incomplete,
             ResultStatusFilter::Infeasible,
@@ -903,6 +1048,12 @@ mod tests {
     test_filter_option!(edges_ub, [Some(0), Some(1)]);
     test_filter_option!(best_score_lb, [Some(0), Some(1)]);
     test_filter_option!(best_score_ub, [Some(0), Some(1)]);
+    test_filter_option!(lower_bound_lb, [Some(0), Some(1)]);
+    test_filter_option!(lower_bound_ub, [Some(0), Some(1)]);
+    test_filter_option!(gap_lb, [Some(0), Some(1)]);
+    test_filter_option!(gap_ub, [Some(0), Some(1)]);
+    test_filter_option!(difficulty_lb, [Some(0.0), Some(0.5)]);
+    test_filter_option!(difficulty_ub, [Some(0.5), Some(1.0)]);
     test_filter_option!(min_deg_lb, [Some(0), Some(1)]);
     test_filter_option!(min_deg_ub, [Some(0), Some(1)]);
     test_filter_option!(max_deg_lb, [Some(0), Some(1)]);
@@ -918,4 +1069,51 @@ mod tests {
     test_filter_option!(planar, [Some(false), Some(true)]);
     test_filter_option!(bipartite, [Some(false), Some(true)]);
     test_filter_option!(regular, [Some(false), Some(true)]);
+    test_filter_option!(run_git_commit, [None, Some("0123abc".to_string())]);
+    test_filter_option!(run_solver_version, [None, Some("1.0".to_string())]);
+    test_filter_option!(run_cpu_model, [None, Some("Xeon".to_string())]);
+    test_filter_option!(run_parameter, [None, Some("threads=4".to_string())]);
+
+    #[sqlx::test(fixtures("instances", "solutions"))]
+    async fn filter_by_run_metadata(db_pool: DbPool) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"UPDATE SolverRun SET git_commit = 'abcdef01', parameters = '{"threads": 4, "mode": "fast"}'
+               WHERE run_uuid = UNHEX('00000000000000000001000000000000')"#,
+        )
+        .execute(&db_pool)
+        .await?;
+
+        let list = |opts: FilterOptions| {
+            let state = Arc::new(AppState::new(db_pool.clone()));
+            async move {
+                matching_instances_query("SELECT i.iid", &opts)
+                    .unwrap()
+                    .build_query_scalar::<i32>()
+                    .fetch_all(state.db())
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let commit = |commit: &str| FilterOptions {
+            run_git_commit: Some(commit.into()),
+            ..Default::default()
+        };
+        let parameter = |parameter: &str| FilterOptions {
+            run_parameter: Some(parameter.into()),
+            ..Default::default()
+        };
+
+        assert_eq!(list(commit("ABC")).await, vec![1]);
+        assert_eq!(list(commit("abd")).await, Vec::<i32>::new());
+        assert_eq!(list(parameter("threads=4")).await, vec![1]);
+        assert_eq!(list(parameter("mode=fast")).await, vec![1]);
+        assert_eq!(list(parameter("mode=slow")).await, Vec::<i32>::new());
+
+        assert!(commit("xyz").check_validity().is_err());
+        assert!(parameter("\"=1").check_validity().is_err());
+        assert!(parameter("threads").check_validity().is_err());
+
+        Ok(())
+    }
 }
@@ -2,13 +2,17 @@ use serde::Deserialize;
 use sqlx::types::chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-use super::{common::*, solution_upload::SolverResultType};
+use super::{
+    common::*,
+    solution_upload::{ResourceUsage, SolverResultType},
+};
 
 pub async fn instance_solutions_handler(
     Query(opts): Query<FilterOptions>,
     State(app_data): State<Arc<AppState>>,
 ) -> HandlerResult<impl IntoResponse> {
     let global_hist = compute_global_histogram(&app_data, opts.iid).await?;
+    let bounds = fetch_score_bounds(&app_data, opts.iid).await?;
 
     let solver_solutions = if let Some(solver) = opts.solver {
         Some(fetch_solutions_of_solver(&app_data, opts.iid, solver).await?)
@@ -19,6 +23,12 @@ pub async fn instance_solutions_handler(
     Ok(Json(Response {
         status: "ok",
         filters: opts,
+        best_score: bounds.best_score,
+        lower_bound: bounds.lower_bound,
+        gap: bounds
+            .best_score
+            .zip(bounds.lower_bound)
+            .map(|(best, lb)| best.saturating_sub(lb)),
         global_score_histogram: global_hist,
         solver_solutions,
     }))
@@ -33,6 +43,25 @@ async fn compute_global_histogram(
         iid).fetch_all(app_data.db()).await?)
 }
 
+struct ScoreBounds {
+    best_score: Option<u32>,
+    lower_bound: Option<u32>,
+}
+
+async fn fetch_score_bounds(app_data: &AppState, iid: u32) -> anyhow::Result<ScoreBounds> {
+    Ok(sqlx::query_as!(
+        ScoreBounds,
+        r#"SELECT best_score, lower_bound FROM Instance WHERE iid = ?"#,
+        iid
+    )
+    .fetch_optional(app_data.db())
+    .await?
+    .unwrap_or(ScoreBounds {
+        best_score: None,
+        lower_bound: None,
+    }))
+}
+
 async fn fetch_solutions_of_solver(
     app_data: &AppState,
     iid: u32,
@@ -47,6 +76,13 @@ async fn fetch_solutions_of_solver(
         seconds_computed: Option<f64>,
         score: Option<u32>,
         error_code: u32,
+
+        peak_memory_bytes: Option<u64>,
+        cpu_seconds: Option<f64>,
+        wall_seconds: Option<f64>,
+        exit_code: Option<i32>,
+        term_signal: Option<i32>,
+        stderr_tail: Option<String>,
     }
 
     let rows = sqlx::query_as!(
@@ -58,9 +94,16 @@ async fn fetch_solutions_of_solver(
             sr.description as run_description,
             s.seconds_computed,
             s.score,
-            s.error_code as "error_code!"
+            s.error_code as "error_code!",
+            r.peak_memory_bytes,
+            r.cpu_seconds,
+            r.wall_seconds,
+            r.exit_code,
+            r.term_signal,
+            r.stderr_tail
            FROM Solution s
            JOIN SolverRun sr ON s.sr_uuid = sr.run_uuid
+           LEFT JOIN SolutionResources r ON r.solution_sid = s.sid
            WHERE s.instance_iid = ? AND sr.solver_uuid = UNHEX(?)"#,
         iid,
         solver.simple().to_string()
@@ -70,6 +113,15 @@ async fn fetch_solutions_of_solver(
 
     let mut result = Vec::with_capacity(rows.len());
     for row in rows {
+        let resources = ResourceUsage {
+            peak_memory_bytes: row.peak_memory_bytes,
+            cpu_seconds: row.cpu_seconds,
+            wall_seconds: row.wall_seconds,
+            exit_code: row.exit_code,
+            signal: row.term_signal,
+            stderr_tail: row.stderr_tail,
+        };
+
         result.push(SolutionRun {
             created_at: row.created_at.to_rfc3339(),
             run: Uuid::from_slice(&row.run)?,
@@ -78,6 +130,7 @@ async fn fetch_solutions_of_solver(
             seconds_computed: row.seconds_computed,
             score: row.score,
             status: SolverResultType::try_from(row.error_code)?,
+            resources: (!resources.is_empty()).then_some(resources),
         });
     }
 
@@ -109,12 +162,22 @@ struct SolutionRun {
     #[serde(skip_serializing_if = "Option::is_none")]
     score: Option<u32>,
     status: SolverResultType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resources: Option<ResourceUsage>,
 }
 
 #[derive(Clone, Serialize, Debug, Default)]
 pub struct Response {
     status: &'static str,
     filters: FilterOptions,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    best_score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lower_bound: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gap: Option<u32>,
+
     global_score_histogram: Vec<HistogramEntry>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
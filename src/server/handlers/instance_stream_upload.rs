@@ -0,0 +1,367 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRequest, Multipart, Request},
+    http::header,
+};
+use futures::{Stream, StreamExt};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use super::{
+    common::*,
+    instance_upload::{compute_normalized_metadata, store_instance, InstanceUploadOptions},
+};
+use crate::{
+    pace::{
+        codec::{encode_edge_list, BlobEncoding},
+        graph_reader::InputFormat,
+        normalize::{normalize_input, NormalizeOptions, NormalizedInstance, TempFile},
+    },
+    server::blob_store::BlobSource,
+};
+
+/// Number of body chunks buffered between the request and the normaliser
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Name of the multipart field carrying the instance; all other fields must precede it
+const DATA_FIELD: &str = "data";
+
+/// Query parameters of raw-body uploads; `tags` is a comma-separated list
+#[derive(Debug, Default, Deserialize)]
+pub struct InstanceStreamUploadQuery {
+    name: Option<String>,
+    description: Option<String>,
+    submitted_by: Option<String>,
+    tags: Option<String>,
+    ignore_header: Option<bool>,
+    format: Option<InputFormat>,
+}
+
+impl From<InstanceStreamUploadQuery> for InstanceUploadOptions {
+    fn from(query: InstanceStreamUploadQuery) -> Self {
+        InstanceUploadOptions {
+            name: query.name,
+            description: query.description,
+            submitted_by: query.submitted_by,
+            tags: query.tags.map(|tags| {
+                tags.split(',')
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(String::from)
+                    .collect()
+            }),
+            ignore_header: query.ignore_header,
+            format: query.format,
+        }
+    }
+}
+
+/// Blocking reader over the chunks an async task sends through a channel
+struct ChannelReader {
+    receiver: mpsc::Receiver<std::io::Result<Bytes>>,
+    chunk: Bytes,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => self.chunk = chunk?,
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+/// Feeds `chunks` into the normaliser running on a blocking thread, which writes the
+/// normalised instance into a temporary file.
+async fn normalize_stream<S, E>(
+    mut chunks: S,
+    options: NormalizeOptions,
+) -> HandlerResult<(NormalizedInstance, TempFile)>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Into<anyhow::Error>,
+{
+    let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+    let normalizer = tokio::task::spawn_blocking(move || {
+        let output = TempFile::create_in(&options.temp_dir)?;
+        let reader = BufReader::new(ChannelReader {
+            receiver,
+            chunk: Bytes::new(),
+        });
+
+        let normalized = normalize_input(reader, BufWriter::new(output.file()), &options)?;
+        Ok::<_, std::io::Error>((normalized, output))
+    });
+
+    while let Some(chunk) = chunks.next().await {
+        match chunk {
+            Ok(chunk) => {
+                // the normaliser stopped early; its error is reported below
+                if sender.send(Ok(chunk)).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                let _ = sender
+                    .send(Err(std::io::Error::other("Upload aborted")))
+                    .await;
+                drop(sender);
+                let _ = normalizer.await;
+                return Err(AppError::from(e));
+            }
+        }
+    }
+    drop(sender);
+
+    Ok(normalizer.await??)
+}
+
+async fn store_normalized(
+    app_data: &AppState,
+    options: InstanceUploadOptions,
+    normalized: NormalizedInstance,
+    output: TempFile,
+) -> HandlerResult<serde_json::Value> {
+    let plain = app_data.blob_store().prefers_plain_instances();
+    let normalize_options = options.normalize_options(app_data);
+    let (max_edges_in_memory, temp_dir) = (
+        normalize_options.max_edges_in_memory,
+        normalize_options.temp_dir,
+    );
+    let (metadata, encoded) = {
+        let path = output.path().to_path_buf();
+        let normalized = normalized.clone();
+        tokio::task::spawn_blocking(move || {
+            let metadata = compute_normalized_metadata(
+                &normalized,
+                BufReader::new(File::open(&path)?),
+                max_edges_in_memory,
+            )?;
+            if plain {
+                return Ok::<_, AppError>((metadata, None));
+            }
+
+            // the normaliser's output is canonical, so the edge list reproduces it exactly
+            let encoded = TempFile::create_in(&temp_dir)?;
+            encode_edge_list(
+                BufReader::new(File::open(&path)?),
+                BufWriter::new(encoded.file()),
+            )?;
+
+            Ok((metadata, Some(encoded)))
+        })
+        .await??
+    };
+
+    let (encoding, data) = match &encoded {
+        Some(encoded) => (BlobEncoding::EdgeList, encoded),
+        None => (BlobEncoding::Plain, &output),
+    };
+
+    store_instance(
+        app_data,
+        options,
+        &normalized,
+        metadata,
+        encoding,
+        BlobSource::File(data.path()),
+    )
+    .await
+}
+
+/// Fields of the form override the query parameters passed in `options`
+async fn upload_multipart(
+    app_data: &AppState,
+    mut options: InstanceUploadOptions,
+    mut multipart: Multipart,
+) -> HandlerResult<serde_json::Value> {
+    while let Some(field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == DATA_FIELD {
+            let normalize_options = options.normalize_options(app_data);
+            let (normalized, output) = normalize_stream(field, normalize_options).await?;
+
+            if multipart.next_field().await?.is_some() {
+                return error_bad_request!("The data field has to be the last one");
+            }
+
+            return store_normalized(app_data, options, normalized, output).await;
+        }
+
+        let value = field.text().await?;
+        match name.as_str() {
+            "name" => options.name = Some(value),
+            "description" => options.description = Some(value),
+            "submitted_by" => options.submitted_by = Some(value),
+            "tags" => options.tags.get_or_insert_with(Vec::new).push(value),
+            "ignore_header" => {
+                let Ok(ignore_header) = value.parse() else {
+                    return error_bad_request!("ignore_header has to be true or false");
+                };
+                options.ignore_header = Some(ignore_header);
+            }
+            "format" => {
+                let Ok(format) = value.parse::<InputFormat>() else {
+                    return error_bad_request!(format!("Unknown format {value:?}"));
+                };
+                options.format = Some(format);
+            }
+            _ => return error_bad_request!(format!("Unknown field {name:?}")),
+        }
+    }
+
+    error_bad_request!("Missing data field")
+}
+
+/// Uploads an instance without holding it in memory. The instance is either the raw
+/// request body (with the remaining fields passed as query parameters) or the last field
+/// of a `multipart/form-data` request.
+pub async fn instance_stream_upload_handler(
+    State(app_data): State<Arc<AppState>>,
+    Query(query): Query<InstanceStreamUploadQuery>,
+    request: Request<Body>,
+) -> HandlerResult<impl IntoResponse> {
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/form-data"));
+
+    let options = InstanceUploadOptions::from(query);
+    let response = if is_multipart {
+        let multipart = Multipart::from_request(request, &()).await?;
+        upload_multipart(&app_data, options, multipart).await?
+    } else {
+        let (normalized, output) = normalize_stream(
+            request.into_body().into_data_stream(),
+            options.normalize_options(&app_data),
+        )
+        .await?;
+
+        store_normalized(&app_data, options, normalized, output).await?
+    };
+
+    debug!("Streaming upload finished: {response}");
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::app_state::DbPool;
+
+    async fn upload(pool: &DbPool, request: Request<Body>) -> serde_json::Value {
+        let response = super::instance_stream_upload_handler(
+            State(Arc::new(AppState::new(pool.clone()))),
+            Query(InstanceStreamUploadQuery {
+                name: Some("streamed".into()),
+                ..Default::default()
+            }),
+            request,
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert!(response.status().is_success());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn raw_and_multipart(pool: DbPool) -> sqlx::Result<()> {
+        // deliver the body in several chunks, splitting lines
+        let chunks: Vec<Result<&'static str, std::io::Error>> =
+            vec![Ok("p ds 4 3\n1 "), Ok("2\n2 3\n3"), Ok(" 4\n")];
+        let raw = Request::builder()
+            .body(Body::from_stream(futures::stream::iter(chunks)))
+            .unwrap();
+
+        let first = upload(&pool, raw).await;
+        assert_eq!(first["status"], "success");
+
+        let (nodes, name) =
+            sqlx::query_as::<_, (i32, String)>(r#"SELECT nodes, name FROM Instance WHERE iid = ?"#)
+                .bind(first["instance_id"].as_i64())
+                .fetch_one(&pool)
+                .await?;
+        assert_eq!((nodes, name.as_str()), (4, "streamed"));
+
+        let boundary = "XBOUNDARYX";
+        let body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"ignore_header\"\r\n\r\ntrue\r\n\
+             --{boundary}\r\nContent-Disposition: form-data; name=\"data\"; filename=\"x.gr\"\r\n\r\n\
+             p ds 9 9\n4 3\n3 2\n2 1\r\n--{boundary}--\r\n"
+        );
+        let multipart = Request::builder()
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .unwrap();
+
+        let second = upload(&pool, multipart).await;
+        assert_eq!(second["status"], "duplicate");
+        assert_eq!(second["instance_id"], first["instance_id"]);
+
+        // gzip-compressed SNAP edge list of the same path
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, b"# snap\n0\t1\n2\t1\n3\t2\n").unwrap();
+        let gzip = Request::builder()
+            .body(Body::from(encoder.finish().unwrap()))
+            .unwrap();
+
+        let third = upload(&pool, gzip).await;
+        assert_eq!(third["status"], "duplicate");
+        assert_eq!(third["instance_id"], first["instance_id"]);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn unknown_format(pool: DbPool) -> sqlx::Result<()> {
+        let boundary = "XBOUNDARYX";
+        let body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"format\"\r\n\r\ngraphml\r\n\
+             --{boundary}\r\nContent-Disposition: form-data; name=\"data\"\r\n\r\n\
+             0 1\r\n--{boundary}--\r\n"
+        );
+        let request = Request::builder()
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .unwrap();
+
+        let Err(error) = super::instance_stream_upload_handler(
+            State(Arc::new(AppState::new(pool))),
+            Query(InstanceStreamUploadQuery::default()),
+            request,
+        )
+        .await
+        else {
+            panic!("unknown format was accepted");
+        };
+        assert_eq!(
+            error.into_response().status(),
+            axum::http::StatusCode::BAD_REQUEST
+        );
+
+        Ok(())
+    }
+}
@@ -28,6 +28,10 @@ pub struct UpdateRequest {
     planar: Option<bool>,
     #[serde(default)]
     bipartite: Option<bool>,
+
+    /// Overrides whether the best-known solution of the instance may be downloaded
+    #[serde(default)]
+    publish_best_solution: Option<bool>,
 }
 
 async fn check_params(app_data: &Arc<AppState>, body: &UpdateRequest) -> HandlerResult<()> {
@@ -114,6 +118,12 @@ async fn update_record(app_data: &Arc<AppState>, body: &UpdateRequest) -> Handle
     process!(treewidth);
     process!(planar);
     process!(bipartite);
+    process!(publish_best_solution);
+
+    // bounds are only kept while the exact diameter is unknown
+    if body.diameter.is_some() {
+        builder.push(", diameter_lower = NULL, diameter_upper = NULL");
+    }
 
     if !any_is_set {
         return error_bad_request!("No fields to update");
@@ -181,6 +191,7 @@ mod test {
     test_field!(treewidth, 7, u32);
     test_field!(planar, true, bool);
     test_field!(bipartite, true, bool);
+    test_field!(publish_best_solution, true, bool);
 
     #[sqlx::test(fixtures("instances"))]
     async fn test_multiple(pool: DbPool) -> sqlx::Result<()> {
@@ -1,115 +1,197 @@
-use sha1::{Digest, Sha1};
+use tracing::debug;
 
-use super::common::*;
+use super::{
+    common::*,
+    instance_compute_meta::{update_instance_metadata, update_instance_streamed_metadata},
+};
 
-use crate::pace::{
-    graph::*, instance_reader::PaceReader, instance_writer::pace_writer, PROBLEM_ID,
+use crate::{
+    pace::{
+        codec::{encode_instance, BlobEncoding},
+        graph::*,
+        graph_reader::InputFormat,
+        instance_reader::PaceReader,
+        metadata::{GraphMetadata, StreamedMetadata},
+        normalize::{normalize_input, NormalizeOptions, NormalizedInstance},
+    },
+    server::{
+        app_state::DbTransaction,
+        blob_gc::put_pending_blob,
+        blob_store::{BlobKind, BlobSource},
+    },
 };
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct InstanceUploadRequest {
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct InstanceUploadOptions {
     pub name: Option<String>,
     pub description: Option<String>,
     pub submitted_by: Option<String>,
     pub tags: Option<Vec<String>>,
     pub ignore_header: Option<bool>,
-    pub data: String,
+    /// Detected from the data if unset; required for large numeric inputs that may be
+    /// METIS or SNAP
+    pub format: Option<InputFormat>,
 }
 
-fn normalize_dimacs(
-    data: &str,
-    check_header: bool,
-) -> HandlerResult<(NumNodes, NumEdges, String, String)> {
-    let pace_reader = PaceReader::try_new(data.as_bytes())?;
-    let num_nodes_per_header = pace_reader.number_of_nodes() as usize;
-    let num_edges_per_header = pace_reader.number_of_edges() as usize;
-
-    let mut edges = Vec::with_capacity(num_edges_per_header);
-    for edge in pace_reader {
-        let edge = edge?.normalized();
-        if check_header && edge.max_node() >= num_nodes_per_header as Node {
-            return error_bad_request!(
-                "Edge contains node id that is larger than the number of nodes in the header"
-            );
+impl InstanceUploadOptions {
+    pub fn normalize_options(&self, app_data: &AppState) -> NormalizeOptions {
+        NormalizeOptions {
+            check_header: !self.ignore_header.unwrap_or(false),
+            format: self.format,
+            temp_dir: app_data.temp_dir().to_path_buf(),
+            ..Default::default()
         }
-
-        edges.push(edge.normalized())
     }
+}
 
-    edges.sort();
-    edges.dedup();
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InstanceUploadRequest {
+    #[serde(flatten)]
+    pub options: InstanceUploadOptions,
+    pub data: String,
+}
 
-    if check_header && edges.len() != num_edges_per_header {
-        return error_bad_request!(
-            "Number of edges after deduplication does not match the number of edges in the header"
-        );
-    }
+/// Metadata computed on upload
+pub enum UploadMetadata {
+    Full(GraphMetadata),
+    /// The graph exceeds the normaliser's memory bound and is not built; the remaining
+    /// properties are left to `/api/instances/compute_meta`
+    Streamed(StreamedMetadata),
+}
 
-    // find all nodes that are used in the edges
-    let mut used_nodes = vec![0 as Node; num_nodes_per_header];
-    for e in &edges {
-        used_nodes[e.0 as usize] = 1;
-        used_nodes[e.1 as usize] = 1;
-    }
+/// Computes the metadata of a normalised instance. The graph is only built if it has at
+/// most `max_edges_in_memory` edges (cf. [`NormalizeOptions`]); otherwise a single pass
+/// over its edges determines what it can. This blocks and belongs in `spawn_blocking`.
+pub fn compute_normalized_metadata<R: std::io::BufRead>(
+    normalized: &NormalizedInstance,
+    reader: R,
+    max_edges_in_memory: usize,
+) -> HandlerResult<UploadMetadata> {
+    let reader = PaceReader::try_new(reader)?;
 
-    // compute prefix sum to compress nodes ids
-    {
-        let mut sum = 0;
-        for x in used_nodes.iter_mut() {
-            let tmp = *x;
-            *x = sum;
-            sum += tmp;
-        }
+    if normalized.number_of_edges as usize > max_edges_in_memory {
+        let number_of_nodes = reader.number_of_nodes();
+        return Ok(UploadMetadata::Streamed(StreamedMetadata::try_compute(
+            number_of_nodes,
+            reader,
+        )?));
     }
 
-    // rewrite edges with compressed node ids
-    for e in &mut edges {
-        e.0 = used_nodes[e.0 as usize];
-        e.1 = used_nodes[e.1 as usize];
-    }
+    let graph = Graph::try_from_pace_reader(reader)?;
+    Ok(UploadMetadata::Full(GraphMetadata::compute(&graph)))
+}
 
-    let mut normalized_data: Vec<u8> = Vec::with_capacity(data.len());
-    let (num_nodes, num_edges) = pace_writer(&mut normalized_data, PROBLEM_ID, edges.into_iter())?;
+/// Stores the payload of an instance unless it is already known and returns the `did` of
+/// its row in `InstanceData`
+async fn insert_instance_data(
+    app_data: &AppState,
+    tx: &mut DbTransaction<'_>,
+    hash: &str,
+    encoding: BlobEncoding,
+    data: BlobSource<'_>,
+) -> HandlerResult<u64> {
+    // data without an instance may remain from an earlier upload; reuse it in that case.
+    // The check bypasses the transaction, whose snapshot may predate the payload.
+    let stored =
+        sqlx::query_scalar::<_, i64>(r#"SELECT COUNT(*) FROM InstanceData WHERE hash = UNHEX(?)"#)
+            .bind(hash)
+            .fetch_one(app_data.db())
+            .await?
+            > 0;
 
-    let normalized_data = String::from_utf8(normalized_data)?;
-    let hash = {
-        let mut hasher = Sha1::new();
-        hasher.update(normalized_data.as_bytes());
-        format!("{:x}", hasher.finalize())
-    };
-    Ok((num_nodes, num_edges, hash, normalized_data))
+    // should the transaction not commit, the payload is eventually collected
+    if !stored {
+        put_pending_blob(app_data, BlobKind::Instance, hash, encoding, data).await?;
+    }
+
+    Ok(sqlx::query(
+        r#"INSERT INTO InstanceData (hash, encoding) VALUES (UNHEX(?), ?) ON DUPLICATE KEY UPDATE did = LAST_INSERT_ID(did)"#,
+    )
+    .bind(hash)
+    .bind(encoding.id())
+    .execute(&mut **tx)
+    .await?
+    .last_insert_id())
 }
 
-pub async fn instance_upload_handler(
-    State(data): State<Arc<AppState>>,
-    Json(body): Json<InstanceUploadRequest>,
-) -> HandlerResult<impl IntoResponse> {
-    let (num_nodes, num_edges, hash, normalized_data) =
-        normalize_dimacs(&body.data, !body.ignore_header.unwrap_or(false))?;
+/// Stores an uploaded instance unless its normalised form is already known. `data` is the
+/// normalised DIMACS text in `encoding` and `metadata` was obtained by
+/// [`compute_normalized_metadata`].
+pub async fn store_instance(
+    app_data: &AppState,
+    options: InstanceUploadOptions,
+    normalized: &NormalizedInstance,
+    metadata: UploadMetadata,
+    encoding: BlobEncoding,
+    data: BlobSource<'_>,
+) -> HandlerResult<serde_json::Value> {
+    // the very same (normalised) instance is already stored
+    let existing = sqlx::query_scalar::<_, i32>(
+        r#"SELECT i.iid FROM Instance i JOIN InstanceData d ON i.data_did = d.did WHERE d.hash = UNHEX(?) ORDER BY i.iid LIMIT 1"#,
+    )
+    .bind(&normalized.hash)
+    .fetch_optional(app_data.db())
+    .await?;
+
+    if let Some(instance_id) = existing {
+        debug!("Upload is a duplicate of instance {instance_id}");
+        return Ok(serde_json::json!({"status": "duplicate", "instance_id": instance_id}));
+    }
+
+    let likely_duplicate_of = match &metadata {
+        UploadMetadata::Full(metadata) => {
+            sqlx::query_scalar::<_, i32>(
+                r#"SELECT iid FROM Instance WHERE fingerprint = ? ORDER BY iid"#,
+            )
+            .bind(&metadata.fingerprint[..])
+            .fetch_all(app_data.db())
+            .await?
+        }
+        UploadMetadata::Streamed(_) => Vec::new(),
+    };
 
     // we need to insert two rows and use a transaction for that
-    let mut tx = data.db().begin().await?;
+    let mut tx = app_data.db().begin().await?;
 
-    let data_did = sqlx::query(r#"INSERT INTO InstanceData (hash, data) VALUES (UNHEX(?), ?)"#)
-        .bind(&hash)
-        .bind(&normalized_data)
-        .execute(&mut *tx)
-        .await?
-        .last_insert_id();
+    let data_did =
+        insert_instance_data(app_data, &mut tx, &normalized.hash, encoding, data).await?;
+
+    // the insert locked the data row, so concurrent uploads of the same instance wait here
+    // until this one commits; the locking read then sees the instance it created
+    let existing = sqlx::query_scalar::<_, i32>(
+        r#"SELECT iid FROM Instance WHERE data_did = ? ORDER BY iid LIMIT 1 FOR UPDATE"#,
+    )
+    .bind(data_did)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(instance_id) = existing {
+        debug!("Upload is a duplicate of instance {instance_id} stored meanwhile");
+        return Ok(serde_json::json!({"status": "duplicate", "instance_id": instance_id}));
+    }
 
     // create instance entry
     let instance_id = sqlx::query(r#"INSERT INTO Instance (data_did,nodes,edges,name,description,submitted_by) VALUES (?, ?, ?, ?, ?, ?)"#)
         .bind(data_did)
-        .bind(num_nodes)
-        .bind(num_edges)
-        .bind(body.name)
-        .bind(body.description)
-        .bind(body.submitted_by)
+        .bind(normalized.number_of_nodes)
+        .bind(normalized.number_of_edges)
+        .bind(options.name)
+        .bind(options.description)
+        .bind(options.submitted_by)
         .execute(&mut *tx)
         .await
         ?.last_insert_id();
 
-    for tag in body.tags.as_ref().unwrap_or(&Vec::new()) {
+    match &metadata {
+        UploadMetadata::Full(metadata) => {
+            update_instance_metadata(&mut *tx, instance_id as u32, metadata).await?
+        }
+        UploadMetadata::Streamed(metadata) => {
+            update_instance_streamed_metadata(&mut *tx, instance_id as u32, metadata).await?
+        }
+    }
+
+    for tag in options.tags.as_ref().unwrap_or(&Vec::new()) {
         sqlx::query(r#"INSERT INTO InstanceTag (instance_iid,tag_tid) VALUES (?, (SELECT tid FROM Tag WHERE name=? LIMIT 1))"#)
             .bind(instance_id)
             .bind(tag)
@@ -120,6 +202,127 @@ pub async fn instance_upload_handler(
 
     tx.commit().await?;
 
-    let note_response = serde_json::json!({"status": "success", "instance_id": instance_id});
-    Ok(Json(note_response))
+    let mut note_response = serde_json::json!({"status": "success", "instance_id": instance_id});
+    if !likely_duplicate_of.is_empty() {
+        debug!("Instance {instance_id} is a likely duplicate of {likely_duplicate_of:?}");
+        note_response["likely_duplicate_of"] = serde_json::json!(likely_duplicate_of);
+    }
+
+    Ok(note_response)
+}
+
+pub async fn instance_upload_handler(
+    State(data): State<Arc<AppState>>,
+    Json(body): Json<InstanceUploadRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let InstanceUploadRequest {
+        options,
+        data: input,
+    } = body;
+    let normalize_options = options.normalize_options(&data);
+    let encode = !data.blob_store().prefers_plain_instances();
+
+    // normalising, computing the metadata and encoding large graphs takes a while and
+    // must not block the runtime
+    let (normalized, metadata, encoding, encoded_data) = tokio::task::spawn_blocking(move || {
+        let mut normalized_data = Vec::with_capacity(input.len());
+        let normalized =
+            normalize_input(input.as_bytes(), &mut normalized_data, &normalize_options)?;
+        drop(input);
+
+        let metadata = compute_normalized_metadata(
+            &normalized,
+            &normalized_data[..],
+            normalize_options.max_edges_in_memory,
+        )?;
+
+        let (encoding, encoded_data) = if encode {
+            encode_instance(&normalized_data)?
+        } else {
+            (BlobEncoding::Plain, normalized_data)
+        };
+
+        Ok::<_, AppError>((normalized, metadata, encoding, encoded_data))
+    })
+    .await??;
+
+    Ok(Json(
+        store_instance(
+            &data,
+            options,
+            &normalized,
+            metadata,
+            encoding,
+            BlobSource::Memory(&encoded_data),
+        )
+        .await?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::app_state::DbPool;
+
+    async fn upload(pool: &DbPool, data: &str) -> serde_json::Value {
+        let response = super::instance_upload_handler(
+            State(Arc::new(AppState::new(pool.clone()))),
+            Json(InstanceUploadRequest {
+                options: InstanceUploadOptions {
+                    name: Some("test".into()),
+                    ..Default::default()
+                },
+                data: data.into(),
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert!(response.status().is_success());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn duplicate_upload(pool: DbPool) -> sqlx::Result<()> {
+        let first = upload(&pool, "p ds 4 3\n1 2\n2 3\n3 4\n").await;
+        assert_eq!(first["status"], "success");
+        assert!(first.get("likely_duplicate_of").is_none());
+
+        // same graph after normalisation (reordered edges, isolated node)
+        let second = upload(&pool, "p ds 5 3\n3 4\n1 2\n2 3\n").await;
+        assert_eq!(second["status"], "duplicate");
+        assert_eq!(second["instance_id"], first["instance_id"]);
+
+        // relabeled copy
+        let third = upload(&pool, "p ds 4 3\n2 1\n1 3\n3 4\n").await;
+        assert_eq!(third["status"], "success");
+        assert_eq!(
+            third["likely_duplicate_of"],
+            serde_json::json!([first["instance_id"]])
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn concurrent_duplicate_upload(pool: DbPool) -> sqlx::Result<()> {
+        let data = "p ds 4 3\n1 2\n2 3\n3 4\n";
+        let (first, second) = tokio::join!(upload(&pool, data), upload(&pool, data));
+
+        let mut statuses = [&first["status"], &second["status"]];
+        statuses.sort_by_key(|s| s.to_string());
+        assert_eq!(statuses, ["duplicate", "success"]);
+        assert_eq!(first["instance_id"], second["instance_id"]);
+
+        let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM Instance")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count, 1);
+
+        Ok(())
+    }
 }
@@ -1,14 +1,13 @@
 pub mod status;
 pub use status::status_handler;
 
-#[cfg(feature = "admin-api")]
 pub mod instance_upload;
-#[cfg(feature = "admin-api")]
 pub use instance_upload::instance_upload_handler;
 
-#[cfg(feature = "admin-api")]
+pub mod instance_stream_upload;
+pub use instance_stream_upload::instance_stream_upload_handler;
+
 pub mod instance_delete;
-#[cfg(feature = "admin-api")]
 pub use instance_delete::instance_delete_handler;
 
 pub mod instance_list;
@@ -17,19 +16,19 @@ pub use instance_list::{instance_list_download_handler, instance_list_handler};
 pub mod instance_download;
 pub use instance_download::instance_download_handler;
 
-#[cfg(feature = "admin-api")]
+pub mod instance_archive;
+pub use instance_archive::instance_archive_handler;
+
 pub mod instance_update_meta;
-#[cfg(feature = "admin-api")]
 pub use instance_update_meta::instance_update_meta_handler;
 
-#[cfg(feature = "admin-api")]
+pub mod instance_compute_meta;
+pub use instance_compute_meta::instance_compute_meta_handler;
+
 pub mod tag_create;
-#[cfg(feature = "admin-api")]
 pub use tag_create::tag_create_handler;
 
-#[cfg(feature = "admin-api")]
 pub mod debug_restart;
-#[cfg(feature = "admin-api")]
 pub use debug_restart::debug_restart_handler;
 
 pub mod tag_list;
@@ -38,23 +37,53 @@ pub use tag_list::tag_list_handler;
 pub mod solution_upload;
 pub use solution_upload::solution_upload_handler;
 
+pub mod solution_batch_upload;
+pub use solution_batch_upload::solution_batch_upload_handler;
+
 pub mod solution_hash_list;
 pub use solution_hash_list::solution_hash_list_handler;
 
 pub mod solver_run_list;
 pub use solver_run_list::solver_run_list_handler;
 
+pub mod solver_register;
+pub use solver_register::solver_register_handler;
+
 pub mod solver_run_annotate;
 pub use solver_run_annotate::solver_run_annotate_handler;
 
 pub mod solver_run_performance;
 pub use solver_run_performance::solver_run_performance_handler;
 
+pub mod solver_run_compare;
+pub use solver_run_compare::solver_run_compare_handler;
+
+pub mod solver_run_archive;
+pub use solver_run_archive::solver_run_archive_handler;
+
+pub mod solver_run_lifecycle;
+pub use solver_run_lifecycle::{
+    solver_run_finish_handler, solver_run_heartbeat_handler, solver_run_start_handler,
+};
+
+pub mod solver_run_metadata;
+
+pub mod solver_run_schedule;
+pub use solver_run_schedule::{solver_run_lease_handler, solver_run_schedule_handler};
+
 pub mod solution_download;
 pub use solution_download::solution_download_handler;
 
+pub mod solution_best;
+pub use solution_best::solution_best_handler;
+
+pub mod solution_diff;
+pub use solution_diff::solution_diff_handler;
+
 pub mod instance_solutions;
 pub use instance_solutions::instance_solutions_handler;
 
+pub mod instance_difficulty;
+
 // imports used by pretty much every handler
 mod common;
@@ -0,0 +1,221 @@
+use axum::{
+    body::Bytes,
+    extract::rejection::BytesRejection,
+    http::{header, HeaderMap},
+};
+use futures::{stream, StreamExt};
+use sqlx::Connection;
+use tracing::{debug, warn};
+
+use super::{
+    common::*,
+    solution_upload::{
+        invalid_solution_json, prepare_upload, store_upload, success_response, PreparedUpload,
+        SolutionUploadRequest,
+    },
+};
+
+/// Number of uploads verified at the same time
+const VERIFY_CONCURRENCY: usize = 8;
+
+/// Number of uploads written within a single transaction
+const INSERT_CHUNK_SIZE: usize = 256;
+
+fn error_json(err: impl Into<AppError>) -> serde_json::Value {
+    err.into().to_json()
+}
+
+/// Parses either a JSON array or newline-delimited JSON; items that cannot be parsed
+/// are reported individually instead of rejecting the whole batch.
+fn parse_batch(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> HandlerResult<Vec<Result<SolutionUploadRequest, serde_json::Value>>> {
+    let is_ndjson = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("ndjson"))
+        || body.iter().find(|c| !c.is_ascii_whitespace()) != Some(&b'[');
+
+    let parse = |value: serde_json::Value| {
+        serde_json::from_value::<SolutionUploadRequest>(value).map_err(error_json)
+    };
+
+    if is_ndjson {
+        Ok(body
+            .split(|&c| c == b'\n')
+            .filter(|line| line.iter().any(|c| !c.is_ascii_whitespace()))
+            .map(|line| {
+                serde_json::from_slice::<serde_json::Value>(line)
+                    .map_err(error_json)
+                    .and_then(parse)
+            })
+            .collect())
+    } else {
+        let items: Vec<serde_json::Value> = serde_json::from_slice(body)?;
+        Ok(items.into_iter().map(parse).collect())
+    }
+}
+
+pub async fn solution_batch_upload_handler(
+    State(app_data): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> HandlerResult<impl IntoResponse> {
+    let body = body?;
+    let items = parse_batch(&headers, &body)?;
+    debug!("Handling batch upload of {} solutions", items.len());
+
+    // verification reads the instance and checks the solution; run it on several tasks
+    let prepared: Vec<_> = stream::iter(items)
+        .map(|item| {
+            let app_data = app_data.clone();
+            tokio::spawn(async move {
+                let mut request = item?;
+                let prepared = prepare_upload(&app_data, &mut request)
+                    .await
+                    .map_err(error_json)?;
+                Ok::<_, serde_json::Value>((request, prepared))
+            })
+        })
+        .buffered(VERIFY_CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut results = Vec::with_capacity(prepared.len());
+    let mut prepared = prepared.into_iter().peekable();
+
+    while prepared.peek().is_some() {
+        let mut tx = app_data.db().begin().await?;
+
+        for item in prepared.by_ref().take(INSERT_CHUNK_SIZE) {
+            let (request, prepared) = match item {
+                Ok(Ok(item)) => item,
+                Ok(Err(e)) => {
+                    results.push(e);
+                    continue;
+                }
+                // earlier chunks are committed already, so only this item may fail
+                Err(e) => {
+                    warn!("Verification task of batch upload failed: {e}");
+                    results.push(error_json(anyhow::anyhow!("Verification failed")));
+                    continue;
+                }
+            };
+
+            if let PreparedUpload::Rejected(report) = &prepared {
+                results.push(invalid_solution_json(report));
+                continue;
+            }
+
+            // each item gets a savepoint, so a failing item does not take down the chunk
+            let mut item_tx = tx.begin().await?;
+            match store_upload(&app_data, &mut item_tx, &request, prepared).await {
+                Ok(stored) => {
+                    if request.dry_run {
+                        item_tx.rollback().await?;
+                    } else {
+                        item_tx.commit().await?;
+                    }
+                    results.push(success_response(&stored));
+                }
+                Err(e) => {
+                    warn!("Failed to store solution of batch upload: {e}");
+                    item_tx.rollback().await?;
+                    results.push(error_json(e));
+                }
+            }
+        }
+
+        tx.commit().await?;
+    }
+
+    Ok(Json(serde_json::json!({
+        "status": "ok",
+        "results": results,
+    })))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::app_state::DbPool;
+
+    async fn upload(pool: DbPool, content_type: &str, body: String) -> Vec<serde_json::Value> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+
+        let response = super::solution_batch_upload_handler(
+            State(Arc::new(AppState::new(pool))),
+            headers,
+            Ok(Bytes::from(body)),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert!(response.status().is_success());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["results"].as_array().unwrap().clone()
+    }
+
+    fn request(instance_id: u32, result: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "instance_id": instance_id,
+            "run_uuid": uuid::Uuid::new_v4(),
+            "solver_uuid": uuid::Uuid::new_v4(),
+            "seconds_computed": 1.0,
+            "result": result,
+        })
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn batch_json_array(pool: DbPool) -> sqlx::Result<()> {
+        let items = serde_json::json!([
+            request(2, serde_json::json!({"status": "valid", "data": [2]})),
+            request(2, serde_json::json!({"status": "valid", "data": [1]})),
+            request(2, serde_json::json!({"status": "timeout"})),
+        ]);
+
+        let results = upload(pool.clone(), "application/json", items.to_string()).await;
+
+        let status: Vec<_> = results.iter().map(|r| r["status"].as_str()).collect();
+        assert_eq!(
+            status,
+            vec![Some("success"), Some("error"), Some("success")]
+        );
+        assert_eq!(results[1]["code"], "invalid_solution");
+        assert_eq!(results[1]["details"]["undominated"], serde_json::json!([3]));
+
+        let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM Solution")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count, 2);
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn batch_ndjson(pool: DbPool) -> sqlx::Result<()> {
+        let body = [
+            request(2, serde_json::json!({"status": "valid", "data": [2]})).to_string(),
+            "{not json".to_string(),
+            request(1234, serde_json::json!({"status": "valid", "data": [2]})).to_string(),
+        ]
+        .join("\n");
+
+        let results = upload(pool, "application/x-ndjson", body).await;
+
+        let status: Vec<_> = results.iter().map(|r| r["status"].as_str()).collect();
+        assert_eq!(status, vec![Some("success"), Some("error"), Some("error")]);
+
+        let codes: Vec<_> = results.iter().map(|r| r["code"].as_str()).collect();
+        assert_eq!(codes, vec![None, Some("bad_request"), Some("not_found")]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use axum::response::Response;
+
+use super::common::*;
+use super::solution_download::{
+    load_solution_data, solution_response, ResponseFormat, SolutionModel,
+};
+
+#[derive(Clone, Deserialize, Serialize, Debug, Default)]
+pub struct BestSolutionOptions {
+    iid: u32,
+
+    #[serde(default)]
+    format: ResponseFormat,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct PublicationModel {
+    publish_best_solution: Option<bool>,
+    best_score: Option<u32>,
+}
+
+/// Fails unless the solutions of instance `iid` are published; returns its best score
+pub(super) async fn check_solutions_published(
+    iid: u32,
+    app_data: &AppState,
+) -> HandlerResult<Option<u32>> {
+    let Some(instance) = sqlx::query_as::<_, PublicationModel>(
+        r#"SELECT publish_best_solution, best_score FROM Instance WHERE iid = ?"#,
+    )
+    .bind(iid)
+    .fetch_optional(app_data.db())
+    .await?
+    else {
+        return error_not_found!("Unknown instance");
+    };
+
+    // the per-instance setting takes precedence over the server-wide default
+    if !instance
+        .publish_best_solution
+        .unwrap_or(app_data.publish_best_solutions())
+    {
+        return error_forbidden!("Best solutions of this instance are not published");
+    }
+
+    Ok(instance.best_score)
+}
+
+async fn fetch_best_solution(
+    opts: &BestSolutionOptions,
+    app_data: &AppState,
+) -> HandlerResult<(u32, SolutionModel)> {
+    let Some(best_score) = check_solutions_published(opts.iid, app_data).await? else {
+        return error_not_found!("Instance has no known solution");
+    };
+
+    // the oldest best solution is the first one to leave the embargo
+    let solution = sqlx::query_as::<_, SolutionModel>(
+        r#"SELECT
+            LOWER(HEX(sd.hash)) AS hash, sd.data, sd.encoding, s.score
+           FROM `Solution` s
+           JOIN SolutionData sd ON s.`solution_hash` = sd.`hash`
+           WHERE s.`instance_iid` = ? AND s.`score` = ?
+             AND s.`created_at` <= NOW() - INTERVAL ? SECOND
+           ORDER BY s.`created_at`, s.`sid`
+           LIMIT 1"#,
+    )
+    .bind(opts.iid)
+    .bind(best_score)
+    .bind(app_data.best_solution_embargo().as_secs())
+    .fetch_optional(app_data.db())
+    .await?;
+
+    let Some(mut solution) = solution else {
+        return error_not_found!("No best solution is available yet");
+    };
+
+    load_solution_data(&mut solution, app_data).await?;
+    Ok((best_score, solution))
+}
+
+/// Returns a solution of the instance whose score matches its best known score, provided
+/// the instance's best solutions are published and the embargo after upload has passed.
+/// The solver and run that produced it are not disclosed.
+pub async fn solution_best_handler(
+    Query(opts): Query<BestSolutionOptions>,
+    State(app_data): State<Arc<AppState>>,
+) -> HandlerResult<Response> {
+    let (score, solution) = fetch_best_solution(&opts, &app_data).await?;
+    let file_name = format!("sol_inst{}_score{score}_best.sol", opts.iid);
+
+    solution_response(opts.format, &file_name, solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::server::app_state::DbPool;
+
+    async fn download(state: AppState) -> HandlerResult<Vec<u32>> {
+        let opts = BestSolutionOptions {
+            iid: 1,
+            format: ResponseFormat::Json,
+        };
+
+        let resp = solution_best_handler(Query(opts), State(Arc::new(state))).await?;
+        let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        Ok(serde_json::from_value(json["solution"].clone()).unwrap())
+    }
+
+    fn status(result: HandlerResult<Vec<u32>>) -> StatusCode {
+        result.unwrap_err().into_response().status()
+    }
+
+    #[sqlx::test(fixtures("instances", "solutions"))]
+    async fn best_solution_policy(db_pool: DbPool) -> sqlx::Result<()> {
+        sqlx::query("UPDATE Instance SET best_score = 1 WHERE iid = 1")
+            .execute(&db_pool)
+            .await?;
+
+        // nothing is published by default
+        let result = download(AppState::new(db_pool.clone())).await;
+        assert_eq!(status(result), StatusCode::FORBIDDEN);
+
+        let published = || AppState::new(db_pool.clone()).with_best_solutions_published(true);
+        assert_eq!(download(published()).await.unwrap(), vec![1, 2, 4]);
+
+        let embargoed = published().with_best_solution_embargo(Duration::from_secs(3600));
+        assert_eq!(status(download(embargoed).await), StatusCode::NOT_FOUND);
+
+        // the instance setting overrides the global one
+        sqlx::query("UPDATE Instance SET publish_best_solution = FALSE WHERE iid = 1")
+            .execute(&db_pool)
+            .await?;
+        assert_eq!(status(download(published()).await), StatusCode::FORBIDDEN);
+
+        sqlx::query("UPDATE Instance SET publish_best_solution = TRUE WHERE iid = 1")
+            .execute(&db_pool)
+            .await?;
+        assert!(download(AppState::new(db_pool.clone())).await.is_ok());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use super::common::*;
+use super::solution_best::check_solutions_published;
+use super::solution_download::{fetch_solution, load_solution_data, SolutionModel};
+use super::solution_upload::read_instance_data;
+use crate::pace::{graph::Node, Solution};
+use crate::server::blob_store::check_hash;
+
+/// A solution is either named by the run that produced it or by its hash in `SolutionData`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SolutionRef {
+    Run { solver: Uuid, run: Uuid },
+    Hash { hash: String },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DiffRequest {
+    iid: u32,
+    first: SolutionRef,
+    second: SolutionRef,
+}
+
+#[derive(Debug, Serialize)]
+struct SolutionSummary {
+    hash: String,
+    score: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct DiffResponse {
+    status: &'static str,
+    iid: u32,
+    first: SolutionSummary,
+    second: SolutionSummary,
+
+    num_common: u32,
+    only_in_first: Vec<Node>,
+    only_in_second: Vec<Node>,
+    dominated_only_by_first: Vec<Node>,
+    dominated_only_by_second: Vec<Node>,
+    jaccard: f64,
+}
+
+/// Only hashes that a solution of instance `iid` refers to can be fetched
+async fn fetch_solution_by_hash(
+    iid: u32,
+    hash: &str,
+    embargo: Duration,
+    app_data: &AppState,
+) -> HandlerResult<SolutionModel> {
+    let hash = hash.to_ascii_lowercase();
+    if let Err(e) = check_hash(&hash) {
+        return error_bad_request!(e);
+    }
+
+    let mut solution = sqlx::query_as::<_, SolutionModel>(
+        r#"SELECT
+            LOWER(HEX(sd.hash)) AS hash, sd.data, sd.encoding, s.score
+           FROM SolutionData sd
+           JOIN `Solution` s ON s.`solution_hash` = sd.`hash`
+           WHERE s.`instance_iid` = ? AND sd.`hash` = UNHEX(?) AND s.`score` IS NOT NULL
+             AND s.`created_at` <= NOW() - INTERVAL ? SECOND
+           LIMIT 1"#,
+    )
+    .bind(iid)
+    .bind(hash)
+    .bind(embargo.as_secs())
+    .fetch_one(app_data.db())
+    .await?;
+
+    load_solution_data(&mut solution, app_data).await?;
+    Ok(solution)
+}
+
+/// Fetches a solution subject to the publication policy and embargo of best solutions,
+/// which only covers solutions attaining the best known score of the instance
+async fn fetch_published_solution(
+    iid: u32,
+    reference: &SolutionRef,
+    best_score: u32,
+    app_data: &AppState,
+) -> HandlerResult<SolutionModel> {
+    let embargo = app_data.best_solution_embargo();
+    let solution = match reference {
+        SolutionRef::Run { solver, run } => {
+            fetch_solution(iid, solver, run, embargo, app_data).await?
+        }
+        SolutionRef::Hash { hash } => fetch_solution_by_hash(iid, hash, embargo, app_data).await?,
+    };
+
+    if solution.score != best_score {
+        return error_not_found!("Only best solutions can be compared");
+    }
+    Ok(solution)
+}
+
+/// Compares two best solutions of an instance: the nodes only one of them contains, the
+/// nodes only those exclusive parts dominate, and the Jaccard similarity of both. The
+/// solutions are disclosed under the same conditions as by the best solution endpoint: the
+/// instance has to publish them and the embargo has to be over. Other solutions, e.g. of
+/// runs that did not reach the best known score, are never disclosed.
+pub async fn solution_diff_handler(
+    State(app_data): State<Arc<AppState>>,
+    Json(request): Json<DiffRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let Some(best_score) = check_solutions_published(request.iid, &app_data).await? else {
+        return error_not_found!("Instance has no known solution");
+    };
+
+    let first =
+        fetch_published_solution(request.iid, &request.first, best_score, &app_data).await?;
+    let second =
+        fetch_published_solution(request.iid, &request.second, best_score, &app_data).await?;
+
+    let graph = read_instance_data(&app_data, request.iid).await?;
+    let comparison = Solution::from_0indexed_vec(first.nodes()?)
+        .compare(&Solution::from_0indexed_vec(second.nodes()?), &graph)?;
+
+    let to_1indexed = |nodes: Vec<Node>| nodes.into_iter().map(|u| u + 1).collect::<Vec<_>>();
+
+    Ok(Json(DiffResponse {
+        status: "ok",
+        iid: request.iid,
+        first: SolutionSummary {
+            hash: first.hash,
+            score: first.score,
+        },
+        second: SolutionSummary {
+            hash: second.hash,
+            score: second.score,
+        },
+        num_common: comparison.num_common,
+        only_in_first: to_1indexed(comparison.only_in_first),
+        only_in_second: to_1indexed(comparison.only_in_second),
+        dominated_only_by_first: to_1indexed(comparison.dominated_only_by_first),
+        dominated_only_by_second: to_1indexed(comparison.dominated_only_by_second),
+        jaccard: comparison.jaccard,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::server::app_state::DbPool;
+
+    const FIRST_HASH: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const SECOND_HASH: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    const WORSE_HASH: &str = "cccccccccccccccccccccccccccccccccccccccc";
+    const HASH_OF_INSTANCE_1: &str = "1d229271928d3f9e2bb0375bd6ce5db6c6d348d9";
+
+    fn run_ref(run: &str) -> SolutionRef {
+        SolutionRef::Run {
+            solver: Uuid::parse_str("00000000-0000-0000-0002-000000000000").unwrap(),
+            run: Uuid::parse_str(run).unwrap(),
+        }
+    }
+
+    fn hash_ref(hash: &str) -> SolutionRef {
+        SolutionRef::Hash {
+            hash: hash.to_string(),
+        }
+    }
+
+    async fn diff_status(state: AppState, first: SolutionRef, second: SolutionRef) -> StatusCode {
+        diff(state, first, second)
+            .await
+            .unwrap_err()
+            .into_response()
+            .status()
+    }
+
+    fn first_ref() -> SolutionRef {
+        run_ref("00000000-0000-0000-0001-000000000000")
+    }
+
+    fn second_ref() -> SolutionRef {
+        run_ref("00000000-0000-0000-0001-000000000001")
+    }
+
+    async fn diff(
+        state: AppState,
+        first: SolutionRef,
+        second: SolutionRef,
+    ) -> HandlerResult<serde_json::Value> {
+        let request = DiffRequest {
+            iid: 2,
+            first,
+            second,
+        };
+
+        let resp = solution_diff_handler(State(Arc::new(state)), Json(request))
+            .await?
+            .into_response();
+        let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+        Ok(serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[sqlx::test(fixtures("instances", "solutions"))]
+    async fn solution_diff(db_pool: DbPool) -> sqlx::Result<()> {
+        // instance 2 is the path 1-2-3; two runs found the best solutions {1} and {2},
+        // another one found {1, 3}
+        sqlx::query(&format!(
+            r#"INSERT INTO SolutionData (hash, data) VALUES
+                (UNHEX('{FIRST_HASH}'), "[0]"), (UNHEX('{SECOND_HASH}'), "[1]"), (UNHEX('{WORSE_HASH}'), "[0,2]")"#
+        ))
+        .execute(&db_pool)
+        .await?;
+        sqlx::query(&format!(
+            r#"INSERT INTO Solution (sr_uuid, instance_iid, solution_hash, error_code, score, seconds_computed) VALUES
+                (UNHEX('00000000000000000001000000000000'), 2, UNHEX('{FIRST_HASH}'), 1, 1, 1.0),
+                (UNHEX('00000000000000000001000000000001'), 2, UNHEX('{SECOND_HASH}'), 1, 1, 1.0),
+                (UNHEX('00000000000000000001000000000002'), 2, UNHEX('{WORSE_HASH}'), 1, 2, 1.0)"#
+        ))
+        .execute(&db_pool)
+        .await?;
+        sqlx::query("UPDATE Instance SET best_score = 1 WHERE iid = 2")
+            .execute(&db_pool)
+            .await?;
+
+        let published = || AppState::new(db_pool.clone()).with_best_solutions_published(true);
+
+        let json = diff(published(), first_ref(), second_ref()).await.unwrap();
+        assert_eq!(json["first"]["hash"], FIRST_HASH);
+        assert_eq!(json["second"]["score"], 1);
+        assert_eq!(json["num_common"], 0);
+        assert_eq!(json["only_in_first"], serde_json::json!([1]));
+        assert_eq!(json["only_in_second"], serde_json::json!([2]));
+        assert_eq!(json["dominated_only_by_first"], serde_json::json!([]));
+        assert_eq!(json["dominated_only_by_second"], serde_json::json!([3]));
+        assert_eq!(json["jaccard"], 0.0);
+
+        let json = diff(published(), first_ref(), first_ref()).await.unwrap();
+        assert_eq!(json["jaccard"], 1.0);
+
+        // hashes may be given in either case
+        let json = diff(
+            published(),
+            hash_ref(FIRST_HASH),
+            hash_ref(&SECOND_HASH.to_uppercase()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(json["second"]["hash"], SECOND_HASH);
+        assert_eq!(json["only_in_second"], serde_json::json!([2]));
+
+        // solutions are only disclosed if the instance publishes them ...
+        let status = diff_status(AppState::new(db_pool.clone()), first_ref(), second_ref()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // ... after the embargo ...
+        let embargoed = || published().with_best_solution_embargo(Duration::from_secs(3600));
+        let status = diff_status(embargoed(), first_ref(), second_ref()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let status = diff_status(embargoed(), hash_ref(FIRST_HASH), first_ref()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // ... and if they attain the best known score
+        let worse_run = SolutionRef::Run {
+            solver: Uuid::parse_str("00000000-0000-0000-0002-000000000001").unwrap(),
+            run: Uuid::parse_str("00000000-0000-0000-0001-000000000002").unwrap(),
+        };
+        let status = diff_status(published(), first_ref(), worse_run).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let status = diff_status(published(), first_ref(), hash_ref(WORSE_HASH)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // runs of other solvers and hashes without a solution of this instance cannot be
+        // referenced
+        let other_solver = run_ref("00000000-0000-0000-0001-000000000002");
+        let status = diff_status(published(), first_ref(), other_solver).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let status = diff_status(published(), first_ref(), hash_ref(HASH_OF_INSTANCE_1)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let unknown = hash_ref("dddddddddddddddddddddddddddddddddddddddd");
+        let status = diff_status(published(), first_ref(), unknown).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let status = diff_status(published(), first_ref(), hash_ref("../x")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+}
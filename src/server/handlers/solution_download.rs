@@ -1,12 +1,15 @@
+use std::time::Duration;
+
 use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
 use axum::http::HeaderValue;
 use axum::response::{IntoResponse, Response};
 use uuid::Uuid;
 
 use super::common::*;
+use crate::pace::codec::{decode_solution, BlobEncoding};
 use crate::pace::graph::Node;
 use crate::pace::Solution;
-use crate::server::app_state::DbPool;
+use crate::server::blob_store::{load_blob, BlobKind};
 
 #[derive(Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Default, Debug)]
 #[serde(rename_all = "lowercase")]
@@ -29,47 +32,90 @@ pub struct FilterOptions {
 
 #[derive(Debug, sqlx::FromRow)]
 #[allow(non_snake_case)]
-struct SolutionModel {
-    score: u32,
-    data: Vec<u8>,
+pub(super) struct SolutionModel {
+    pub(super) score: u32,
+    pub(super) hash: String,
+    data: Option<Vec<u8>>,
+    encoding: u8,
+}
+
+impl SolutionModel {
+    pub(super) fn nodes(&self) -> HandlerResult<Vec<Node>> {
+        Ok(decode_solution(
+            BlobEncoding::try_from(self.encoding)?,
+            self.data.as_deref().unwrap_or_default(),
+        )?)
+    }
 }
 
-async fn fetch_solution(opts: &FilterOptions, db_pool: &DbPool) -> HandlerResult<SolutionModel> {
-    let run = opts.run.simple().to_string();
-    let solver = opts.solver.simple().to_string();
+/// Fetches the solution that `run` of `solver` produced for instance `iid`, unless it was
+/// uploaded less than `embargo` ago
+pub(super) async fn fetch_solution(
+    iid: u32,
+    solver: &Uuid,
+    run: &Uuid,
+    embargo: Duration,
+    app_data: &AppState,
+) -> HandlerResult<SolutionModel> {
+    let run = run.simple().to_string();
+    let solver = solver.simple().to_string();
 
     // attempt to fetch instance from database
-    Ok(sqlx::query_as::<_, SolutionModel>(
+    let mut solution = sqlx::query_as::<_, SolutionModel>(
         r#"SELECT 
-            sd.data, s.score
+            LOWER(HEX(sd.hash)) AS hash, sd.data, sd.encoding, s.score
            FROM SolutionData sd
            JOIN `Solution` s ON s.`solution_hash` = sd.`hash`
            JOIN `SolverRun` sr ON sr.`run_uuid` = s.`sr_uuid`
            WHERE s.`instance_iid` = ? AND sr.run_uuid = UNHEX(?) AND sr.solver_uuid = UNHEX(?) AND s.`score` IS NOT NULL
+             AND s.`created_at` <= NOW() - INTERVAL ? SECOND
            LIMIT 1"#,
     )
-    .bind(opts.iid)
+    .bind(iid)
     .bind(run)
     .bind(solver)
-    .fetch_one(db_pool)
-    .await?)
+    .bind(embargo.as_secs())
+    .fetch_one(app_data.db())
+    .await?;
+
+    load_solution_data(&mut solution, app_data).await?;
+    Ok(solution)
 }
 
-fn dimacs_response(
-    opts: &FilterOptions,
-    solution: SolutionModel,
-) -> HandlerResult<impl IntoResponse> {
-    let header_line = format!(
-        "attachment; filename=\"sol_inst{}_score{}_run{}.sol\"",
-        opts.iid, solution.score, opts.run
-    );
+/// Replaces the inline payload of `solution` by the one kept in the blob store, if any
+pub(super) async fn load_solution_data(
+    solution: &mut SolutionModel,
+    app_data: &AppState,
+) -> HandlerResult<()> {
+    let inline = solution.data.take();
+    solution.data = load_blob(
+        app_data.blob_store(),
+        BlobKind::Solution,
+        &solution.hash,
+        inline,
+    )
+    .await?;
+
+    if solution.data.is_none() {
+        return error_not_found!("Solution data is missing");
+    }
+
+    Ok(())
+}
+
+/// Name under which the solution of `run` for instance `iid` is delivered
+pub(super) fn solution_file_name(iid: u32, score: u32, run: &Uuid) -> String {
+    format!("sol_inst{iid}_score{score}_run{run}.sol")
+}
+
+fn dimacs_response(file_name: &str, solution: SolutionModel) -> HandlerResult<impl IntoResponse> {
+    let header_line = format!("attachment; filename=\"{file_name}\"");
 
     let content_disposition = HeaderValue::from_str(&header_line)?;
 
-    let domset: Vec<Node> = serde_json::from_slice(solution.data.as_slice())?;
-    let pace_solution = Solution::from_0indexed_vec(domset);
+    let pace_solution = Solution::from_0indexed_vec(solution.nodes()?);
 
-    let mut out_buffer: Vec<u8> = Vec::with_capacity(solution.data.len() + 100);
+    let mut out_buffer: Vec<u8> = Vec::with_capacity(solution.score as usize * 8 + 100);
     pace_solution.write(&mut out_buffer)?;
 
     Ok((
@@ -87,8 +133,8 @@ struct JsonResponse {
     solution: Vec<Node>,
 }
 
-fn json_response(buffer: Vec<u8>) -> HandlerResult<impl IntoResponse> {
-    let mut solution: Vec<Node> = serde_json::from_slice(buffer.as_slice())?;
+fn json_response(solution: SolutionModel) -> HandlerResult<impl IntoResponse> {
+    let mut solution = solution.nodes()?;
     for x in solution.iter_mut() {
         *x += 1;
     }
@@ -99,16 +145,27 @@ fn json_response(buffer: Vec<u8>) -> HandlerResult<impl IntoResponse> {
     }))
 }
 
+/// Delivers `solution` in the requested format; `file_name` is used for DIMACS downloads
+pub(super) fn solution_response(
+    format: ResponseFormat,
+    file_name: &str,
+    solution: SolutionModel,
+) -> HandlerResult<Response> {
+    Ok(match format {
+        ResponseFormat::Dimacs => dimacs_response(file_name, solution)?.into_response(),
+        ResponseFormat::Json => json_response(solution)?.into_response(),
+    })
+}
+
 pub async fn solution_download_handler(
     Query(opts): Query<FilterOptions>,
     State(app_data): State<Arc<AppState>>,
 ) -> HandlerResult<Response> {
-    let solution = fetch_solution(&opts, app_data.db()).await?;
+    let solution =
+        fetch_solution(opts.iid, &opts.solver, &opts.run, Duration::ZERO, &app_data).await?;
+    let file_name = solution_file_name(opts.iid, solution.score, &opts.run);
 
-    Ok(match opts.format {
-        ResponseFormat::Dimacs => dimacs_response(&opts, solution)?.into_response(),
-        ResponseFormat::Json => json_response(solution.data)?.into_response(),
-    })
+    solution_response(opts.format, &file_name, solution)
 }
 
 #[cfg(test)]
@@ -116,6 +173,7 @@ mod tests {
     use http_body_util::BodyExt;
 
     use super::*;
+    use crate::server::app_state::DbPool;
 
     #[sqlx::test(fixtures("instances", "solutions"))]
     async fn solution_download_handler_json(db_pool: DbPool) -> sqlx::Result<()> {
@@ -1,4 +1,3 @@
-use axum::extract::Path;
 use serde_json::json;
 use tracing::debug;
 use uuid::Uuid;
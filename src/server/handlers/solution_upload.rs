@@ -1,12 +1,42 @@
+use axum::{http::StatusCode, response::Response};
 use tracing::{debug, error};
 
-use super::common::*;
+use super::{
+    common::*,
+    instance_difficulty::mark_difficulty_outdated,
+    solver_register::authorize_solver,
+    solver_run_metadata::{store_run_metadata, RunMetadata},
+    solver_run_schedule::complete_scheduled_instance,
+};
 
 use crate::{
-    pace::{graph::*, instance_reader::PaceReader, Solution},
-    server::app_state::{DbPool, DbTransaction},
+    pace::{
+        codec::{decode_instance, encode_solution, BlobEncoding},
+        graph::*,
+        instance_reader::PaceReader,
+        DomsetReport, Solution,
+    },
+    server::{
+        app_state::DbTransaction,
+        blob_gc::{put_pending_blob, release_blob},
+        blob_store::{load_blob, BlobKind, BlobSource},
+    },
 };
 
+/// Maximum number of undominated/redundant nodes listed in a verification report
+const MAX_REPORTED_NODES: usize = 100;
+
+/// Longer stderr outputs are cut, keeping their end
+const MAX_STDERR_TAIL_BYTES: usize = 4096;
+
+/// Pseudo-solver credited with the pruned versions of uploaded solutions
+pub const PRUNING_SOLVER_UUID: uuid::Uuid =
+    uuid::Uuid::from_u128(0x5e7e_0000_0000_0000_0000_0000_0000_0001);
+
+/// The single run of [`PRUNING_SOLVER_UUID`]; it holds the smallest pruned solution per instance
+pub const PRUNING_RUN_UUID: uuid::Uuid =
+    uuid::Uuid::from_u128(0x5e7e_0000_0000_0000_0001_0000_0000_0001);
+
 #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(tag = "status", rename_all = "lowercase")]
 pub enum SolverResult {
@@ -71,6 +101,40 @@ impl SolverResult {
     }
 }
 
+/// Resource usage and diagnostics reported by the runner; all fields are optional
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct ResourceUsage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_memory_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_seconds: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wall_seconds: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the solver
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    /// Last part of the solver's stderr output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_tail: Option<String>,
+}
+
+impl ResourceUsage {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Returns the last (at most) [`MAX_STDERR_TAIL_BYTES`] bytes, cut at a character boundary
+fn stderr_tail(stderr: &str) -> &str {
+    let mut start = stderr.len().saturating_sub(MAX_STDERR_TAIL_BYTES);
+    while !stderr.is_char_boundary(start) {
+        start += 1;
+    }
+    &stderr[start..]
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SolutionUploadRequest {
     pub instance_id: u32,
@@ -78,66 +142,132 @@ pub struct SolutionUploadRequest {
     pub run_uuid: uuid::Uuid,
     pub solver_uuid: Option<uuid::Uuid>,
 
+    /// Key obtained when registering the solver; required for registered solvers
+    #[serde(default)]
+    pub solver_key: Option<String>,
+
     #[serde(default)]
     pub seconds_computed: Option<f64>,
     pub result: SolverResult,
 
     #[serde(default)]
     pub dry_run: bool,
+
+    /// Additionally store a minimal version of a valid solution, credited to the server's
+    /// pruning pseudo-solver; requires the solution data rather than a cached hash
+    #[serde(default)]
+    pub prune_redundant: bool,
+
+    #[serde(flatten)]
+    pub resources: ResourceUsage,
+
+    /// Setup of the run; only needs to be sent with one of its uploads
+    #[serde(default)]
+    pub run_metadata: Option<RunMetadata>,
 }
 
-async fn read_instance_data(db: &DbPool, instance_id: u32) -> HandlerResult<(NumNodes, Vec<Edge>)> {
+pub async fn read_instance_data(app_data: &AppState, instance_id: u32) -> HandlerResult<Graph> {
     struct Record {
         nodes: u32,
+        hash: String,
         data: Option<Vec<u8>>,
+        encoding: u8,
     }
 
-    let record = sqlx::query_as!(Record, r"SELECT i.nodes, id.data FROM Instance i JOIN InstanceData id ON id.did = i.data_did WHERE i.iid = ? LIMIT 1", instance_id)
-            .fetch_one(db)
+    let record = sqlx::query_as!(Record, r#"SELECT i.nodes, LOWER(HEX(id.hash)) AS "hash!", id.data, id.encoding FROM Instance i JOIN InstanceData id ON id.did = i.data_did WHERE i.iid = ? LIMIT 1"#, instance_id)
+            .fetch_one(app_data.db())
             .await
             ?;
 
-    let instance_reader = PaceReader::try_new(record.data.as_ref().unwrap().as_slice())?;
+    let data = load_blob(
+        app_data.blob_store(),
+        BlobKind::Instance,
+        &record.hash,
+        record.data,
+    )
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("Data of instance {instance_id} is missing"))?;
+    let data = decode_instance(BlobEncoding::try_from(record.encoding)?, &data)?;
+    let instance_reader = PaceReader::try_new(data.as_ref())?;
 
     if instance_reader.number_of_nodes() != record.nodes {
-        return error_bad_request!("Instance node count mismatch");
+        return Err(anyhow::anyhow!("Instance node count mismatch").into());
     }
 
-    let mut edges = Vec::with_capacity(instance_reader.number_of_edges() as usize);
-    for edge in instance_reader {
-        edges.push(edge?.normalized());
-    }
-
-    Ok((record.nodes as NumNodes, edges))
+    Ok(Graph::try_from_pace_reader(instance_reader)?)
 }
 
 async fn verify_solution(
-    db: &DbPool,
+    app_data: &AppState,
     instance_id: u32,
     solution: Vec<Node>,
-) -> HandlerResult<Solution> {
-    let (nodes, edges) = read_instance_data(db, instance_id).await?;
+) -> HandlerResult<(Solution, DomsetReport, Graph)> {
+    let graph = read_instance_data(app_data, instance_id).await?;
 
-    let solution = Solution::from_1indexed_vec(solution, Some(nodes as NumNodes))?;
+    let solution = Solution::from_1indexed_vec(solution, Some(graph.number_of_nodes()))?;
+    let report = solution.verify_domset(&graph, MAX_REPORTED_NODES)?;
 
-    if !solution.valid_domset_for_instance(nodes, edges.into_iter())? {
-        return error_bad_request!("Solution is not a valid dominating set for the instance");
-    }
+    Ok((solution, report, graph))
+}
+
+/// Error body in the common format, with the code `invalid_solution` and the domination
+/// report as details
+pub fn invalid_solution_json(report: &DomsetReport) -> serde_json::Value {
+    let to_1indexed = |nodes: &[Node]| nodes.iter().map(|u| u + 1).collect::<Vec<_>>();
+
+    let mut json = AppError::BadRequest(anyhow::anyhow!(
+        "Solution is not a valid dominating set for the instance"
+    ))
+    .to_json();
+
+    json["code"] = "invalid_solution".into();
+    json["details"] = serde_json::json!({
+        "score": report.score,
+        "num_undominated": report.num_undominated,
+        "undominated": to_1indexed(&report.undominated),
+        "num_redundant": report.num_redundant,
+        "redundant": to_1indexed(&report.redundant),
+    });
+
+    json
+}
 
-    Ok(solution)
+fn invalid_solution_response(report: &DomsetReport) -> Response {
+    (StatusCode::BAD_REQUEST, Json(invalid_solution_json(report))).into_response()
 }
 
 async fn insert_solution_data(
+    app_data: &AppState,
     tx: &mut DbTransaction<'_>,
     solution: &Solution,
+    dry_run: bool,
 ) -> HandlerResult<String> {
     let hash = format!("{:x}", solution.compute_digest());
+    let (encoding, encoded_solution) = encode_solution(solution.solution());
+
+    // the check bypasses the transaction, whose snapshot may predate payloads stored
+    // meanwhile; should the transaction not commit, the payload is eventually collected
+    let stored =
+        sqlx::query_scalar::<_, i64>(r#"SELECT COUNT(*) FROM SolutionData WHERE hash = UNHEX(?)"#)
+            .bind(&hash)
+            .fetch_one(app_data.db())
+            .await?
+            > 0;
+
+    if !stored && !dry_run {
+        put_pending_blob(
+            app_data,
+            BlobKind::Solution,
+            &hash,
+            encoding,
+            BlobSource::Memory(&encoded_solution),
+        )
+        .await?;
+    }
 
-    let encoded_solution = serde_json::to_string(solution.solution())?;
-
-    sqlx::query(r#"INSERT IGNORE INTO SolutionData (hash,data) VALUES (UNHEX(?), ?)"#)
+    sqlx::query(r#"INSERT IGNORE INTO SolutionData (hash,encoding) VALUES (UNHEX(?), ?)"#)
         .bind(&hash)
-        .bind(encoded_solution)
+        .bind(encoding.id())
         .execute(&mut **tx)
         .await?;
 
@@ -146,6 +276,30 @@ async fn insert_solution_data(
     Ok(hash)
 }
 
+/// Rejects uploads into a run that was created by another solver
+async fn check_run_owner(
+    conn: &mut sqlx::MySqlConnection,
+    body: &SolutionUploadRequest,
+) -> HandlerResult<()> {
+    if body.solver_uuid == Some(PRUNING_SOLVER_UUID) || body.run_uuid == PRUNING_RUN_UUID {
+        return error_forbidden!("Run is reserved for the server");
+    }
+
+    let owner = sqlx::query_scalar::<_, Option<String>>(
+        r#"SELECT LOWER(HEX(solver_uuid)) FROM SolverRun WHERE run_uuid = UNHEX(?)"#,
+    )
+    .bind(body.run_uuid.simple().to_string())
+    .fetch_optional(conn)
+    .await?;
+
+    match owner {
+        Some(owner) if owner != body.solver_uuid.map(|x| x.simple().to_string()) => {
+            error_forbidden!("Run belongs to a different solver")
+        }
+        _ => Ok(()),
+    }
+}
+
 async fn insert_solver_run_entry(
     tx: &mut DbTransaction<'_>,
     body: &SolutionUploadRequest,
@@ -197,7 +351,7 @@ async fn insert_invalid_solution_entry(
 ) -> HandlerResult<()> {
     if result_type == SolverResultType::Valid {
         error!("result_type indicates valid solution in invalid branch");
-        return error_bad_request!("Invalid solution result");
+        return Err(anyhow::anyhow!("Invalid solution result").into());
     };
 
     sqlx::query(
@@ -216,6 +370,35 @@ async fn insert_invalid_solution_entry(
     Ok(())
 }
 
+async fn insert_resource_usage(
+    tx: &mut DbTransaction<'_>,
+    body: &SolutionUploadRequest,
+) -> HandlerResult<()> {
+    let resources = &body.resources;
+    if resources.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"INSERT INTO SolutionResources (solution_sid, peak_memory_bytes, cpu_seconds, wall_seconds, exit_code, term_signal, stderr_tail)
+           SELECT sid, ?, ?, ?, ?, ?, ? FROM Solution WHERE sr_uuid = UNHEX(?) AND instance_iid = ?"#,
+    )
+    .bind(resources.peak_memory_bytes)
+    .bind(resources.cpu_seconds)
+    .bind(resources.wall_seconds)
+    .bind(resources.exit_code)
+    .bind(resources.signal)
+    .bind(resources.stderr_tail.as_deref().map(stderr_tail))
+    .bind(body.run_uuid.simple().to_string())
+    .bind(body.instance_id)
+    .execute(&mut **tx)
+    .await?;
+
+    debug!(" Stored resource usage");
+
+    Ok(())
+}
+
 async fn update_instance_score(
     tx: &mut DbTransaction<'_>,
     instance_iid: u32,
@@ -234,141 +417,306 @@ async fn update_instance_score(
     Ok(())
 }
 
-async fn handle_valid_new_solution(
-    app_data: Arc<AppState>,
-    request: SolutionUploadRequest,
-    solution_data: Vec<Node>,
-) -> HandlerResult<impl IntoResponse> {
-    debug!("Handling upload of new solution data");
+/// Outcome of checking an upload before anything is written to the database
+pub enum PreparedUpload {
+    /// A valid solution and, if requested, its pruned version
+    New(Solution, Option<Solution>),
+    Cached(String),
+    Invalid(SolverResultType),
+    Rejected(DomsetReport),
+}
 
-    let solution = verify_solution(app_data.db(), request.instance_id, solution_data).await?;
-    let solution_score = solution.solution.len() as NumNodes;
+/// Authorizes the request and verifies a contained solution; takes the result out of `request`
+pub async fn prepare_upload(
+    app_data: &AppState,
+    request: &mut SolutionUploadRequest,
+) -> HandlerResult<PreparedUpload> {
+    authorize_solver(
+        app_data,
+        request.solver_uuid.as_ref(),
+        request.solver_key.as_deref(),
+    )
+    .await?;
 
-    let mut tx = app_data.db().begin().await?;
+    let result = std::mem::replace(&mut request.result, SolverResult::Empty);
+    let Some(result_type) = result.result_type() else {
+        return error_bad_request!("Empty solution result");
+    };
 
-    insert_solver_run_entry(&mut tx, &request).await?;
-    let solution_hash = insert_solution_data(&mut tx, &solution).await?;
-    insert_valid_solution_entry(&mut tx, &request, &solution_hash, solution_score).await?;
+    Ok(match result {
+        SolverResult::Valid {
+            data: solution_data,
+        } => {
+            let (solution, report, graph) =
+                verify_solution(app_data, request.instance_id, solution_data).await?;
+
+            if report.is_valid() {
+                let pruned = if request.prune_redundant {
+                    Some(solution.prune_redundant(&graph)?)
+                } else {
+                    None
+                };
+                PreparedUpload::New(solution, pruned)
+            } else {
+                debug!(
+                    " Rejected solution leaving {} nodes undominated",
+                    report.num_undominated
+                );
+                PreparedUpload::Rejected(report)
+            }
+        }
+        SolverResult::ValidCached { .. } if request.prune_redundant => {
+            return error_bad_request!("Redundancy pruning requires the solution data");
+        }
+        SolverResult::ValidCached { hash } => PreparedUpload::Cached(hash),
+        _ => PreparedUpload::Invalid(result_type),
+    })
+}
 
-    update_instance_score(&mut tx, request.instance_id, solution_score).await?;
+/// Stores the pruned version of an uploaded solution unless it is identical
+async fn store_pruned_solution(
+    app_data: &AppState,
+    tx: &mut DbTransaction<'_>,
+    request: &SolutionUploadRequest,
+    solution_score: NumNodes,
+    pruned: Solution,
+) -> HandlerResult<PrunedUpload> {
+    let score = pruned.solution.len() as NumNodes;
+    let mut result = PrunedUpload {
+        num_redundant: solution_score - score,
+        score,
+        solution_hash: None,
+    };
 
-    if request.dry_run {
-        tx.rollback().await?;
-    } else {
-        tx.commit().await?;
+    if result.num_redundant == 0 {
+        return Ok(result);
     }
 
-    let note_response = serde_json::json!({"status": "success", "solution_hash": solution_hash});
-    Ok(Json(note_response))
-}
+    // the pseudo-solver keeps one solution per instance, namely the smallest one
+    let current = sqlx::query_as::<_, (String, NumNodes)>(
+        r#"SELECT LOWER(HEX(solution_hash)), score FROM Solution WHERE sr_uuid = UNHEX(?) AND instance_iid = ? FOR UPDATE"#,
+    )
+    .bind(PRUNING_RUN_UUID.simple().to_string())
+    .bind(request.instance_id)
+    .fetch_optional(&mut **tx)
+    .await?;
 
-async fn handle_valid_cached_solution(
-    app_data: Arc<AppState>,
-    request: SolutionUploadRequest,
-    solution_hash: String,
-) -> HandlerResult<impl IntoResponse> {
-    debug!("Handling upload of cached solution data");
+    if current
+        .as_ref()
+        .is_some_and(|(_, current_score)| *current_score <= score)
+    {
+        return Ok(result);
+    }
+
+    sqlx::query(
+        r#"INSERT IGNORE INTO SolverRun (run_uuid, solver_uuid, name, description) VALUES (UNHEX(?), UNHEX(?), ?, ?)"#,
+    )
+    .bind(PRUNING_RUN_UUID.simple().to_string())
+    .bind(PRUNING_SOLVER_UUID.simple().to_string())
+    .bind("Redundancy pruning")
+    .bind("Uploaded solutions without vertices whose neighbourhood is dominated by the others")
+    .execute(&mut **tx)
+    .await?;
 
-    let mut tx = app_data.db().begin().await?;
+    let solution_hash = insert_solution_data(app_data, tx, &pruned, request.dry_run).await?;
 
-    let solution_score = sqlx::query_scalar::<_, NumNodes>(
-        r#"SELECT score FROM Solution WHERE solution_hash=UNHEX(?)"#,
+    sqlx::query(
+        r#"INSERT INTO Solution (sr_uuid, instance_iid, solution_hash, error_code, score, seconds_computed) VALUES (UNHEX(?), ?, UNHEX(?), ?, ?, NULL)
+           ON DUPLICATE KEY UPDATE solution_hash = VALUES(solution_hash), score = VALUES(score)"#,
     )
+    .bind(PRUNING_RUN_UUID.simple().to_string())
+    .bind(request.instance_id)
     .bind(&solution_hash)
-    .fetch_one(&mut *tx)
+    .bind(SolverResultType::Valid as u32)
+    .bind(score)
+    .execute(&mut **tx)
     .await?;
 
-    insert_solver_run_entry(&mut tx, &request).await?;
-    insert_valid_solution_entry(&mut tx, &request, &solution_hash, solution_score).await?;
-
-    if request.dry_run {
-        tx.rollback().await?;
-    } else {
-        tx.commit().await?;
+    // the replaced solution is no longer referenced by this run; the collector keeps it
+    // if another run shares it
+    if let Some((old_hash, _)) = current.filter(|(old_hash, _)| *old_hash != solution_hash) {
+        release_blob(tx, BlobKind::Solution, &old_hash).await?;
     }
 
-    let note_response = serde_json::json!({"status": "success", "solution_hash": solution_hash});
-    Ok(Json(note_response))
+    update_instance_score(tx, request.instance_id, score).await?;
+    debug!(
+        " Stored pruned solution without {} redundant nodes",
+        result.num_redundant
+    );
+
+    result.solution_hash = Some(solution_hash);
+    Ok(result)
 }
 
-async fn handle_invalid_solution(
-    app_data: Arc<AppState>,
-    request: SolutionUploadRequest,
-    result_type: SolverResultType,
-) -> HandlerResult<impl IntoResponse> {
-    debug!("Handling upload of invalid solution");
+/// Outcome of server-side redundancy pruning of an uploaded solution
+pub struct PrunedUpload {
+    pub num_redundant: NumNodes,
+    pub score: NumNodes,
+    /// Unset if no additional solution was stored, as nothing was redundant or the
+    /// pseudo-solver already holds one at most as large for this instance
+    pub solution_hash: Option<String>,
+}
 
-    let mut tx = app_data.db().begin().await?;
+/// Hashes of what [`store_upload`] wrote
+#[derive(Default)]
+pub struct StoredUpload {
+    pub solution_hash: Option<String>,
+    pub pruned: Option<PrunedUpload>,
+}
 
-    insert_solver_run_entry(&mut tx, &request).await?;
-    insert_invalid_solution_entry(&mut tx, &request, result_type).await?;
+/// Writes a prepared upload
+pub async fn store_upload(
+    app_data: &AppState,
+    tx: &mut DbTransaction<'_>,
+    request: &SolutionUploadRequest,
+    prepared: PreparedUpload,
+) -> HandlerResult<StoredUpload> {
+    check_run_owner(&mut *tx, request).await?;
+
+    let mut pruned_upload = None;
+    let solution_hash = match prepared {
+        PreparedUpload::New(solution, pruned) => {
+            debug!("Handling upload of new solution data");
+            let solution_score = solution.solution.len() as NumNodes;
+
+            insert_solver_run_entry(tx, request).await?;
+            let solution_hash =
+                insert_solution_data(app_data, tx, &solution, request.dry_run).await?;
+            insert_valid_solution_entry(tx, request, &solution_hash, solution_score).await?;
+            update_instance_score(tx, request.instance_id, solution_score).await?;
+
+            if let Some(pruned) = pruned {
+                pruned_upload = Some(
+                    store_pruned_solution(app_data, tx, request, solution_score, pruned).await?,
+                );
+            }
 
-    if request.dry_run {
-        tx.rollback().await?;
-    } else {
-        tx.commit().await?;
+            Some(solution_hash)
+        }
+        PreparedUpload::Cached(solution_hash) => {
+            debug!("Handling upload of cached solution data");
+            let solution_score = sqlx::query_scalar::<_, NumNodes>(
+                r#"SELECT score FROM Solution WHERE solution_hash=UNHEX(?)"#,
+            )
+            .bind(&solution_hash)
+            .fetch_one(&mut **tx)
+            .await?;
+
+            insert_solver_run_entry(tx, request).await?;
+            insert_valid_solution_entry(tx, request, &solution_hash, solution_score).await?;
+
+            Some(solution_hash)
+        }
+        PreparedUpload::Invalid(result_type) => {
+            debug!("Handling upload of invalid solution");
+            insert_solver_run_entry(tx, request).await?;
+            insert_invalid_solution_entry(tx, request, result_type).await?;
+
+            None
+        }
+        PreparedUpload::Rejected(_) => {
+            return Err(anyhow::anyhow!("Cannot store rejected solution").into())
+        }
+    };
+
+    insert_resource_usage(tx, request).await?;
+    if let Some(metadata) = &request.run_metadata {
+        store_run_metadata(tx, &request.run_uuid, metadata).await?;
+    }
+    mark_difficulty_outdated(tx, request.instance_id).await?;
+    complete_scheduled_instance(tx, &request.run_uuid, request.instance_id).await?;
+
+    Ok(StoredUpload {
+        solution_hash,
+        pruned: pruned_upload,
+    })
+}
+
+pub fn success_response(stored: &StoredUpload) -> serde_json::Value {
+    let mut response = match &stored.solution_hash {
+        Some(solution_hash) => {
+            serde_json::json!({"status": "success", "solution_hash": solution_hash})
+        }
+        None => serde_json::json!({"status": "success"}),
+    };
+
+    if let Some(pruned) = &stored.pruned {
+        response["pruned"] = serde_json::json!({
+            "num_redundant": pruned.num_redundant,
+            "score": pruned.score,
+            "solution_hash": pruned.solution_hash,
+        });
     }
 
-    let note_response = serde_json::json!({"status": "success"});
-    Ok(Json(note_response))
+    response
 }
 
 pub async fn solution_upload_handler(
     State(app_state): State<Arc<AppState>>,
     Json(mut request): Json<SolutionUploadRequest>,
 ) -> HandlerResult<impl IntoResponse> {
-    let result = std::mem::replace(&mut request.result, SolverResult::Empty);
-    let result_type = result.result_type().unwrap();
+    let prepared = prepare_upload(&app_state, &mut request).await?;
+    if let PreparedUpload::Rejected(report) = &prepared {
+        return Ok(invalid_solution_response(report));
+    }
 
-    Ok(match result {
-        SolverResult::Valid {
-            data: solution_data,
-        } => handle_valid_new_solution(app_state, request, solution_data)
-            .await?
-            .into_response(),
-        SolverResult::ValidCached { hash } => {
-            handle_valid_cached_solution(app_state, request, hash)
-                .await?
-                .into_response()
-        }
-        SolverResult::Infeasible
-        | SolverResult::SyntaxError
-        | SolverResult::Timeout
-        | SolverResult::NonCompetitive
-        | SolverResult::IncompleteOutput => {
-            handle_invalid_solution(app_state, request, result_type)
-                .await?
-                .into_response()
-        }
-        SolverResult::Empty => return error_bad_request!("Empty solution result"),
-    })
+    let mut tx = app_state.db().begin().await?;
+    let stored = store_upload(&app_state, &mut tx, &request, prepared).await?;
+
+    if request.dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+
+    Ok(Json(success_response(&stored)).into_response())
 }
 
 #[cfg(test)]
 mod test {
+    use http_body_util::BodyExt;
     use tracing_test::traced_test;
 
     use super::*;
+    use crate::server::app_state::DbPool;
 
     #[sqlx::test(fixtures("instances"))]
     async fn read_instance_data(pool: DbPool) -> sqlx::Result<()> {
-        let (nodes, edges) = super::read_instance_data(&pool, 2).await.unwrap();
+        let graph = super::read_instance_data(&AppState::new(pool), 2)
+            .await
+            .unwrap();
 
-        assert_eq!(nodes, 3);
-        assert_eq!(edges, vec![Edge(0, 1), Edge(1, 2)]);
+        assert_eq!(graph.number_of_nodes(), 3);
+        assert_eq!(
+            graph.edges().collect::<Vec<_>>(),
+            vec![Edge(0, 1), Edge(1, 2)]
+        );
 
         Ok(())
     }
 
     #[sqlx::test(fixtures("instances"))]
     async fn verify_solution(pool: DbPool) -> sqlx::Result<()> {
+        let app_data = AppState::new(pool);
         let solution = vec![1 as Node, 2];
 
-        let _ = super::verify_solution(&pool, 2, solution).await.unwrap();
+        let (_, report, _) = super::verify_solution(&app_data, 2, solution)
+            .await
+            .unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.num_redundant, 1);
 
         let solution = vec![1 as Node];
 
-        assert!(super::verify_solution(&pool, 2, solution).await.is_err());
+        let (_, report, _) = super::verify_solution(&app_data, 2, solution)
+            .await
+            .unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(report.undominated, vec![2]);
+
+        assert!(super::verify_solution(&app_data, 2, vec![4 as Node])
+            .await
+            .is_err());
 
         Ok(())
     }
@@ -398,7 +746,12 @@ mod test {
                 instance_id: 2,
                 run_uuid: uuid::Uuid::new_v4(),
                 solver_uuid: None,
-                seconds_computed: 1.0,
+                solver_key: None,
+                seconds_computed: Some(1.0),
+                dry_run: false,
+                prune_redundant: false,
+                resources: Default::default(),
+                run_metadata: None,
 
                 result: SolverResult::Valid {
                     data: vec![1 as Node, 2],
@@ -419,7 +772,12 @@ mod test {
                 instance_id: 2,
                 run_uuid: uuid::Uuid::new_v4(),
                 solver_uuid: None,
-                seconds_computed: 1.0,
+                solver_key: None,
+                seconds_computed: Some(1.0),
+                dry_run: false,
+                prune_redundant: false,
+                resources: Default::default(),
+                run_metadata: None,
 
                 result: SolverResult::Valid {
                     data: vec![1 as Node, 2],
@@ -449,7 +807,12 @@ mod test {
                 instance_id: 2,
                 run_uuid: uuid::Uuid::new_v4(),
                 solver_uuid: None,
-                seconds_computed: 1.0,
+                solver_key: None,
+                seconds_computed: Some(1.0),
+                dry_run: false,
+                prune_redundant: false,
+                resources: Default::default(),
+                run_metadata: None,
 
                 result: SolverResult::ValidCached { hash: hash.clone() }
             },
@@ -477,7 +840,12 @@ mod test {
                 instance_id: 2,
                 run_uuid: uuid::Uuid::new_v4(),
                 solver_uuid: None,
-                seconds_computed: 1.0,
+                solver_key: None,
+                seconds_computed: Some(1.0),
+                dry_run: false,
+                prune_redundant: false,
+                resources: Default::default(),
+                run_metadata: None,
 
                 result: SolverResult::Infeasible,
             },
@@ -497,7 +865,12 @@ mod test {
                 instance_id: 2,
                 run_uuid,
                 solver_uuid: Some(solver_uuid),
-                seconds_computed: 1.0,
+                solver_key: None,
+                seconds_computed: Some(1.0),
+                dry_run: false,
+                prune_redundant: false,
+                resources: Default::default(),
+                run_metadata: None,
                 result: SolverResult::Valid {
                     data: vec![1 as Node, 2]
                 },
@@ -511,7 +884,12 @@ mod test {
                 instance_id: 2,
                 run_uuid,
                 solver_uuid: Some(solver_uuid),
-                seconds_computed: 1.0,
+                solver_key: None,
+                seconds_computed: Some(1.0),
+                dry_run: false,
+                prune_redundant: false,
+                resources: Default::default(),
+                run_metadata: None,
                 result: SolverResult::Valid {
                     data: vec![2 as Node],
                 }
@@ -521,4 +899,149 @@ mod test {
 
         Ok(())
     }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn solution_upload_prune_redundant(pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(pool.clone()));
+
+        // instance 2 is the path 1-2-3, so only node 2 is needed
+        const LARGER_HASH: &str = "00112233445566778899aabbccddeeff00112233";
+        let request = |cached: bool| SolutionUploadRequest {
+            instance_id: 2,
+            run_uuid: uuid::Uuid::new_v4(),
+            solver_uuid: None,
+            solver_key: None,
+            seconds_computed: Some(1.0),
+            dry_run: false,
+            prune_redundant: true,
+            resources: Default::default(),
+            run_metadata: None,
+            result: if cached {
+                SolverResult::ValidCached {
+                    hash: LARGER_HASH.into(),
+                }
+            } else {
+                SolverResult::Valid {
+                    data: vec![1 as Node, 2, 3],
+                }
+            },
+        };
+
+        let response = super::solution_upload_handler(State(state.clone()), Json(request(false)))
+            .await
+            .unwrap()
+            .into_response();
+        assert!(response.status().is_success());
+
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["pruned"]["num_redundant"], 2);
+        assert_eq!(json["pruned"]["score"], 1);
+        assert!(json["pruned"]["solution_hash"].is_string());
+        let pruned_hash = json["pruned"]["solution_hash"].clone();
+
+        let best_score =
+            sqlx::query_scalar::<_, Option<u32>>("SELECT best_score FROM Instance WHERE iid = 2")
+                .fetch_one(&pool)
+                .await?;
+        assert_eq!(best_score, Some(1));
+
+        let pruned_score = sqlx::query_scalar::<_, Option<u32>>(
+            "SELECT score FROM Solution WHERE sr_uuid = UNHEX(?) AND instance_iid = 2",
+        )
+        .bind(PRUNING_RUN_UUID.simple().to_string())
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(pruned_score, Some(1));
+
+        // a pruned solution at most as large as the stored one is not stored again
+        let response = super::solution_upload_handler(State(state.clone()), Json(request(false)))
+            .await
+            .unwrap()
+            .into_response();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["pruned"]["num_redundant"], 2);
+        assert!(json["pruned"]["solution_hash"].is_null());
+
+        // a smaller one replaces the stored solution, whose payload is left to the collector
+        sqlx::query("INSERT INTO SolutionData (hash, encoding) VALUES (UNHEX(?), 0)")
+            .bind(LARGER_HASH)
+            .execute(&pool)
+            .await?;
+        sqlx::query("UPDATE Solution SET solution_hash = UNHEX(?), score = 2 WHERE sr_uuid = UNHEX(?) AND instance_iid = 2")
+            .bind(LARGER_HASH)
+            .bind(PRUNING_RUN_UUID.simple().to_string())
+            .execute(&pool)
+            .await?;
+
+        let response = super::solution_upload_handler(State(state.clone()), Json(request(false)))
+            .await
+            .unwrap()
+            .into_response();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["pruned"]["solution_hash"], pruned_hash);
+
+        let released = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM BlobPending WHERE kind = 'solutions' AND hash = UNHEX(?)",
+        )
+        .bind(LARGER_HASH)
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(released, 1);
+
+        // pruning cannot be applied to cached solutions
+        let response = super::solution_upload_handler(State(state), Json(request(true))).await;
+        assert!(response.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn stderr_tail_is_cut_at_char_boundary() {
+        assert_eq!(super::stderr_tail("short"), "short");
+
+        let long = format!("x{}", "ä".repeat(MAX_STDERR_TAIL_BYTES / 2));
+        let tail = super::stderr_tail(&long);
+        assert_eq!(tail.len(), MAX_STDERR_TAIL_BYTES);
+        assert!(tail.chars().all(|c| c == 'ä'));
+
+        let long = "ä".repeat(MAX_STDERR_TAIL_BYTES);
+        assert_eq!(super::stderr_tail(&long).len(), MAX_STDERR_TAIL_BYTES);
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn solution_upload_resource_usage(pool: DbPool) -> sqlx::Result<()> {
+        let request: SolutionUploadRequest = serde_json::from_value(serde_json::json!({
+            "instance_id": 2,
+            "run_uuid": uuid::Uuid::new_v4(),
+            "solver_uuid": null,
+            "seconds_computed": 10.0,
+            "result": {"status": "timeout"},
+            "peak_memory_bytes": 1u64 << 33,
+            "cpu_seconds": 9.5,
+            "signal": 9,
+            "stderr_tail": "e".repeat(2 * MAX_STDERR_TAIL_BYTES),
+        }))
+        .unwrap();
+        assert_eq!(request.resources.signal, Some(9));
+
+        test!(pool.clone(), request, true);
+
+        let (memory, signal, exit_code, stderr) = sqlx::query_as::<
+            _,
+            (Option<u64>, Option<i32>, Option<i32>, Option<String>),
+        >(
+            "SELECT peak_memory_bytes, term_signal, exit_code, stderr_tail FROM SolutionResources",
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(memory, Some(1 << 33));
+        assert_eq!(signal, Some(9));
+        assert_eq!(exit_code, None);
+        assert_eq!(stderr.unwrap().len(), MAX_STDERR_TAIL_BYTES);
+
+        Ok(())
+    }
 }
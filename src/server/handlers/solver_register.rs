@@ -0,0 +1,205 @@
+use axum::http::HeaderMap;
+use tracing::debug;
+use uuid::Uuid;
+
+use super::common::*;
+use crate::server::auth::{
+    check_solver_key, generate_secret, hash_secret, headers_grant, Scope, SolverKeyCheck,
+};
+
+#[derive(Clone, Deserialize, Serialize, Debug, Default)]
+pub struct SolverRegisterRequest {
+    /// Claim an existing (so far unregistered) solver uuid; a fresh one is generated otherwise.
+    /// Solvers that already have runs can only be claimed with an admin token.
+    #[serde(default)]
+    solver_uuid: Option<Uuid>,
+
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Rejects requests on behalf of `solver` that do not carry its key. Requests for
+/// unregistered solvers (or without any solver) pass if the server accepts them.
+pub async fn authorize_solver(
+    app_data: &AppState,
+    solver: Option<&Uuid>,
+    key: Option<&str>,
+) -> HandlerResult<()> {
+    let check = match solver {
+        Some(solver) => check_solver_key(app_data.db(), solver, key).await?,
+        None => SolverKeyCheck::Unregistered,
+    };
+
+    match check {
+        SolverKeyCheck::Valid => Ok(()),
+        SolverKeyCheck::Invalid => error_forbidden!("Invalid or missing solver key"),
+        SolverKeyCheck::Unregistered if app_data.accept_unregistered_solvers() => Ok(()),
+        SolverKeyCheck::Unregistered => {
+            error_forbidden!("Solver is not registered; register it at /api/solvers/register")
+        }
+    }
+}
+
+pub async fn solver_register_handler(
+    State(app_data): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<SolverRegisterRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    if let Some(solver_uuid) = &request.solver_uuid {
+        // solver uuids are public, so anyone could otherwise take over the runs of a solver
+        let num_runs = sqlx::query_scalar::<_, i64>(
+            r#"SELECT COUNT(*) FROM SolverRun WHERE solver_uuid = UNHEX(?)"#,
+        )
+        .bind(solver_uuid.simple().to_string())
+        .fetch_one(app_data.db())
+        .await?;
+
+        if num_runs > 0 && !headers_grant(app_data.db(), &headers, Scope::Admin).await? {
+            return error_forbidden!(
+                "Solver already has runs; claiming it requires an admin token"
+            );
+        }
+    }
+
+    let solver_uuid = request.solver_uuid.unwrap_or_else(Uuid::new_v4);
+    let key = generate_secret();
+
+    let result = sqlx::query(
+        r#"INSERT IGNORE INTO Solver (solver_uuid, key_hash, name) VALUES (UNHEX(?), UNHEX(?), ?)"#,
+    )
+    .bind(solver_uuid.simple().to_string())
+    .bind(hash_secret(&key))
+    .bind(request.name.as_ref().map(|n| n.trim()))
+    .execute(app_data.db())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return error_conflict!("Solver is already registered");
+    }
+
+    debug!("Registered solver {solver_uuid}");
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "solver_uuid": solver_uuid,
+        "key": key,
+    })))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::{app_state::DbPool, auth::mint_token};
+
+    async fn register(state: &Arc<AppState>, solver_uuid: Option<Uuid>) -> (Uuid, String) {
+        let response = super::solver_register_handler(
+            State(state.clone()),
+            HeaderMap::new(),
+            Json(SolverRegisterRequest {
+                solver_uuid,
+                name: Some("solver".into()),
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        (
+            json["solver_uuid"].as_str().unwrap().parse().unwrap(),
+            json["key"].as_str().unwrap().to_string(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn register_and_authorize(pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(pool));
+        let (solver, key) = register(&state, None).await;
+
+        assert!(authorize_solver(&state, Some(&solver), Some(&key))
+            .await
+            .is_ok());
+        assert!(authorize_solver(&state, Some(&solver), Some("wrong"))
+            .await
+            .is_err());
+        assert!(authorize_solver(&state, Some(&solver), None).await.is_err());
+
+        // registering the same uuid twice must not replace the key
+        assert!(super::solver_register_handler(
+            State(state.clone()),
+            HeaderMap::new(),
+            Json(SolverRegisterRequest {
+                solver_uuid: Some(solver),
+                name: None,
+            }),
+        )
+        .await
+        .is_err());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn unregistered_solvers(pool: DbPool) -> sqlx::Result<()> {
+        let solver = Uuid::new_v4();
+
+        let lenient = AppState::new(pool.clone());
+        assert!(authorize_solver(&lenient, Some(&solver), None)
+            .await
+            .is_ok());
+        assert!(authorize_solver(&lenient, None, None).await.is_ok());
+
+        let strict = AppState::new(pool).with_unregistered_solvers_accepted(false);
+        assert!(authorize_solver(&strict, Some(&solver), None)
+            .await
+            .is_err());
+        assert!(authorize_solver(&strict, None, None).await.is_err());
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("instances", "solutions"))]
+    async fn claim_solver_with_runs(pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(pool.clone()));
+        // has runs in the solutions fixture
+        let solver_with_runs = Uuid::parse_str("00000000-0000-0000-0002-000000000000").unwrap();
+
+        let request = SolverRegisterRequest {
+            solver_uuid: Some(solver_with_runs),
+            name: None,
+        };
+
+        let Err(error) = super::solver_register_handler(
+            State(state.clone()),
+            HeaderMap::new(),
+            Json(request.clone()),
+        )
+        .await
+        else {
+            panic!("claimed a solver with runs without admin token");
+        };
+        assert_eq!(
+            error.into_response().status(),
+            axum::http::StatusCode::FORBIDDEN
+        );
+
+        let (_, admin_token) = mint_token(&pool, "admin", &[Scope::Admin]).await.unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::AUTHORIZATION,
+            format!("Bearer {admin_token}").parse().unwrap(),
+        );
+
+        assert!(
+            super::solver_register_handler(State(state), headers, Json(request))
+                .await
+                .is_ok()
+        );
+
+        Ok(())
+    }
+}
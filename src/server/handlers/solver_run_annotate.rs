@@ -1,12 +1,16 @@
-use super::common::*;
+use super::{common::*, solver_register::authorize_solver};
 use sqlx::QueryBuilder;
 use uuid::Uuid;
 
 #[derive(Clone, Deserialize, Serialize, Debug, Default)]
-pub struct FilterOptions {
+pub struct AnnotateRequest {
     solver: Uuid,
     run: Uuid,
 
+    /// Key obtained when registering the solver
+    #[serde(default)]
+    key: Option<String>,
+
     #[serde(default)]
     name: Option<String>,
 
@@ -18,10 +22,10 @@ pub struct FilterOptions {
 }
 
 pub async fn solver_run_annotate_handler(
-    opts: Option<Query<FilterOptions>>,
     State(app_data): State<Arc<AppState>>,
+    Json(opts): Json<AnnotateRequest>,
 ) -> HandlerResult<impl IntoResponse> {
-    let opts = opts.unwrap_or_default();
+    authorize_solver(&app_data, Some(&opts.solver), opts.key.as_deref()).await?;
 
     let mut builder = QueryBuilder::new("UPDATE SolverRun SET ");
 
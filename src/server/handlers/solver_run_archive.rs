@@ -0,0 +1,246 @@
+use std::fmt::Write as _;
+
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::HeaderValue;
+use uuid::Uuid;
+
+use super::common::*;
+use super::solution_download::solution_file_name;
+use super::solution_upload::SolverResultType;
+use crate::pace::codec::{decode_solution, BlobEncoding};
+use crate::pace::Solution;
+use crate::server::archive::{stream_archive, ArchiveOptions, ArchiveStream};
+use crate::server::blob_store::{load_blob, BlobKind};
+
+#[derive(Clone, Copy, Deserialize, Serialize, Debug)]
+pub struct RunArchiveOptions {
+    solver: Uuid,
+    run: Uuid,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct SolutionModel {
+    iid: i32,
+    error_code: Option<u32>,
+    score: Option<u32>,
+    seconds_computed: Option<f64>,
+    hash: Option<String>,
+    encoding: Option<u8>,
+}
+
+impl SolutionModel {
+    fn status(&self) -> String {
+        match self.error_code {
+            Some(code) => match SolverResultType::try_from(code) {
+                Ok(status) => format!("{status:?}"),
+                Err(_) => code.to_string(),
+            },
+            None => String::new(),
+        }
+    }
+
+    /// Empty solutions cannot be written as `.sol` file, so they only appear in the summary
+    fn file_name(&self, run: &Uuid) -> Option<String> {
+        self.hash.as_ref()?;
+        let score = self.score.filter(|&s| s > 0)?;
+        Some(solution_file_name(self.iid as u32, score, run))
+    }
+}
+
+async fn fetch_solutions(
+    opts: &RunArchiveOptions,
+    app_data: &AppState,
+) -> HandlerResult<Vec<SolutionModel>> {
+    let run = opts.run.simple().to_string();
+    let solver = opts.solver.simple().to_string();
+
+    // requiring the matching solver prevents enumerating runs by their uuid alone
+    let known_run = sqlx::query_scalar::<_, i64>(
+        r#"SELECT COUNT(*) FROM SolverRun WHERE run_uuid = UNHEX(?) AND solver_uuid = UNHEX(?)"#,
+    )
+    .bind(&run)
+    .bind(&solver)
+    .fetch_one(app_data.db())
+    .await?;
+
+    if known_run == 0 {
+        return error_not_found!("Unknown solver run");
+    }
+
+    // the payloads are loaded one by one while the archive is written
+    Ok(sqlx::query_as::<_, SolutionModel>(
+        r#"SELECT
+            s.instance_iid AS iid, s.error_code, s.score, s.seconds_computed,
+            LOWER(HEX(sd.hash)) AS hash, sd.encoding
+           FROM `Solution` s
+           LEFT JOIN SolutionData sd ON sd.hash = s.solution_hash
+           WHERE s.sr_uuid = UNHEX(?)
+           ORDER BY s.instance_iid"#,
+    )
+    .bind(&run)
+    .fetch_all(app_data.db())
+    .await?)
+}
+
+async fn solution_document(
+    hash: &str,
+    encoding: Option<u8>,
+    app_data: &AppState,
+) -> HandlerResult<Vec<u8>> {
+    let inline = sqlx::query_scalar::<_, Option<Vec<u8>>>(
+        r#"SELECT data FROM SolutionData WHERE hash = UNHEX(?)"#,
+    )
+    .bind(hash)
+    .fetch_one(app_data.db())
+    .await?;
+
+    let Some(data) = load_blob(app_data.blob_store(), BlobKind::Solution, hash, inline).await?
+    else {
+        return error_not_found!("Solution data is missing");
+    };
+
+    let encoding = BlobEncoding::try_from(encoding.unwrap_or_default())?;
+    let solution = Solution::from_0indexed_vec(decode_solution(encoding, &data)?);
+
+    let mut document = Vec::with_capacity(data.len() * 8 + 100);
+    solution.write(&mut document)?;
+    Ok(document)
+}
+
+fn summary_csv(opts: &RunArchiveOptions, solutions: &[SolutionModel]) -> String {
+    let mut csv = String::from("iid,status,score,seconds_computed,file\n");
+    for solution in solutions {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{}",
+            solution.iid,
+            solution.status(),
+            solution.score.map(|s| s.to_string()).unwrap_or_default(),
+            solution
+                .seconds_computed
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+            solution.file_name(&opts.run).unwrap_or_default(),
+        );
+    }
+    csv
+}
+
+async fn write_archive(
+    mut archive: ArchiveStream,
+    opts: RunArchiveOptions,
+    solutions: Vec<SolutionModel>,
+    app_data: Arc<AppState>,
+) -> HandlerResult<ArchiveStream> {
+    if !archive
+        .add_file("summary.csv", summary_csv(&opts, &solutions))
+        .await?
+    {
+        return Ok(archive);
+    }
+
+    for solution in &solutions {
+        let (Some(file_name), Some(hash)) = (solution.file_name(&opts.run), &solution.hash) else {
+            continue;
+        };
+
+        let document = solution_document(hash, solution.encoding, &app_data).await?;
+        if !archive.add_file(&file_name, document).await? {
+            break;
+        }
+    }
+
+    Ok(archive)
+}
+
+/// Streams all solutions of a run as `.sol` files (named as by the single solution
+/// download) together with a `summary.csv` listing the status of every instance
+pub async fn solver_run_archive_handler(
+    Query(opts): Query<RunArchiveOptions>,
+    Query(options): Query<ArchiveOptions>,
+    State(app_data): State<Arc<AppState>>,
+) -> HandlerResult<impl IntoResponse> {
+    let solutions = fetch_solutions(&opts, &app_data).await?;
+
+    let body = stream_archive(options, |archive| {
+        write_archive(archive, opts, solutions, app_data)
+    })?;
+
+    let content_disposition = HeaderValue::from_str(&format!(
+        "attachment; filename=\"{}\"",
+        options.file_name(&format!("solutions_run{}", opts.run))
+    ))?;
+
+    Ok((
+        [
+            (CONTENT_DISPOSITION, content_disposition),
+            (
+                CONTENT_TYPE,
+                HeaderValue::from_static(options.content_type()),
+            ),
+        ],
+        body,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::server::{app_state::DbPool, archive::read_tar};
+
+    #[sqlx::test(fixtures("instances", "solutions"))]
+    async fn run_archive(db_pool: DbPool) -> sqlx::Result<()> {
+        let opts = RunArchiveOptions {
+            solver: Uuid::parse_str("00000000-0000-0000-0002-000000000000").unwrap(),
+            run: Uuid::parse_str("00000000-0000-0000-0001-000000000000").unwrap(),
+        };
+
+        let state = Arc::new(AppState::new(db_pool.clone()));
+        let resp = solver_run_archive_handler(
+            Query(opts),
+            Query(ArchiveOptions::default()),
+            State(state.clone()),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        assert!(resp.status().is_success());
+
+        let archive = resp.into_body().collect().await.unwrap().to_bytes();
+        let entries = read_tar(&archive);
+        assert_eq!(entries.len(), 2);
+
+        let file_name = "sol_inst1_score1_run00000000-0000-0000-0001-000000000000.sol";
+        let (name, summary) = &entries[0];
+        assert_eq!(name, "summary.csv");
+        let mut lines = std::str::from_utf8(summary).unwrap().lines();
+        assert_eq!(lines.next(), Some("iid,status,score,seconds_computed,file"));
+        assert_eq!(
+            lines.next(),
+            Some(format!("1,0,1,1.2,{file_name}").as_str())
+        );
+        assert_eq!(lines.next(), None);
+
+        let (name, solution) = &entries[1];
+        assert_eq!(name, file_name);
+        let solution = Solution::read(solution.as_slice(), None).unwrap();
+        assert_eq!(solution.take_1indexed_solution(), vec![1, 2, 4]);
+
+        // run belongs to another solver
+        let wrong_solver = RunArchiveOptions {
+            solver: Uuid::parse_str("00000000-0000-0000-0002-000000000001").unwrap(),
+            ..opts
+        };
+        assert!(solver_run_archive_handler(
+            Query(wrong_solver),
+            Query(ArchiveOptions::default()),
+            State(state),
+        )
+        .await
+        .is_err());
+
+        Ok(())
+    }
+}
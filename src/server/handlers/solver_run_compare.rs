@@ -0,0 +1,470 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use uuid::Uuid;
+
+use super::{common::*, instance_list::SortDirection, solution_upload::SolverResultType};
+
+/// Maximum number of instances reported per page
+const MAX_LIMIT: usize = 1000;
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, Default)]
+pub struct RunRef {
+    solver: Uuid,
+    run: Uuid,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareSortBy {
+    #[default]
+    Iid,
+    ScoreDelta,
+    SecondsDelta,
+}
+
+/// Outcome of an instance from the perspective of the candidate run
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Both runs solved the instance and the candidate found a smaller solution
+    Win,
+    Tie,
+    Loss,
+    /// The baseline failed, but the candidate solved the instance
+    OnlyCandidateSolved,
+    OnlyBaselineSolved,
+    NeitherSolved,
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug, Default)]
+pub struct CompareOptions {
+    baseline: RunRef,
+    candidate: RunRef,
+
+    /// Only list instances with this outcome; the counts always cover all instances
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    outcome: Option<Outcome>,
+
+    #[serde(default)]
+    sort_by: CompareSortBy,
+
+    #[serde(default)]
+    sort_direction: SortDirection,
+
+    #[serde(default = "default_page")]
+    page: usize,
+
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+#[derive(Clone, Copy, Debug, sqlx::FromRow)]
+struct ResultModel {
+    iid: i32,
+    error_code: Option<u32>,
+    score: Option<u32>,
+    seconds_computed: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+struct RunResult {
+    /// Unset for error codes unknown to this server
+    status: Option<SolverResultType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seconds_computed: Option<f64>,
+}
+
+impl From<ResultModel> for RunResult {
+    fn from(model: ResultModel) -> Self {
+        Self {
+            status: model
+                .error_code
+                .and_then(|c| SolverResultType::try_from(c).ok()),
+            score: model.score,
+            seconds_computed: model.seconds_computed,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+struct InstanceComparison {
+    iid: u32,
+    outcome: Outcome,
+    baseline: RunResult,
+    candidate: RunResult,
+
+    /// Candidate score minus baseline score; negative if the candidate is better
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score_delta: Option<i64>,
+    /// Candidate time minus baseline time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seconds_delta: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, PartialEq, Eq)]
+struct OutcomeCounts {
+    /// Instances attempted by both runs
+    num_common: u32,
+    num_only_in_baseline: u32,
+    num_only_in_candidate: u32,
+
+    wins: u32,
+    ties: u32,
+    losses: u32,
+    only_candidate_solved: u32,
+    only_baseline_solved: u32,
+    neither_solved: u32,
+}
+
+#[derive(Serialize, Debug)]
+struct Response {
+    status: &'static str,
+    options: CompareOptions,
+
+    #[serde(flatten)]
+    counts: OutcomeCounts,
+
+    /// Number of instances matching the outcome filter
+    total_matches: u32,
+    instances: Vec<InstanceComparison>,
+}
+
+async fn fetch_run_results(
+    run: &RunRef,
+    app_data: &AppState,
+) -> HandlerResult<HashMap<u32, RunResult>> {
+    let run_uuid = run.run.simple().to_string();
+
+    // requiring the matching solver prevents enumerating runs by their uuid alone
+    let known_run = sqlx::query_scalar::<_, i64>(
+        r#"SELECT COUNT(*) FROM SolverRun WHERE run_uuid = UNHEX(?) AND solver_uuid = UNHEX(?)"#,
+    )
+    .bind(&run_uuid)
+    .bind(run.solver.simple().to_string())
+    .fetch_one(app_data.db())
+    .await?;
+
+    if known_run == 0 {
+        return error_not_found!("Unknown solver run");
+    }
+
+    Ok(sqlx::query_as::<_, ResultModel>(
+        r#"SELECT instance_iid AS iid, error_code, score, seconds_computed
+           FROM `Solution` WHERE sr_uuid = UNHEX(?)"#,
+    )
+    .bind(&run_uuid)
+    .fetch_all(app_data.db())
+    .await?
+    .into_iter()
+    .map(|r| (r.iid as u32, r.into()))
+    .collect())
+}
+
+fn outcome(baseline: &RunResult, candidate: &RunResult) -> Outcome {
+    match (baseline.score, candidate.score) {
+        (Some(b), Some(c)) => match c.cmp(&b) {
+            Ordering::Less => Outcome::Win,
+            Ordering::Equal => Outcome::Tie,
+            Ordering::Greater => Outcome::Loss,
+        },
+        (None, Some(_)) => Outcome::OnlyCandidateSolved,
+        (Some(_), None) => Outcome::OnlyBaselineSolved,
+        (None, None) => Outcome::NeitherSolved,
+    }
+}
+
+/// Pairs the results of both runs by instance, sorted by iid
+fn compare_results(
+    baseline: &HashMap<u32, RunResult>,
+    candidate: &HashMap<u32, RunResult>,
+) -> (Vec<InstanceComparison>, OutcomeCounts) {
+    let mut counts = OutcomeCounts::default();
+    let mut comparisons = Vec::with_capacity(baseline.len().min(candidate.len()));
+
+    for (&iid, base) in baseline {
+        let Some(cand) = candidate.get(&iid) else {
+            counts.num_only_in_baseline += 1;
+            continue;
+        };
+
+        let outcome = outcome(base, cand);
+        *match outcome {
+            Outcome::Win => &mut counts.wins,
+            Outcome::Tie => &mut counts.ties,
+            Outcome::Loss => &mut counts.losses,
+            Outcome::OnlyCandidateSolved => &mut counts.only_candidate_solved,
+            Outcome::OnlyBaselineSolved => &mut counts.only_baseline_solved,
+            Outcome::NeitherSolved => &mut counts.neither_solved,
+        } += 1;
+
+        comparisons.push(InstanceComparison {
+            iid,
+            outcome,
+            baseline: *base,
+            candidate: *cand,
+            score_delta: base.score.zip(cand.score).map(|(b, c)| c as i64 - b as i64),
+            seconds_delta: base
+                .seconds_computed
+                .zip(cand.seconds_computed)
+                .map(|(b, c)| c - b),
+        });
+    }
+
+    counts.num_common = comparisons.len() as u32;
+    counts.num_only_in_candidate = (candidate.len() - comparisons.len()) as u32;
+
+    comparisons.sort_unstable_by_key(|c| c.iid);
+    (comparisons, counts)
+}
+
+/// Sorts by the requested key; instances without a value for it always come last
+fn sort_comparisons(
+    comparisons: &mut [InstanceComparison],
+    sort_by: CompareSortBy,
+    direction: SortDirection,
+) {
+    let directed = |ord: Ordering| match direction {
+        SortDirection::Asc => ord,
+        SortDirection::Desc => ord.reverse(),
+    };
+
+    fn none_last<T>(a: Option<T>, b: Option<T>, cmp: impl Fn(T, T) -> Ordering) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) => cmp(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    // the sort is stable, so ties remain ordered by iid
+    match sort_by {
+        CompareSortBy::Iid => comparisons.sort_by(|a, b| directed(a.iid.cmp(&b.iid))),
+        CompareSortBy::ScoreDelta => comparisons
+            .sort_by(|a, b| none_last(a.score_delta, b.score_delta, |a, b| directed(a.cmp(&b)))),
+        CompareSortBy::SecondsDelta => comparisons.sort_by(|a, b| {
+            none_last(a.seconds_delta, b.seconds_delta, |a, b| {
+                directed(a.total_cmp(&b))
+            })
+        }),
+    }
+}
+
+/// Joins the results of two runs on their instances and reports, per instance, how the
+/// candidate run fares against the baseline, together with win/tie/loss counts
+pub async fn solver_run_compare_handler(
+    State(app_data): State<Arc<AppState>>,
+    Json(opts): Json<CompareOptions>,
+) -> HandlerResult<impl IntoResponse> {
+    if opts.limit == 0 || opts.limit > MAX_LIMIT {
+        return error_bad_request!("limit must be between 1 and {MAX_LIMIT}");
+    }
+
+    let baseline = fetch_run_results(&opts.baseline, &app_data).await?;
+    let candidate = fetch_run_results(&opts.candidate, &app_data).await?;
+
+    let (mut comparisons, counts) = compare_results(&baseline, &candidate);
+    if let Some(outcome) = opts.outcome {
+        comparisons.retain(|c| c.outcome == outcome);
+    }
+    sort_comparisons(&mut comparisons, opts.sort_by, opts.sort_direction);
+
+    let total_matches = comparisons.len() as u32;
+    let instances = comparisons
+        .into_iter()
+        .skip(opts.page.saturating_sub(1).saturating_mul(opts.limit))
+        .take(opts.limit)
+        .collect();
+
+    Ok(Json(Response {
+        status: "ok",
+        options: opts,
+        counts,
+        total_matches,
+        instances,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::app_state::DbPool;
+
+    fn solved(score: u32, seconds: f64) -> RunResult {
+        RunResult {
+            status: Some(SolverResultType::Valid),
+            score: Some(score),
+            seconds_computed: Some(seconds),
+        }
+    }
+
+    fn failed(status: SolverResultType) -> RunResult {
+        RunResult {
+            status: Some(status),
+            score: None,
+            seconds_computed: None,
+        }
+    }
+
+    #[test]
+    fn compare() {
+        let baseline = HashMap::from([
+            (1, solved(5, 1.0)),
+            (2, solved(5, 1.0)),
+            (3, solved(5, 3.0)),
+            (4, failed(SolverResultType::Timeout)),
+            (5, solved(5, 1.0)),
+            (6, failed(SolverResultType::Timeout)),
+            (7, solved(5, 1.0)),
+        ]);
+        let candidate = HashMap::from([
+            (1, solved(4, 2.0)),
+            (2, solved(5, 0.5)),
+            (3, solved(6, 1.0)),
+            (4, solved(7, 9.0)),
+            (5, failed(SolverResultType::IncompleteOutput)),
+            (6, failed(SolverResultType::Timeout)),
+            (8, solved(5, 1.0)),
+        ]);
+
+        let (mut comparisons, counts) = compare_results(&baseline, &candidate);
+        assert_eq!(
+            counts,
+            OutcomeCounts {
+                num_common: 6,
+                num_only_in_baseline: 1,
+                num_only_in_candidate: 1,
+                wins: 1,
+                ties: 1,
+                losses: 1,
+                only_candidate_solved: 1,
+                only_baseline_solved: 1,
+                neither_solved: 1,
+            }
+        );
+
+        let outcomes: Vec<_> = comparisons.iter().map(|c| c.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                Outcome::Win,
+                Outcome::Tie,
+                Outcome::Loss,
+                Outcome::OnlyCandidateSolved,
+                Outcome::OnlyBaselineSolved,
+                Outcome::NeitherSolved,
+            ]
+        );
+        assert_eq!(comparisons[0].score_delta, Some(-1));
+        assert_eq!(comparisons[2].seconds_delta, Some(-2.0));
+        assert_eq!(comparisons[3].score_delta, None);
+
+        let order = |comparisons: &[InstanceComparison]| -> Vec<u32> {
+            comparisons.iter().map(|c| c.iid).collect()
+        };
+
+        sort_comparisons(
+            &mut comparisons,
+            CompareSortBy::ScoreDelta,
+            SortDirection::Desc,
+        );
+        assert_eq!(order(&comparisons), vec![3, 2, 1, 4, 5, 6]);
+
+        sort_comparisons(
+            &mut comparisons,
+            CompareSortBy::SecondsDelta,
+            SortDirection::Asc,
+        );
+        assert_eq!(order(&comparisons), vec![3, 2, 1, 4, 5, 6]);
+
+        sort_comparisons(&mut comparisons, CompareSortBy::Iid, SortDirection::Desc);
+        assert_eq!(order(&comparisons), vec![6, 5, 4, 3, 2, 1]);
+    }
+
+    #[sqlx::test(fixtures("instances", "solutions"))]
+    async fn compare_runs(db_pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(db_pool.clone()));
+        let solver = Uuid::parse_str("00000000-0000-0000-0002-000000000000").unwrap();
+
+        let opts = CompareOptions {
+            baseline: RunRef {
+                solver,
+                run: Uuid::parse_str("00000000-0000-0000-0001-000000000000").unwrap(),
+            },
+            candidate: RunRef {
+                solver,
+                run: Uuid::parse_str("00000000-0000-0000-0001-000000000001").unwrap(),
+            },
+            page: 1,
+            limit: 10,
+            ..Default::default()
+        };
+
+        let resp = solver_run_compare_handler(State(state.clone()), Json(opts.clone()))
+            .await
+            .unwrap()
+            .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(json["num_common"], 1);
+        assert_eq!(json["ties"], 1);
+        assert_eq!(json["instances"][0]["iid"], 1);
+        assert_eq!(json["instances"][0]["outcome"], "tie");
+        assert_eq!(
+            json["instances"][0]["seconds_delta"].as_f64().unwrap(),
+            2.3 - 1.2
+        );
+
+        // pages beyond the last one are empty
+        let far_page = CompareOptions {
+            page: usize::MAX,
+            ..opts.clone()
+        };
+        let resp = solver_run_compare_handler(State(state.clone()), Json(far_page))
+            .await
+            .unwrap()
+            .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["instances"], serde_json::json!([]));
+
+        let too_many = CompareOptions {
+            limit: MAX_LIMIT + 1,
+            ..opts.clone()
+        };
+        assert!(
+            solver_run_compare_handler(State(state.clone()), Json(too_many))
+                .await
+                .is_err()
+        );
+
+        // the candidate run belongs to another solver
+        let wrong_solver = CompareOptions {
+            candidate: RunRef {
+                solver,
+                run: Uuid::parse_str("00000000-0000-0000-0001-000000000002").unwrap(),
+            },
+            ..opts
+        };
+        assert!(solver_run_compare_handler(State(state), Json(wrong_solver))
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
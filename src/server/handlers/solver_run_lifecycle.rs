@@ -0,0 +1,347 @@
+use tracing::debug;
+use uuid::Uuid;
+
+use super::{
+    common::*,
+    solver_register::authorize_solver,
+    solver_run_metadata::{store_run_metadata, RunMetadata},
+};
+
+/// Runs without a heartbeat for this long are reported as stale, i.e. probably crashed
+pub const HEARTBEAT_TIMEOUT_SECONDS: i64 = 600;
+
+/// State of a run opened via [`solver_run_start_handler`]; runs created implicitly by
+/// uploads have no state
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum RunState {
+    Running = 1,
+    Finished = 2,
+    Aborted = 3,
+}
+
+impl TryFrom<u32> for RunState {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            x if x == RunState::Running as u32 => Ok(RunState::Running),
+            x if x == RunState::Finished as u32 => Ok(RunState::Finished),
+            x if x == RunState::Aborted as u32 => Ok(RunState::Aborted),
+            _ => Err(anyhow::anyhow!("Invalid RunState value")),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug, Default)]
+pub struct StartRequest {
+    solver: Uuid,
+    run: Uuid,
+
+    /// Key obtained when registering the solver
+    #[serde(default)]
+    key: Option<String>,
+
+    /// Number of instances the run will attempt; kept as is if the run was scheduled
+    #[serde(default)]
+    num_scheduled: Option<u32>,
+
+    #[serde(default)]
+    solver_version: Option<String>,
+
+    /// Time limit per instance
+    #[serde(default)]
+    timeout_seconds: Option<f64>,
+
+    #[serde(flatten)]
+    metadata: RunMetadata,
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug, Default)]
+pub struct RunRequest {
+    solver: Uuid,
+    run: Uuid,
+
+    /// Key obtained when registering the solver
+    #[serde(default)]
+    key: Option<String>,
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug, Default)]
+pub struct FinishRequest {
+    solver: Uuid,
+    run: Uuid,
+
+    /// Key obtained when registering the solver
+    #[serde(default)]
+    key: Option<String>,
+
+    /// Marks the run as aborted rather than finished
+    #[serde(default)]
+    abort: bool,
+}
+
+/// Locks the run and returns its state; fails unless `solver` owns the run
+async fn lock_run(
+    conn: &mut sqlx::MySqlConnection,
+    solver: &Uuid,
+    run: &Uuid,
+) -> HandlerResult<Option<Option<RunState>>> {
+    let Some((owner, state)) = sqlx::query_as::<_, (Option<String>, Option<u32>)>(
+        r#"SELECT LOWER(HEX(solver_uuid)), state FROM SolverRun WHERE run_uuid = UNHEX(?) FOR UPDATE"#,
+    )
+    .bind(run.simple().to_string())
+    .fetch_optional(conn)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    if owner != Some(solver.simple().to_string()) {
+        return error_forbidden!("Run belongs to a different solver");
+    }
+
+    Ok(Some(state.map(RunState::try_from).transpose()?))
+}
+
+/// Like [`lock_run`], but only accepts runs that were started and have not finished yet
+async fn lock_running_run(
+    conn: &mut sqlx::MySqlConnection,
+    solver: &Uuid,
+    run: &Uuid,
+) -> HandlerResult<()> {
+    match lock_run(conn, solver, run).await? {
+        None => error_not_found!("Unknown solver run"),
+        Some(None) => error_conflict!("Run was not started"),
+        Some(Some(RunState::Running)) => Ok(()),
+        Some(Some(_)) => error_conflict!("Run has already finished"),
+    }
+}
+
+/// Opens a run explicitly, recording its setup; the run is then reported as running
+/// until it is finished or aborted (or as stale if its heartbeats stop)
+pub async fn solver_run_start_handler(
+    State(app_data): State<Arc<AppState>>,
+    Json(request): Json<StartRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    authorize_solver(&app_data, Some(&request.solver), request.key.as_deref()).await?;
+
+    if request.timeout_seconds.is_some_and(|t| t <= 0.0) {
+        return error_bad_request!("Timeout must be positive");
+    }
+
+    let mut tx = app_data.db().begin().await?;
+
+    if let Some(Some(_)) = lock_run(&mut tx, &request.solver, &request.run).await? {
+        return error_conflict!("Run was already started");
+    }
+
+    // runs may already exist if they were scheduled or received uploads before; the
+    // instance count of a scheduled run is determined by the server
+    sqlx::query(
+        r#"INSERT INTO SolverRun (run_uuid, solver_uuid, num_scheduled, solver_version, timeout_seconds, state, started_at, last_heartbeat)
+           VALUES (UNHEX(?), UNHEX(?), ?, ?, ?, ?, NOW(), NOW())
+           ON DUPLICATE KEY UPDATE
+            num_scheduled = IF(lease_seconds IS NULL, COALESCE(VALUES(num_scheduled), num_scheduled), num_scheduled),
+            solver_version = VALUES(solver_version),
+            timeout_seconds = VALUES(timeout_seconds),
+            state = VALUES(state),
+            started_at = VALUES(started_at),
+            last_heartbeat = VALUES(last_heartbeat)"#,
+    )
+    .bind(request.run.simple().to_string())
+    .bind(request.solver.simple().to_string())
+    .bind(request.num_scheduled)
+    .bind(request.solver_version.as_ref().map(|v| v.trim()))
+    .bind(request.timeout_seconds)
+    .bind(RunState::Running as u32)
+    .execute(&mut *tx)
+    .await?;
+
+    store_run_metadata(&mut tx, &request.run, &request.metadata).await?;
+
+    tx.commit().await?;
+    debug!("Started run {}", request.run);
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "heartbeat_timeout_seconds": HEARTBEAT_TIMEOUT_SECONDS,
+    })))
+}
+
+/// Signals that a running run is still alive
+pub async fn solver_run_heartbeat_handler(
+    State(app_data): State<Arc<AppState>>,
+    Json(request): Json<RunRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    authorize_solver(&app_data, Some(&request.solver), request.key.as_deref()).await?;
+
+    let mut tx = app_data.db().begin().await?;
+    lock_running_run(&mut tx, &request.solver, &request.run).await?;
+
+    sqlx::query(r#"UPDATE SolverRun SET last_heartbeat = NOW() WHERE run_uuid = UNHEX(?)"#)
+        .bind(request.run.simple().to_string())
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(serde_json::json!({"status": "success"})))
+}
+
+/// Ends a running run, either as finished or as aborted
+pub async fn solver_run_finish_handler(
+    State(app_data): State<Arc<AppState>>,
+    Json(request): Json<FinishRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    authorize_solver(&app_data, Some(&request.solver), request.key.as_deref()).await?;
+
+    let mut tx = app_data.db().begin().await?;
+    lock_running_run(&mut tx, &request.solver, &request.run).await?;
+
+    let state = if request.abort {
+        RunState::Aborted
+    } else {
+        RunState::Finished
+    };
+
+    sqlx::query(
+        r#"UPDATE SolverRun SET state = ?, finished_at = NOW(), last_heartbeat = NOW() WHERE run_uuid = UNHEX(?)"#,
+    )
+    .bind(state as u32)
+    .bind(request.run.simple().to_string())
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    debug!("Run {} is {state:?}", request.run);
+
+    Ok(Json(serde_json::json!({"status": "success"})))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::server::{app_state::DbPool, handlers::solver_run_schedule_handler};
+
+    fn solver() -> Uuid {
+        Uuid::parse_str("00000000-0000-0000-0002-000000000000").unwrap()
+    }
+
+    fn run() -> Uuid {
+        Uuid::parse_str("00000000-0000-0000-0001-000000000003").unwrap()
+    }
+
+    fn run_request() -> RunRequest {
+        RunRequest {
+            solver: solver(),
+            run: run(),
+            key: None,
+        }
+    }
+
+    fn finish_request(abort: bool) -> FinishRequest {
+        FinishRequest {
+            solver: solver(),
+            run: run(),
+            key: None,
+            abort,
+        }
+    }
+
+    fn status<T: IntoResponse>(result: HandlerResult<T>) -> StatusCode {
+        match result {
+            Ok(resp) => resp.into_response().status(),
+            Err(e) => e.into_response().status(),
+        }
+    }
+
+    #[sqlx::test(fixtures("instances", "solutions"))]
+    async fn run_lifecycle(db_pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(db_pool.clone()));
+
+        // heartbeats need a started run
+        let result = solver_run_heartbeat_handler(State(state.clone()), Json(run_request())).await;
+        assert_eq!(status(result), StatusCode::NOT_FOUND);
+
+        let start = StartRequest {
+            solver: solver(),
+            run: run(),
+            num_scheduled: Some(10),
+            solver_version: Some("1.2.3".into()),
+            timeout_seconds: Some(300.0),
+            ..Default::default()
+        };
+        let result = solver_run_start_handler(State(state.clone()), Json(start.clone())).await;
+        assert_eq!(status(result), StatusCode::OK);
+
+        let result = solver_run_start_handler(State(state.clone()), Json(start)).await;
+        assert_eq!(status(result), StatusCode::CONFLICT);
+
+        let result = solver_run_heartbeat_handler(State(state.clone()), Json(run_request())).await;
+        assert_eq!(status(result), StatusCode::OK);
+
+        // runs of other solvers cannot be controlled
+        let other_run = RunRequest {
+            run: Uuid::parse_str("00000000-0000-0000-0001-000000000002").unwrap(),
+            ..run_request()
+        };
+        let result = solver_run_heartbeat_handler(State(state.clone()), Json(other_run)).await;
+        assert_eq!(status(result), StatusCode::FORBIDDEN);
+
+        let result =
+            solver_run_finish_handler(State(state.clone()), Json(finish_request(true))).await;
+        assert_eq!(status(result), StatusCode::OK);
+
+        let result =
+            solver_run_finish_handler(State(state.clone()), Json(finish_request(false))).await;
+        assert_eq!(status(result), StatusCode::CONFLICT);
+
+        let (run_state, num_scheduled) = sqlx::query_as::<_, (Option<u32>, Option<u32>)>(
+            "SELECT state, num_scheduled FROM SolverRun WHERE run_uuid = UNHEX(?)",
+        )
+        .bind(run().simple().to_string())
+        .fetch_one(&db_pool)
+        .await?;
+        assert_eq!(run_state, Some(RunState::Aborted as u32));
+        assert_eq!(num_scheduled, Some(10));
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn scheduled_run_keeps_its_count(db_pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(db_pool.clone()));
+        let run = Uuid::parse_str("00000000-0000-0000-0001-000000000004").unwrap();
+
+        let schedule = serde_json::from_value(serde_json::json!({
+            "solver": solver(),
+            "run": run,
+            "budget": 2,
+        }))
+        .unwrap();
+        let result = solver_run_schedule_handler(State(state.clone()), Json(schedule)).await;
+        assert_eq!(status(result), StatusCode::OK);
+
+        let start = StartRequest {
+            solver: solver(),
+            run,
+            num_scheduled: Some(10),
+            ..Default::default()
+        };
+        let result = solver_run_start_handler(State(state.clone()), Json(start)).await;
+        assert_eq!(status(result), StatusCode::OK);
+
+        let (run_state, num_scheduled) = sqlx::query_as::<_, (Option<u32>, Option<u32>)>(
+            "SELECT state, num_scheduled FROM SolverRun WHERE run_uuid = UNHEX(?)",
+        )
+        .bind(run.simple().to_string())
+        .fetch_one(&db_pool)
+        .await?;
+        assert_eq!(run_state, Some(RunState::Running as u32));
+        assert_eq!(num_scheduled, Some(2));
+
+        Ok(())
+    }
+}
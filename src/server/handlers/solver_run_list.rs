@@ -1,6 +1,11 @@
 use std::collections::HashMap;
 
-use super::{common::*, solution_upload::SolverResultType};
+use super::{
+    common::*,
+    solution_upload::SolverResultType,
+    solver_run_lifecycle::{RunState, HEARTBEAT_TIMEOUT_SECONDS},
+    solver_run_metadata::RunMetadata,
+};
 use sqlx::types::chrono::{DateTime, Utc};
 use sqlx_conditional_queries::conditional_query_as;
 use uuid::Uuid;
@@ -33,6 +38,35 @@ struct RunModel {
     user_key: Option<String>,
 
     num_scheduled: Option<u32>,
+
+    state: Option<u32>,
+    solver_version: Option<String>,
+    timeout_seconds: Option<f64>,
+    started_at: Option<DateTime<Utc>>,
+    last_heartbeat: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+
+    git_commit: Option<String>,
+    build_flags: Option<String>,
+    command_line: Option<String>,
+    parameters: Option<String>,
+    cpu_model: Option<String>,
+    cpu_cores: Option<u32>,
+    memory_bytes: Option<u64>,
+}
+
+/// Lifecycle state as reported to clients
+#[derive(Clone, Copy, Serialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ReportedState {
+    /// The run was never started explicitly, but created by uploads
+    #[default]
+    Unknown,
+    Running,
+    /// Running, but without heartbeat for [`HEARTBEAT_TIMEOUT_SECONDS`]; probably crashed
+    Stale,
+    Finished,
+    Aborted,
 }
 
 #[derive(Clone, Serialize, Debug, Default)]
@@ -48,8 +82,28 @@ struct RunResponse {
     description: Option<String>,
     user_key: Option<String>,
 
+    state: ReportedState,
+    solver_version: Option<String>,
+    timeout_seconds: Option<f64>,
+    started_at: Option<String>,
+    last_heartbeat: Option<String>,
+    finished_at: Option<String>,
+    /// Time from start (or creation) until the run finished, its last heartbeat if it is
+    /// stale, or now if it is running; unknown for runs not started explicitly
+    wall_clock_seconds: Option<f64>,
+
+    #[serde(flatten)]
+    metadata: RunMetadata,
+
     num_scheduled: Option<u32>,
+    /// Number of instances with a result, of any kind
+    num_completed: u32,
+    /// Fraction of the scheduled instances with a result
+    progress: Option<f64>,
+    /// Number of solutions matching the lower bound of their instance, i.e. proven optimal
     num_optimal: u32,
+    /// Number of solutions matching the best known score, but not proven optimal
+    num_best: u32,
     num_suboptimal: u32,
     num_infeasible: u32,
     num_error: u32,
@@ -57,11 +111,20 @@ struct RunResponse {
     num_incomplete: u32,
 
     seconds_computed_optimal: f64,
+    seconds_computed_best: f64,
     seconds_computed_suboptimal: f64,
     seconds_computed_infeasible: f64,
     seconds_computed_error: f64,
     seconds_computed_timeout: f64,
     seconds_computed_incomplete: f64,
+
+    /// Largest peak memory reported by any solution of the run
+    peak_memory_bytes: Option<u64>,
+    /// Sum of the reported CPU and wall-clock times
+    cpu_seconds: Option<f64>,
+    wall_seconds: Option<f64>,
+    /// Number of solver executions that ended with a non-zero exit code or a signal
+    num_crashed: u32,
 }
 
 impl TryFrom<RunModel> for RunResponse {
@@ -82,23 +145,71 @@ impl TryFrom<RunModel> for RunResponse {
             None => return Err(anyhow::anyhow!("sr_id is required")),
         };
 
-        let created_at = match r.created_at {
-            Some(date) => date.to_rfc3339(),
-            None => return Err(anyhow::anyhow!("created_at is required")),
+        let Some(created) = r.created_at else {
+            return Err(anyhow::anyhow!("created_at is required"));
+        };
+
+        let now = Utc::now();
+        let state = match r.state.map(RunState::try_from).transpose()? {
+            None => ReportedState::Unknown,
+            Some(RunState::Running)
+                if r.last_heartbeat
+                    .is_some_and(|t| (now - t).num_seconds() > HEARTBEAT_TIMEOUT_SECONDS) =>
+            {
+                ReportedState::Stale
+            }
+            Some(RunState::Running) => ReportedState::Running,
+            Some(RunState::Finished) => ReportedState::Finished,
+            Some(RunState::Aborted) => ReportedState::Aborted,
+        };
+
+        let end = match state {
+            ReportedState::Unknown => None,
+            ReportedState::Running => Some(now),
+            ReportedState::Stale => r.last_heartbeat,
+            ReportedState::Finished | ReportedState::Aborted => r.finished_at,
+        };
+        let start = r.started_at.unwrap_or(created);
+        let wall_clock_seconds = end.map(|end| (end - start).num_milliseconds() as f64 / 1000.0);
+
+        let metadata = RunMetadata {
+            git_commit: r.git_commit,
+            build_flags: r.build_flags,
+            command_line: r.command_line,
+            parameters: r
+                .parameters
+                .as_deref()
+                .map(serde_json::from_str)
+                .transpose()?,
+            cpu_model: r.cpu_model,
+            cpu_cores: r.cpu_cores,
+            memory_bytes: r.memory_bytes,
         };
 
         Ok(RunResponse {
             sr_id,
             run_uuid,
             solver_uuid,
-            created_at,
+            created_at: created.to_rfc3339(),
             hide: r.hide != 0,
             name: r.name,
             description: r.description,
             user_key: r.user_key,
 
+            state,
+            solver_version: r.solver_version,
+            timeout_seconds: r.timeout_seconds,
+            started_at: r.started_at.map(|t| t.to_rfc3339()),
+            last_heartbeat: r.last_heartbeat.map(|t| t.to_rfc3339()),
+            finished_at: r.finished_at.map(|t| t.to_rfc3339()),
+            wall_clock_seconds,
+            metadata,
+
             num_scheduled: r.num_scheduled,
+            num_completed: 0,
+            progress: None,
             num_optimal: 0,
+            num_best: 0,
             num_suboptimal: 0,
             num_infeasible: 0,
             num_error: 0,
@@ -106,11 +217,17 @@ impl TryFrom<RunModel> for RunResponse {
             num_incomplete: 0,
 
             seconds_computed_optimal: 0.0,
+            seconds_computed_best: 0.0,
             seconds_computed_suboptimal: 0.0,
             seconds_computed_infeasible: 0.0,
             seconds_computed_error: 0.0,
             seconds_computed_timeout: 0.0,
             seconds_computed_incomplete: 0.0,
+
+            peak_memory_bytes: None,
+            cpu_seconds: None,
+            wall_seconds: None,
+            num_crashed: 0,
         })
     }
 }
@@ -124,6 +241,7 @@ struct Response {
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 enum SolutionTypes {
     Optimal,
+    Best,
     Feasible,
     Infeasible,
     Error,
@@ -196,6 +314,7 @@ async fn solution_count(
 
     struct OptRow {
         sr_id: i32,
+        proven: i64,
         count: i64,
         seconds_computed: f64,
     }
@@ -204,12 +323,13 @@ async fn solution_count(
     let num_opt_solutions = conditional_query_as!(
         OptRow,
         "SELECT 
-            sr.`sr_id`, COUNT(s.sid) as `count!`, SUM(s.seconds_computed) as `seconds_computed!`
+            sr.`sr_id`, CAST(COALESCE(i.lower_bound = s.score, 0) AS SIGNED) as `proven!`,
+            COUNT(s.sid) as `count!`, SUM(s.seconds_computed) as `seconds_computed!`
          FROM `Solution` s
          JOIN `SolverRun` sr ON s.`sr_uuid` = sr.`run_uuid`
          JOIN `Instance` i ON `s`.`instance_iid` = `i`.`iid`
          WHERE sr.`solver_uuid` = UNHEX({solver_uuid}) {#run_cond} {#inst_of_cond} AND s.`error_code` = {valid} AND i.best_score = s.score
-         GROUP BY sr.`sr_id`",
+         GROUP BY sr.`sr_id`, `proven!`",
         #run_cond = match &run_uuid {
             Some(_) => "AND sr.`run_uuid` = UNHEX({run_uuid})",
             None => "",
@@ -222,8 +342,14 @@ async fn solution_count(
     .fetch_all(app_data.db())
     .await?;
 
+    // a score matching the lower bound also matches the best score, so optimal solutions
+    // are exactly the best ones proven by the lower bound
     for row in num_opt_solutions {
-        let key = (row.sr_id as u32, SolutionTypes::Optimal);
+        let solution_type = match row.proven {
+            0 => SolutionTypes::Best,
+            _ => SolutionTypes::Optimal,
+        };
+        let key = (row.sr_id as u32, solution_type);
         hash_map.insert(
             key,
             CountTime {
@@ -236,6 +362,49 @@ async fn solution_count(
     Ok(hash_map)
 }
 
+#[derive(Clone, Copy, Debug, Default, sqlx::FromRow)]
+struct ResourceTotals {
+    sr_id: i32,
+    peak_memory_bytes: Option<u64>,
+    cpu_seconds: Option<f64>,
+    wall_seconds: Option<f64>,
+    num_crashed: i64,
+}
+
+async fn resource_totals(
+    opts: &FilterOptions,
+    app_data: &AppState,
+) -> HandlerResult<HashMap<u32, ResourceTotals>> {
+    let solver_uuid = opts.solver.simple().to_string();
+    let run_uuid = opts.run.map(|r| r.simple().to_string());
+    let instances_of_uuid = opts.instances_of.map(|r| r.simple().to_string());
+
+    let rows = sqlx::query_as::<_, ResourceTotals>(
+        r#"SELECT
+            sr.`sr_id`,
+            MAX(r.peak_memory_bytes) AS peak_memory_bytes,
+            SUM(r.cpu_seconds) AS cpu_seconds,
+            SUM(r.wall_seconds) AS wall_seconds,
+            COUNT(CASE WHEN r.exit_code <> 0 OR r.term_signal IS NOT NULL THEN 1 END) AS num_crashed
+         FROM SolutionResources r
+         JOIN `Solution` s ON r.solution_sid = s.sid
+         JOIN `SolverRun` sr ON s.`sr_uuid` = sr.`run_uuid`
+         WHERE sr.`solver_uuid` = UNHEX(?)
+           AND (? IS NULL OR sr.`run_uuid` = UNHEX(?))
+           AND (? IS NULL OR s.`instance_iid` IN (SELECT instance_iid FROM Solution WHERE sr_uuid = UNHEX(?)))
+         GROUP BY sr.`sr_id`"#,
+    )
+    .bind(solver_uuid)
+    .bind(&run_uuid)
+    .bind(&run_uuid)
+    .bind(&instances_of_uuid)
+    .bind(&instances_of_uuid)
+    .fetch_all(app_data.db())
+    .await?;
+
+    Ok(rows.into_iter().map(|r| (r.sr_id as u32, r)).collect())
+}
+
 pub async fn solver_run_list_handler(
     opts: Option<Query<FilterOptions>>,
     State(app_data): State<Arc<AppState>>,
@@ -246,7 +415,9 @@ pub async fn solver_run_list_handler(
     let run = opts.run.map(|r| r.simple().to_string());
     let run_models : Vec<_> = conditional_query_as!(RunModel,
         "SELECT 
-            sr_id, run_uuid, solver_uuid, name, description, user_key, num_scheduled, created_at, hide as 'hide!'
+            sr_id, run_uuid, solver_uuid, name, description, user_key, num_scheduled, created_at, hide as 'hide!',
+            state, solver_version, timeout_seconds, started_at, last_heartbeat, finished_at,
+            git_commit, build_flags, command_line, CAST(parameters AS CHAR) AS parameters, cpu_model, cpu_cores, memory_bytes
         FROM SolverRun sr
         WHERE solver_uuid = UNHEX({solver}) {#run_cond} {#hidden_cond}
         ORDER BY created_at DESC",
@@ -261,6 +432,7 @@ pub async fn solver_run_list_handler(
         ).fetch_all(app_data.db()).await?;
 
     let counts = solution_count(&opts, &app_data).await?;
+    let resources = resource_totals(&opts, &app_data).await?;
 
     let mut run_response = Vec::with_capacity(run_models.len());
     for r in run_models {
@@ -277,18 +449,39 @@ pub async fn solver_run_list_handler(
             };
         }
 
-        // we previously count Feasible solutions as Optimal+Suboptimal
-        // now subtract optimals from them ...
+        // we previously count Feasible solutions as Optimal+Best+Suboptimal
+        // now subtract optimals and best ones from them ...
         update_resp!(suboptimal, +=, SolutionTypes::Feasible);
         update_resp!(suboptimal, -=, SolutionTypes::Optimal);
+        update_resp!(suboptimal, -=, SolutionTypes::Best);
 
         update_resp!(optimal, +=, SolutionTypes::Optimal);
+        update_resp!(best, +=, SolutionTypes::Best);
         update_resp!(infeasible, +=, SolutionTypes::Infeasible);
         update_resp!(error, +=, SolutionTypes::Error);
         update_resp!(timeout, +=, SolutionTypes::Timeout);
 
         update_resp!(incomplete, +=, SolutionTypes::IncompleteOutput);
 
+        resp.num_completed = resp.num_optimal
+            + resp.num_best
+            + resp.num_suboptimal
+            + resp.num_infeasible
+            + resp.num_error
+            + resp.num_timeout
+            + resp.num_incomplete;
+        resp.progress = resp
+            .num_scheduled
+            .filter(|&n| n > 0)
+            .map(|n| resp.num_completed as f64 / n as f64);
+
+        if let Some(totals) = resources.get(&resp.sr_id) {
+            resp.peak_memory_bytes = totals.peak_memory_bytes;
+            resp.cpu_seconds = totals.cpu_seconds;
+            resp.wall_seconds = totals.wall_seconds;
+            resp.num_crashed = totals.num_crashed as u32;
+        }
+
         run_response.push(resp);
     }
 
@@ -0,0 +1,91 @@
+use uuid::Uuid;
+
+use super::common::*;
+
+/// Maximum length of a git commit hash; enough for SHA-256 repositories
+const MAX_GIT_COMMIT_LEN: usize = 64;
+
+/// Setup of a run needed to reproduce its results. Fields that are not provided keep
+/// their stored value, so the metadata can be sent with every upload of a run.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct RunMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_flags: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_line: Option<String>,
+    /// Solver parameters as flat or nested JSON object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Map<String, serde_json::Value>>,
+
+    /// Also accepted as `hardware`, which is what run starts called it
+    #[serde(default, alias = "hardware", skip_serializing_if = "Option::is_none")]
+    pub cpu_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_cores: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_bytes: Option<u64>,
+}
+
+impl RunMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn check_validity(&self) -> HandlerResult<()> {
+        if let Some(commit) = &self.git_commit {
+            if commit.is_empty()
+                || commit.len() > MAX_GIT_COMMIT_LEN
+                || !commit.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return error_bad_request!("git_commit must be a hexadecimal commit hash");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Updates the provided metadata fields of an existing run
+pub(super) async fn store_run_metadata(
+    conn: &mut sqlx::MySqlConnection,
+    run: &Uuid,
+    metadata: &RunMetadata,
+) -> HandlerResult<()> {
+    if metadata.is_empty() {
+        return Ok(());
+    }
+
+    metadata.check_validity()?;
+
+    let parameters = metadata
+        .parameters
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
+    sqlx::query(
+        r#"UPDATE SolverRun SET
+            git_commit = COALESCE(?, git_commit),
+            build_flags = COALESCE(?, build_flags),
+            command_line = COALESCE(?, command_line),
+            parameters = COALESCE(CAST(? AS JSON), parameters),
+            cpu_model = COALESCE(?, cpu_model),
+            cpu_cores = COALESCE(?, cpu_cores),
+            memory_bytes = COALESCE(?, memory_bytes)
+           WHERE run_uuid = UNHEX(?)"#,
+    )
+    .bind(metadata.git_commit.as_ref().map(|c| c.to_ascii_lowercase()))
+    .bind(&metadata.build_flags)
+    .bind(&metadata.command_line)
+    .bind(parameters)
+    .bind(metadata.cpu_model.as_ref().map(|m| m.trim()))
+    .bind(metadata.cpu_cores)
+    .bind(metadata.memory_bytes)
+    .bind(run.simple().to_string())
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,430 @@
+use sqlx::{MySql, QueryBuilder};
+use tracing::debug;
+use uuid::Uuid;
+
+use super::{
+    common::*,
+    instance_list::{matching_instances_query, FilterOptions},
+    solver_register::authorize_solver,
+    solver_run_lifecycle::RunState,
+};
+use crate::server::app_state::DbTransaction;
+
+/// Number of rows written per INSERT statement when scheduling large runs
+const INSERT_CHUNK_SIZE: usize = 10_000;
+
+fn default_lease_seconds() -> u32 {
+    600
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct ScheduleRequest {
+    solver: Uuid,
+    run: Uuid,
+
+    /// Key obtained when registering the solver
+    #[serde(default)]
+    key: Option<String>,
+
+    /// Instances to attempt in the order given by the filter; pagination is ignored
+    #[serde(default)]
+    filter: FilterOptions,
+
+    /// Maximum number of instances scheduled for the run
+    #[serde(default)]
+    budget: Option<u32>,
+
+    /// Time a runner has to upload a result before its instance is leased to another one
+    #[serde(default = "default_lease_seconds")]
+    lease_seconds: u32,
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct LeaseRequest {
+    solver: Uuid,
+    run: Uuid,
+
+    /// Key obtained when registering the solver
+    #[serde(default)]
+    key: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum LeaseResponse {
+    /// The runner should attempt `iid` and upload a result within `lease_seconds`
+    Leased {
+        iid: i32,
+        lease_seconds: u32,
+        attempt: u32,
+    },
+    /// All remaining instances are leased by other runners; retry once their leases expire
+    Pending { num_leased: i64 },
+    /// Every scheduled instance has a result, or the run has ended
+    Done,
+}
+
+/// Rejects runs that belong to another solver and returns the lease timeout of the run,
+/// if it was scheduled
+async fn check_run(
+    conn: &mut sqlx::MySqlConnection,
+    solver: &Uuid,
+    run: &Uuid,
+) -> HandlerResult<Option<Option<u32>>> {
+    let Some((owner, lease_seconds)) = sqlx::query_as::<_, (Option<String>, Option<u32>)>(
+        r#"SELECT LOWER(HEX(solver_uuid)), lease_seconds FROM SolverRun WHERE run_uuid = UNHEX(?)"#,
+    )
+    .bind(run.simple().to_string())
+    .fetch_optional(conn)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    if owner != Some(solver.simple().to_string()) {
+        return error_forbidden!("Run belongs to a different solver");
+    }
+
+    Ok(Some(lease_seconds))
+}
+
+async fn insert_schedule(
+    tx: &mut DbTransaction<'_>,
+    run: &Uuid,
+    instances: &[i32],
+) -> HandlerResult<()> {
+    let run = run.simple().to_string();
+
+    for (chunk_idx, chunk) in instances.chunks(INSERT_CHUNK_SIZE).enumerate() {
+        let mut builder: QueryBuilder<MySql> =
+            QueryBuilder::new("INSERT INTO ScheduledInstance (sr_uuid, instance_iid, position) ");
+
+        builder.push_values(chunk.iter().enumerate(), |mut b, (i, iid)| {
+            b.push("UNHEX(")
+                .push_bind_unseparated(&run)
+                .push_unseparated(")")
+                .push_bind(iid)
+                .push_bind((chunk_idx * INSERT_CHUNK_SIZE + i) as u32);
+        });
+
+        builder.build().execute(&mut **tx).await?;
+    }
+
+    // instances the run already has a result for need not be attempted again
+    sqlx::query(
+        r#"UPDATE ScheduledInstance si
+           JOIN Solution s ON s.sr_uuid = si.sr_uuid AND s.instance_iid = si.instance_iid
+           SET si.completed_at = s.created_at
+           WHERE si.sr_uuid = UNHEX(?)"#,
+    )
+    .bind(&run)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Marks a scheduled instance as completed; called whenever a result is uploaded
+pub(super) async fn complete_scheduled_instance(
+    tx: &mut DbTransaction<'_>,
+    run: &Uuid,
+    iid: u32,
+) -> HandlerResult<()> {
+    sqlx::query(
+        r#"UPDATE ScheduledInstance SET completed_at = NOW(), leased_until = NULL
+           WHERE sr_uuid = UNHEX(?) AND instance_iid = ? AND completed_at IS NULL"#,
+    )
+    .bind(run.simple().to_string())
+    .bind(iid)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Opens a run whose instances are handed out by the server: all instances matching the
+/// filter (at most `budget` many) are scheduled and can then be leased one at a time
+pub async fn solver_run_schedule_handler(
+    State(app_data): State<Arc<AppState>>,
+    Json(request): Json<ScheduleRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    authorize_solver(&app_data, Some(&request.solver), request.key.as_deref()).await?;
+    request.filter.check_validity()?;
+
+    if request.lease_seconds == 0 {
+        return error_bad_request!("Lease timeout must be positive");
+    }
+
+    let mut instances = matching_instances_query("SELECT i.iid", &request.filter)?
+        .build_query_scalar::<i32>()
+        .fetch_all(app_data.db())
+        .await?;
+
+    if let Some(budget) = request.budget {
+        instances.truncate(budget as usize);
+    }
+
+    let mut tx = app_data.db().begin().await?;
+
+    if let Some(Some(_)) = check_run(&mut tx, &request.solver, &request.run).await? {
+        return error_conflict!("Run is already scheduled");
+    }
+
+    sqlx::query(
+        r#"INSERT INTO SolverRun (run_uuid, solver_uuid) VALUES (UNHEX(?), UNHEX(?))
+           ON DUPLICATE KEY UPDATE run_uuid = run_uuid"#,
+    )
+    .bind(request.run.simple().to_string())
+    .bind(request.solver.simple().to_string())
+    .execute(&mut *tx)
+    .await?;
+
+    // the check above does not lock; of concurrent requests to schedule the same run,
+    // only the first one to update the row proceeds while the others wait for it
+    let updated = sqlx::query(
+        r#"UPDATE SolverRun SET num_scheduled = ?, lease_seconds = ?
+           WHERE run_uuid = UNHEX(?) AND solver_uuid = UNHEX(?) AND lease_seconds IS NULL"#,
+    )
+    .bind(instances.len() as u32)
+    .bind(request.lease_seconds)
+    .bind(request.run.simple().to_string())
+    .bind(request.solver.simple().to_string())
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if updated == 0 {
+        return error_conflict!("Run is already scheduled");
+    }
+
+    insert_schedule(&mut tx, &request.run, &instances).await?;
+    tx.commit().await?;
+
+    debug!(
+        "Scheduled {} instances for run {}",
+        instances.len(),
+        request.run
+    );
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "num_scheduled": instances.len(),
+    })))
+}
+
+/// Leases the next instance of a scheduled run that has neither a result nor a valid lease.
+/// Expired leases are re-issued, so instances of crashed runners are eventually retried.
+pub async fn solver_run_lease_handler(
+    State(app_data): State<Arc<AppState>>,
+    Json(request): Json<LeaseRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    authorize_solver(&app_data, Some(&request.solver), request.key.as_deref()).await?;
+
+    let mut tx = app_data.db().begin().await?;
+
+    let Some(Some(lease_seconds)) = check_run(&mut tx, &request.solver, &request.run).await? else {
+        return error_not_found!("Run is not scheduled");
+    };
+
+    let run = request.run.simple().to_string();
+
+    // runs that were ended explicitly do not hand out further instances
+    let state = sqlx::query_scalar::<_, Option<u32>>(
+        r#"SELECT state FROM SolverRun WHERE run_uuid = UNHEX(?)"#,
+    )
+    .bind(&run)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if matches!(
+        state.map(RunState::try_from).transpose()?,
+        Some(RunState::Finished | RunState::Aborted)
+    ) {
+        return Ok(Json(LeaseResponse::Done));
+    }
+
+    // rows locked by concurrent leases are skipped, so runners never receive the same instance
+    let next = sqlx::query_as::<_, (i32, u32)>(
+        r#"SELECT instance_iid, num_leases FROM ScheduledInstance
+           WHERE sr_uuid = UNHEX(?) AND completed_at IS NULL
+             AND (leased_until IS NULL OR leased_until <= NOW())
+           ORDER BY position
+           LIMIT 1
+           FOR UPDATE SKIP LOCKED"#,
+    )
+    .bind(&run)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let response = match next {
+        Some((iid, num_leases)) => {
+            sqlx::query(
+                r#"UPDATE ScheduledInstance
+                   SET leased_until = NOW() + INTERVAL ? SECOND, num_leases = num_leases + 1
+                   WHERE sr_uuid = UNHEX(?) AND instance_iid = ?"#,
+            )
+            .bind(lease_seconds)
+            .bind(&run)
+            .bind(iid)
+            .execute(&mut *tx)
+            .await?;
+
+            LeaseResponse::Leased {
+                iid,
+                lease_seconds,
+                attempt: num_leases + 1,
+            }
+        }
+        None => {
+            let num_leased = sqlx::query_scalar::<_, i64>(
+                r#"SELECT COUNT(*) FROM ScheduledInstance WHERE sr_uuid = UNHEX(?) AND completed_at IS NULL"#,
+            )
+            .bind(&run)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if num_leased > 0 {
+                LeaseResponse::Pending { num_leased }
+            } else {
+                LeaseResponse::Done
+            }
+        }
+    };
+
+    tx.commit().await?;
+
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::server::app_state::DbPool;
+    use crate::server::handlers::solution_upload::{
+        solution_upload_handler, SolutionUploadRequest, SolverResult,
+    };
+
+    fn solver() -> Uuid {
+        Uuid::parse_str("00000000-0000-0000-0002-000000000003").unwrap()
+    }
+
+    fn run() -> Uuid {
+        Uuid::parse_str("00000000-0000-0000-0001-000000000003").unwrap()
+    }
+
+    async fn lease(state: &Arc<AppState>) -> serde_json::Value {
+        let request = LeaseRequest {
+            solver: solver(),
+            run: run(),
+            key: None,
+        };
+
+        let resp = solver_run_lease_handler(State(state.clone()), Json(request))
+            .await
+            .unwrap()
+            .into_response();
+        let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    async fn upload_timeout(state: &Arc<AppState>, iid: u32) {
+        let request = SolutionUploadRequest {
+            instance_id: iid,
+            run_uuid: run(),
+            solver_uuid: Some(solver()),
+            solver_key: None,
+            seconds_computed: Some(1.0),
+            result: SolverResult::Timeout,
+            dry_run: false,
+            prune_redundant: false,
+            resources: Default::default(),
+            run_metadata: None,
+        };
+
+        solution_upload_handler(State(state.clone()), Json(request))
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn schedule_and_lease(db_pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(db_pool.clone()));
+
+        let request = ScheduleRequest {
+            solver: solver(),
+            run: run(),
+            key: None,
+            filter: FilterOptions::default(),
+            budget: Some(2),
+            lease_seconds: 60,
+        };
+
+        solver_run_schedule_handler(State(state.clone()), Json(request.clone()))
+            .await
+            .unwrap();
+
+        // a run can only be scheduled once
+        let status = solver_run_schedule_handler(State(state.clone()), Json(request))
+            .await
+            .err()
+            .unwrap()
+            .into_response()
+            .status();
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let first = lease(&state).await;
+        let second = lease(&state).await;
+        assert_eq!(first["status"], "leased");
+        assert_eq!(second["status"], "leased");
+        assert_ne!(first["iid"], second["iid"]);
+        assert_eq!(lease(&state).await["status"], "pending");
+
+        let first_iid = first["iid"].as_u64().unwrap() as u32;
+        upload_timeout(&state, first_iid).await;
+
+        // the lease of the second instance expires, so it is handed out again
+        sqlx::query("UPDATE ScheduledInstance SET leased_until = NOW() - INTERVAL 1 SECOND")
+            .execute(&db_pool)
+            .await?;
+
+        let retry = lease(&state).await;
+        assert_eq!(retry["iid"], second["iid"]);
+        assert_eq!(retry["attempt"], 2);
+
+        upload_timeout(&state, retry["iid"].as_u64().unwrap() as u32).await;
+        assert_eq!(lease(&state).await["status"], "done");
+
+        Ok(())
+    }
+
+    #[sqlx::test(fixtures("instances"))]
+    async fn concurrent_schedule(db_pool: DbPool) -> sqlx::Result<()> {
+        let state = Arc::new(AppState::new(db_pool.clone()));
+
+        let request = ScheduleRequest {
+            solver: solver(),
+            run: run(),
+            key: None,
+            filter: FilterOptions::default(),
+            budget: None,
+            lease_seconds: 60,
+        };
+
+        let (first, second) = tokio::join!(
+            solver_run_schedule_handler(State(state.clone()), Json(request.clone())),
+            solver_run_schedule_handler(State(state.clone()), Json(request)),
+        );
+
+        let mut statuses = [first, second].map(|result| match result {
+            Ok(resp) => resp.into_response().status(),
+            Err(err) => err.into_response().status(),
+        });
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+
+        Ok(())
+    }
+}
@@ -1,4 +1,10 @@
 pub mod app_error;
 pub mod app_state;
+pub mod archive;
+pub mod auth;
+pub mod blob_gc;
+pub mod blob_reencode;
+pub mod blob_store;
+pub mod extract;
 pub mod handlers;
 pub mod router;
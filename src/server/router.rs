@@ -1,8 +1,13 @@
-use super::{app_state::AppState, handlers::*};
+use super::{
+    app_state::AppState,
+    auth::{authorize, Scope},
+    handlers::*,
+};
 use axum::{
-    extract::DefaultBodyLimit,
+    extract::{DefaultBodyLimit, Request, State},
     handler::HandlerWithoutStateExt,
     http::StatusCode,
+    middleware::{self, Next},
     routing::{get, post},
     Router,
 };
@@ -15,34 +20,63 @@ async fn handle_404() -> (StatusCode, &'static str) {
     (StatusCode::NOT_FOUND, "Not found")
 }
 
+/// Wraps `routes` into a layer that only lets requests pass whose bearer token grants `scope`
+fn with_scope(
+    app_state: &Arc<AppState>,
+    scope: Scope,
+    routes: Router<Arc<AppState>>,
+) -> Router<Arc<AppState>> {
+    routes.route_layer(middleware::from_fn_with_state(
+        app_state.clone(),
+        move |state: State<Arc<AppState>>, request: Request, next: Next| {
+            authorize(scope, state, request, next)
+        },
+    ))
+}
+
 #[rustfmt::skip]
 pub fn create_router(app_state: Arc<AppState>) -> Router {
-    // needs to be mutable to allow adding routes based on feature flags
-    #[allow(unused_mut)]
-    let mut router = Router::new();
-    #[cfg(feature = "admin-api")]
-    {
-        router = router
-            .route("/api/instances/new", post(instance_upload_handler))
-            .route("/api/instances/update", post(instance_update_meta_handler))
-            .route("/api/instances/delete/:id", get(instance_delete_handler))
-            .route("/api/tags/new", post(tag_create_handler))
-            .route("/api/debug_restart", get(debug_restart_handler));
-    }
-
-    router = router
+    let admin_routes = Router::new()
+        .route("/api/instances/update", post(instance_update_meta_handler))
+        .route("/api/instances/compute_meta", post(instance_compute_meta_handler))
+        .route("/api/instances/delete/:id", get(instance_delete_handler))
+        .route("/api/tags/new", post(tag_create_handler))
+        .route("/api/debug_restart", get(debug_restart_handler));
+
+    let instance_upload_routes = Router::new()
+        .route("/api/instances/new", post(instance_upload_handler))
+        .route("/api/instances/upload", post(instance_stream_upload_handler).layer(DefaultBodyLimit::disable()));
+
+    let solution_upload_routes = Router::new()
+        .route("/api/solutions/new", post(solution_upload_handler))
+        .route("/api/solutions/batch", post(solution_batch_upload_handler))
+        .route("/api/solver_run/schedule", post(solver_run_schedule_handler))
+        .route("/api/solver_run/lease", post(solver_run_lease_handler))
+        .route("/api/solver_run/start", post(solver_run_start_handler))
+        .route("/api/solver_run/heartbeat", post(solver_run_heartbeat_handler))
+        .route("/api/solver_run/finish", post(solver_run_finish_handler));
+
+    let router = Router::new()
+        .merge(with_scope(&app_state, Scope::Admin, admin_routes))
+        .merge(with_scope(&app_state, Scope::InstanceUpload, instance_upload_routes))
+        .merge(with_scope(&app_state, Scope::SolutionUpload, solution_upload_routes))
         .route("/api/status", get(status_handler))
         .route("/api/instances/list", post(instance_list_handler))
         .route("/api/instances/list_download", get(instance_list_download_handler))
         .route("/api/instances/download/:id", get(instance_download_handler))
+        .route("/api/instances/archive", get(instance_archive_handler))
         .route("/api/instance_solutions", get(instance_solutions_handler))
         .route("/api/tags", get(tag_list_handler))
-        .route("/api/solutions/new", post(solution_upload_handler))
         .route("/api/solutions/download", get(solution_download_handler))
+        .route("/api/solutions/best", get(solution_best_handler))
+        .route("/api/solutions/diff", post(solution_diff_handler))
         .route("/api/solutions/hashes/:solver_uuid", get(solution_hash_list_handler))
         .route("/api/solver_run/list", get(solver_run_list_handler))
         .route("/api/solver_run/performance", post(solver_run_performance_handler))
-        .route("/api/solver_run/annotate", get(solver_run_annotate_handler));
+        .route("/api/solver_run/compare", post(solver_run_compare_handler))
+        .route("/api/solver_run/annotate", post(solver_run_annotate_handler))
+        .route("/api/solver_run/archive", get(solver_run_archive_handler))
+        .route("/api/solvers/register", post(solver_register_handler));
 
     let service_404 = handle_404.into_service();
     router